@@ -1,8 +1,5 @@
-DROP TABLE `meta`;
-DROP TABLE `relation`;
-DROP TABLE `instances`;
-DROP TABLE `task`;
-DROP TABLE `task_error`;
+-- The whole schema of the newest version for reference only.
+-- Tables are created and upgraded by the migrations under `migrations/`, see `Migrator::migrate`.
 
 create TABLE `meta` (
 	`meta_type`	VARCHAR ( 10 ) NOT NULL,
@@ -34,9 +31,15 @@ CREATE TABLE `instances` (
   `create_time` datetime NOT NULL,
   `sys_context` text DEFAULT NULL,
   `from_key` varchar(256) NOT NULL COMMENT 'meta|id|para|sta_ver',
+  `content_ref` varchar(255) DEFAULT NULL COMMENT 'where the overflowed content saved in the blob store',
+  `content_codec` TINYINT NOT NULL DEFAULT 0 COMMENT 'how the content is compressed, 0: plain',
+  `delete_time` datetime DEFAULT NULL COMMENT 'soft deleted when it is not null',
+  `delete_by` TINYINT NOT NULL DEFAULT 0 COMMENT 'who soft deleted it, 1: user, 2: retention',
   PRIMARY KEY (`ins_key`,`state_version`),
   UNIQUE KEY `instances_un` (`ins_key`,`from_key`),
-  KEY `instances_create_time_IDX` (`create_time`) USING BTREE
+  KEY `instances_create_time_IDX` (`create_time`) USING BTREE,
+  KEY `instances_from_key_IDX` (`from_key`) USING BTREE,
+  KEY `instances_delete_time_IDX` (`delete_time`) USING BTREE
 ) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
 
 create TABLE `task` (
@@ -49,9 +52,15 @@ create TABLE `task` (
 	`create_time`	DATETIME NOT NULL,
 	`execute_time`	DATETIME NOT NULL,
 	`retried_times`	SMALLINT NOT NULL,
+	`data_codec`	TINYINT NOT NULL DEFAULT 0 COMMENT 'how the data is compressed, 0: plain',
+	`lease_owner`	VARCHAR ( 64 ) DEFAULT NULL COMMENT 'the worker which claimed the task',
+	`lease_expire`	DATETIME(6) DEFAULT NULL COMMENT 'others can claim the task after it',
+	`priority`	TINYINT NOT NULL DEFAULT 0 COMMENT 'the greater is executed first',
 	UNIQUE KEY `task_un` (`task_key`,`task_type`,`task_for`),
 	PRIMARY KEY(`task_id`),
-	KEY `task_create_time_IDX` (`create_time`,`task_state`) USING BTREE
+	KEY `task_create_time_IDX` (`create_time`,`task_state`) USING BTREE,
+	KEY `task_lease_IDX` (`lease_owner`,`lease_expire`) USING BTREE,
+	KEY `task_overdue_IDX` (`task_state`,`priority`,`execute_time`) USING BTREE
 )ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
 
 create TABLE `task_error` (
@@ -62,6 +71,7 @@ create TABLE `task_error` (
 	`data`	TEXT NOT NULL,
 	`create_time`	DATETIME NOT NULL,
 	`msg`	VARCHAR ( 255 ) NOT NULL,
+	`priority`	TINYINT NOT NULL DEFAULT 0 COMMENT 'kept for the requeued task',
 	UNIQUE KEY `task_un` (`task_key`,`task_type`,`task_for`),
 	PRIMARY KEY(`task_id`)
 )ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
@@ -0,0 +1 @@
+ALTER TABLE `instances` DROP INDEX `instances_from_key_IDX`;
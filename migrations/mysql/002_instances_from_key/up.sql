@@ -0,0 +1 @@
+ALTER TABLE `instances` ADD INDEX `instances_from_key_IDX` (`from_key`) USING BTREE;
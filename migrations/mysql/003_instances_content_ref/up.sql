@@ -0,0 +1 @@
+ALTER TABLE `instances` ADD COLUMN `content_ref` varchar(255) DEFAULT NULL COMMENT 'where the overflowed content saved in the blob store';
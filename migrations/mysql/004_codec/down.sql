@@ -0,0 +1,2 @@
+ALTER TABLE `task` DROP COLUMN `data_codec`;
+ALTER TABLE `instances` DROP COLUMN `content_codec`;
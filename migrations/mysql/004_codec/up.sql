@@ -0,0 +1,2 @@
+ALTER TABLE `instances` ADD COLUMN `content_codec` TINYINT NOT NULL DEFAULT 0 COMMENT 'how the content is compressed, 0: plain';
+ALTER TABLE `task` ADD COLUMN `data_codec` TINYINT NOT NULL DEFAULT 0 COMMENT 'how the data is compressed, 0: plain';
@@ -0,0 +1,2 @@
+ALTER TABLE `instances` DROP INDEX `instances_delete_time_IDX`;
+ALTER TABLE `instances` DROP COLUMN `delete_time`;
@@ -0,0 +1,2 @@
+ALTER TABLE `instances` ADD COLUMN `delete_time` datetime DEFAULT NULL COMMENT 'soft deleted when it is not null';
+ALTER TABLE `instances` ADD INDEX `instances_delete_time_IDX` (`delete_time`) USING BTREE;
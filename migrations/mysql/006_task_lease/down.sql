@@ -0,0 +1,3 @@
+ALTER TABLE `task` DROP INDEX `task_lease_IDX`;
+ALTER TABLE `task` DROP COLUMN `lease_expire`;
+ALTER TABLE `task` DROP COLUMN `lease_owner`;
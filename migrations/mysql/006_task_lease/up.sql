@@ -0,0 +1,3 @@
+ALTER TABLE `task` ADD COLUMN `lease_owner` VARCHAR(64) DEFAULT NULL COMMENT 'the worker which claimed the task';
+ALTER TABLE `task` ADD COLUMN `lease_expire` DATETIME(6) DEFAULT NULL COMMENT 'others can claim the task after it';
+ALTER TABLE `task` ADD INDEX `task_lease_IDX` (`lease_owner`,`lease_expire`) USING BTREE;
@@ -0,0 +1,2 @@
+ALTER TABLE `task` DROP INDEX `task_overdue_IDX`;
+ALTER TABLE `task` DROP COLUMN `priority`;
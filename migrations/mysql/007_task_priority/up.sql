@@ -0,0 +1,2 @@
+ALTER TABLE `task` ADD COLUMN `priority` TINYINT NOT NULL DEFAULT 0 COMMENT 'the greater is executed first';
+ALTER TABLE `task` ADD INDEX `task_overdue_IDX` (`task_state`,`priority`,`execute_time`) USING BTREE;
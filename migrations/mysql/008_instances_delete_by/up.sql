@@ -0,0 +1,2 @@
+ALTER TABLE `instances` ADD COLUMN `delete_by` TINYINT NOT NULL DEFAULT 0 COMMENT 'who soft deleted it, 1: user, 2: retention';
+UPDATE `instances` SET `delete_by` = 1 WHERE `delete_time` is not null;
@@ -0,0 +1 @@
+ALTER TABLE `task_error` DROP COLUMN `priority`;
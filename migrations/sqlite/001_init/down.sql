@@ -0,0 +1,5 @@
+DROP TABLE IF EXISTS `task_error`;
+DROP TABLE IF EXISTS `task`;
+DROP TABLE IF EXISTS `instances`;
+DROP TABLE IF EXISTS `relation`;
+DROP TABLE IF EXISTS `meta`;
@@ -0,0 +1,61 @@
+CREATE TABLE IF NOT EXISTS `meta` (
+	`meta_type`	VARCHAR ( 10 ) NOT NULL,
+	`meta_key`	VARCHAR ( 255 ) NOT NULL,
+	`description`	VARCHAR ( 1023 ),
+	`version`	INTEGER NOT NULL,
+	`states`	VARCHAR ( 1023 ),
+	`fields`	VARCHAR ( 1023 ),
+	`config`    VARCHAR(2047) DEFAULT '{}' NOT NULL,
+	`flag`      INTEGER DEFAULT 1 NOT NULL,
+	`create_time`	DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	PRIMARY KEY(`meta_type`,`meta_key`,`version`)
+);
+
+CREATE TABLE IF NOT EXISTS `relation` (
+	`from_meta`	VARCHAR ( 255 ) NOT NULL,
+	`to_meta`	VARCHAR ( 255 ) NOT NULL,
+	`settings`  VARCHAR ( 2047 ) NOT NULL,
+	`flag`      INTEGER DEFAULT 1 NOT NULL,
+	PRIMARY KEY(`from_meta`,`to_meta`)
+);
+
+CREATE TABLE IF NOT EXISTS `instances` (
+	`ins_key` VARCHAR ( 256 ) NOT NULL,
+	`content` TEXT NOT NULL,
+	`context` TEXT DEFAULT NULL,
+	`states` TEXT DEFAULT NULL,
+	`state_version` INTEGER NOT NULL,
+	`create_time` DATETIME NOT NULL,
+	`sys_context` TEXT DEFAULT NULL,
+	`from_key` VARCHAR ( 256 ) NOT NULL,
+	PRIMARY KEY (`ins_key`,`state_version`),
+	CONSTRAINT `instances_un` UNIQUE (`ins_key`,`from_key`)
+);
+CREATE INDEX IF NOT EXISTS `instances_create_time_IDX` ON `instances` (`create_time`);
+
+CREATE TABLE IF NOT EXISTS `task` (
+	`task_id`	CHAR ( 40 ) NOT NULL,
+	`task_key`	VARCHAR ( 511 ) NOT NULL,
+	`task_type`	TINYINT NOT NULL,
+	`task_for`	VARCHAR ( 255 ) NOT NULL,
+	`task_state`	TINYINT NOT NULL,
+	`data`	TEXT NOT NULL,
+	`create_time`	DATETIME NOT NULL,
+	`execute_time`	DATETIME NOT NULL,
+	`retried_times`	SMALLINT NOT NULL,
+	PRIMARY KEY(`task_id`),
+	CONSTRAINT `task_un` UNIQUE (`task_key`,`task_type`,`task_for`)
+);
+CREATE INDEX IF NOT EXISTS `task_create_time_IDX` ON `task` (`create_time`,`task_state`);
+
+CREATE TABLE IF NOT EXISTS `task_error` (
+	`task_id`	CHAR ( 40 ) NOT NULL,
+	`task_key`	VARCHAR ( 511 ) NOT NULL,
+	`task_type`	TINYINT NOT NULL,
+	`task_for`	VARCHAR ( 255 ) NOT NULL,
+	`data`	TEXT NOT NULL,
+	`create_time`	DATETIME NOT NULL,
+	`msg`	VARCHAR ( 255 ) NOT NULL,
+	PRIMARY KEY(`task_id`),
+	CONSTRAINT `task_error_un` UNIQUE (`task_key`,`task_type`,`task_for`)
+);
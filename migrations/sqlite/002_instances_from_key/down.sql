@@ -0,0 +1 @@
+DROP INDEX IF EXISTS `instances_from_key_IDX`;
@@ -0,0 +1 @@
+CREATE INDEX IF NOT EXISTS `instances_from_key_IDX` ON `instances` (`from_key`);
@@ -0,0 +1 @@
+ALTER TABLE `instances` ADD COLUMN `content_ref` VARCHAR ( 255 ) DEFAULT NULL;
@@ -0,0 +1,39 @@
+-- no `DROP COLUMN` before sqlite 3.35, so rebuild the tables
+CREATE TABLE `task_old` (
+	`task_id`	CHAR ( 40 ) NOT NULL,
+	`task_key`	VARCHAR ( 511 ) NOT NULL,
+	`task_type`	TINYINT NOT NULL,
+	`task_for`	VARCHAR ( 255 ) NOT NULL,
+	`task_state`	TINYINT NOT NULL,
+	`data`	TEXT NOT NULL,
+	`create_time`	DATETIME NOT NULL,
+	`execute_time`	DATETIME NOT NULL,
+	`retried_times`	SMALLINT NOT NULL,
+	PRIMARY KEY(`task_id`),
+	CONSTRAINT `task_un` UNIQUE (`task_key`,`task_type`,`task_for`)
+);
+INSERT INTO `task_old`
+	SELECT task_id, task_key, task_type, task_for, task_state, `data`, create_time, execute_time, retried_times FROM `task`;
+DROP TABLE `task`;
+ALTER TABLE `task_old` RENAME TO `task`;
+CREATE INDEX IF NOT EXISTS `task_create_time_IDX` ON `task` (`create_time`,`task_state`);
+
+CREATE TABLE `instances_old` (
+	`ins_key` VARCHAR ( 256 ) NOT NULL,
+	`content` TEXT NOT NULL,
+	`context` TEXT DEFAULT NULL,
+	`states` TEXT DEFAULT NULL,
+	`state_version` INTEGER NOT NULL,
+	`create_time` DATETIME NOT NULL,
+	`sys_context` TEXT DEFAULT NULL,
+	`from_key` VARCHAR ( 256 ) NOT NULL,
+	`content_ref` VARCHAR ( 255 ) DEFAULT NULL,
+	PRIMARY KEY (`ins_key`,`state_version`),
+	CONSTRAINT `instances_un` UNIQUE (`ins_key`,`from_key`)
+);
+INSERT INTO `instances_old`
+	SELECT ins_key, content, context, states, state_version, create_time, sys_context, from_key, content_ref FROM `instances`;
+DROP TABLE `instances`;
+ALTER TABLE `instances_old` RENAME TO `instances`;
+CREATE INDEX IF NOT EXISTS `instances_create_time_IDX` ON `instances` (`create_time`);
+CREATE INDEX IF NOT EXISTS `instances_from_key_IDX` ON `instances` (`from_key`);
@@ -0,0 +1,2 @@
+ALTER TABLE `instances` ADD COLUMN `content_codec` TINYINT NOT NULL DEFAULT 0;
+ALTER TABLE `task` ADD COLUMN `data_codec` TINYINT NOT NULL DEFAULT 0;
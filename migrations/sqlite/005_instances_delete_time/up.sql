@@ -0,0 +1,2 @@
+ALTER TABLE `instances` ADD COLUMN `delete_time` DATETIME DEFAULT NULL;
+CREATE INDEX IF NOT EXISTS `instances_delete_time_IDX` ON `instances` (`delete_time`);
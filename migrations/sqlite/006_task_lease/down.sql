@@ -0,0 +1,20 @@
+-- no `DROP COLUMN` before sqlite 3.35, so rebuild the table
+CREATE TABLE `task_old` (
+	`task_id`	CHAR ( 40 ) NOT NULL,
+	`task_key`	VARCHAR ( 511 ) NOT NULL,
+	`task_type`	TINYINT NOT NULL,
+	`task_for`	VARCHAR ( 255 ) NOT NULL,
+	`task_state`	TINYINT NOT NULL,
+	`data`	TEXT NOT NULL,
+	`create_time`	DATETIME NOT NULL,
+	`execute_time`	DATETIME NOT NULL,
+	`retried_times`	SMALLINT NOT NULL,
+	`data_codec`	TINYINT NOT NULL DEFAULT 0,
+	PRIMARY KEY(`task_id`),
+	CONSTRAINT `task_un` UNIQUE (`task_key`,`task_type`,`task_for`)
+);
+INSERT INTO `task_old`
+	SELECT task_id, task_key, task_type, task_for, task_state, `data`, create_time, execute_time, retried_times, data_codec FROM `task`;
+DROP TABLE `task`;
+ALTER TABLE `task_old` RENAME TO `task`;
+CREATE INDEX IF NOT EXISTS `task_create_time_IDX` ON `task` (`create_time`,`task_state`);
@@ -0,0 +1,3 @@
+ALTER TABLE `task` ADD COLUMN `lease_owner` VARCHAR ( 64 ) DEFAULT NULL;
+ALTER TABLE `task` ADD COLUMN `lease_expire` DATETIME DEFAULT NULL;
+CREATE INDEX IF NOT EXISTS `task_lease_IDX` ON `task` (`lease_owner`,`lease_expire`);
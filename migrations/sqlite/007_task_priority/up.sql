@@ -0,0 +1,2 @@
+ALTER TABLE `task` ADD COLUMN `priority` TINYINT NOT NULL DEFAULT 0;
+CREATE INDEX IF NOT EXISTS `task_overdue_IDX` ON `task` (`task_state`,`priority`,`execute_time`);
@@ -0,0 +1,23 @@
+-- no `DROP COLUMN` before sqlite 3.35, so rebuild the table
+CREATE TABLE `instances_old` (
+	`ins_key` VARCHAR ( 256 ) NOT NULL,
+	`content` TEXT NOT NULL,
+	`context` TEXT DEFAULT NULL,
+	`states` TEXT DEFAULT NULL,
+	`state_version` INTEGER NOT NULL,
+	`create_time` DATETIME NOT NULL,
+	`sys_context` TEXT DEFAULT NULL,
+	`from_key` VARCHAR ( 256 ) NOT NULL,
+	`content_ref` VARCHAR ( 255 ) DEFAULT NULL,
+	`content_codec` TINYINT NOT NULL DEFAULT 0,
+	`delete_time` DATETIME DEFAULT NULL,
+	PRIMARY KEY (`ins_key`,`state_version`),
+	CONSTRAINT `instances_un` UNIQUE (`ins_key`,`from_key`)
+);
+INSERT INTO `instances_old`
+	SELECT ins_key, content, context, states, state_version, create_time, sys_context, from_key, content_ref, content_codec, delete_time FROM `instances`;
+DROP TABLE `instances`;
+ALTER TABLE `instances_old` RENAME TO `instances`;
+CREATE INDEX IF NOT EXISTS `instances_create_time_IDX` ON `instances` (`create_time`);
+CREATE INDEX IF NOT EXISTS `instances_from_key_IDX` ON `instances` (`from_key`);
+CREATE INDEX IF NOT EXISTS `instances_delete_time_IDX` ON `instances` (`delete_time`);
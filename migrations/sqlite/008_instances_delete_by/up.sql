@@ -0,0 +1,2 @@
+ALTER TABLE `instances` ADD COLUMN `delete_by` TINYINT NOT NULL DEFAULT 0;
+UPDATE `instances` SET `delete_by` = 1 WHERE `delete_time` is not null;
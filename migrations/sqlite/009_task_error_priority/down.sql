@@ -0,0 +1,16 @@
+-- no `DROP COLUMN` before sqlite 3.35, so rebuild the table
+CREATE TABLE `task_error_old` (
+	`task_id`	CHAR ( 40 ) NOT NULL,
+	`task_key`	VARCHAR ( 511 ) NOT NULL,
+	`task_type`	TINYINT NOT NULL,
+	`task_for`	VARCHAR ( 255 ) NOT NULL,
+	`data`	TEXT NOT NULL,
+	`create_time`	DATETIME NOT NULL,
+	`msg`	VARCHAR ( 255 ) NOT NULL,
+	PRIMARY KEY(`task_id`),
+	CONSTRAINT `task_error_un` UNIQUE (`task_key`,`task_type`,`task_for`)
+);
+INSERT INTO `task_error_old`
+	SELECT task_id, task_key, task_type, task_for, `data`, create_time, msg FROM `task_error`;
+DROP TABLE `task_error`;
+ALTER TABLE `task_error_old` RENAME TO `task_error`;
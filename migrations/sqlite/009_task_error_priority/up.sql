@@ -0,0 +1 @@
+ALTER TABLE `task_error` ADD COLUMN `priority` TINYINT NOT NULL DEFAULT 0;
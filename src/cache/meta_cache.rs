@@ -169,7 +169,7 @@ mod test {
 
     use nature_common::MetaSetting;
 
-    use crate::RawMeta;
+    use crate::{DbResult, RawMeta};
 
     use super::*;
 
@@ -369,7 +369,7 @@ mod test {
 
     #[async_trait]
     impl MetaDao for MetaMock {
-        async fn get(&self, m: &str) -> Result<Option<RawMeta>> {
+        async fn get(&self, m: &str) -> DbResult<Option<RawMeta>> {
             let rtn = match m {
                 "L:state:1" => {
                     let mut rtn = RawMeta::default();
@@ -543,20 +543,20 @@ mod test {
                     rtn.meta_key = "master-master".to_string();
                     rtn
                 }
-                _ => return Err(NatureError::LogicalError("undefined meta".to_string()))
+                _ => return Err(NatureError::LogicalError("undefined meta".to_string()).into())
             };
             Ok(Some(rtn))
         }
 
-        async fn insert(&self, _define: &RawMeta) -> Result<usize> {
+        async fn insert(&self, _define: &RawMeta) -> DbResult<usize> {
             unimplemented!()
         }
 
-        async fn update_flag(&self, _meta_str: &str, _flag_f: i32) -> Result<usize> {
+        async fn update_flag(&self, _meta_str: &str, _flag_f: i32) -> DbResult<usize> {
             unimplemented!()
         }
 
-        async fn delete(&self, _m: &Meta) -> Result<usize> {
+        async fn delete(&self, _m: &Meta) -> DbResult<usize> {
             unimplemented!()
         }
     }
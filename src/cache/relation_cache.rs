@@ -53,7 +53,7 @@ impl RelationCache for RelationCacheImpl {
 mod test {
     use nature_common::{Meta, NatureError, Result};
 
-    use crate::{RawMeta, RawRelation};
+    use crate::{DbResult, RawMeta, RawRelation};
 
     use super::*;
 
@@ -108,23 +108,23 @@ mod test {
             Err(NatureError::EnvironmentError("can't connect".to_string()))
         }
 
-        async fn insert(&self, _one: RawRelation) -> Result<usize> {
+        async fn insert(&self, _one: RawRelation) -> DbResult<usize> {
             unimplemented!()
         }
 
-        async fn delete(&self, _one: RawRelation) -> Result<usize> {
+        async fn delete(&self, _one: RawRelation) -> DbResult<usize> {
             unimplemented!()
         }
 
-        async fn update_flag(&self, _from: &str, _to: &str, _flag_f: i32) -> Result<usize> {
+        async fn update_flag(&self, _from: &str, _to: &str, _flag_f: i32) -> DbResult<usize> {
             unimplemented!()
         }
 
-        async fn insert_by_biz(&self, _from: &str, _to: &str, _url: &str, _protocol: &str) -> Result<RawRelation> {
+        async fn insert_by_biz(&self, _from: &str, _to: &str, _url: &str, _protocol: &str) -> DbResult<RawRelation> {
             unimplemented!()
         }
 
-        async fn delete_by_biz(&self, _from: &str, _to: &str) -> Result<usize> {
+        async fn delete_by_biz(&self, _from: &str, _to: &str) -> DbResult<usize> {
             unimplemented!()
         }
     }
@@ -135,23 +135,23 @@ mod test {
             Err(NatureError::EnvironmentError("another error".to_string()))
         }
 
-        async fn insert(&self, _one: RawRelation) -> Result<usize> {
+        async fn insert(&self, _one: RawRelation) -> DbResult<usize> {
             unimplemented!()
         }
 
-        async fn delete(&self, _one: RawRelation) -> Result<usize> {
+        async fn delete(&self, _one: RawRelation) -> DbResult<usize> {
             unimplemented!()
         }
 
-        async fn update_flag(&self, _from: &str, _to: &str, _flag_f: i32) -> Result<usize> {
+        async fn update_flag(&self, _from: &str, _to: &str, _flag_f: i32) -> DbResult<usize> {
             unimplemented!()
         }
 
-        async fn insert_by_biz(&self, _from: &str, _to: &str, _url: &str, _protocol: &str) -> Result<RawRelation> {
+        async fn insert_by_biz(&self, _from: &str, _to: &str, _url: &str, _protocol: &str) -> DbResult<RawRelation> {
             unimplemented!()
         }
 
-        async fn delete_by_biz(&self, _from: &str, _to: &str) -> Result<usize> {
+        async fn delete_by_biz(&self, _from: &str, _to: &str) -> DbResult<usize> {
             unimplemented!()
         }
     }
@@ -162,23 +162,23 @@ mod test {
             Ok(vec![])
         }
 
-        async fn insert(&self, _one: RawRelation) -> Result<usize> {
+        async fn insert(&self, _one: RawRelation) -> DbResult<usize> {
             unimplemented!()
         }
 
-        async fn delete(&self, _one: RawRelation) -> Result<usize> {
+        async fn delete(&self, _one: RawRelation) -> DbResult<usize> {
             unimplemented!()
         }
 
-        async fn update_flag(&self, _from: &str, _to: &str, _flag_f: i32) -> Result<usize> {
+        async fn update_flag(&self, _from: &str, _to: &str, _flag_f: i32) -> DbResult<usize> {
             unimplemented!()
         }
 
-        async fn insert_by_biz(&self, _from: &str, _to: &str, _url: &str, _protocol: &str) -> Result<RawRelation> {
+        async fn insert_by_biz(&self, _from: &str, _to: &str, _url: &str, _protocol: &str) -> DbResult<RawRelation> {
             unimplemented!()
         }
 
-        async fn delete_by_biz(&self, _from: &str, _to: &str) -> Result<usize> {
+        async fn delete_by_biz(&self, _from: &str, _to: &str) -> DbResult<usize> {
             unimplemented!()
         }
     }
@@ -199,19 +199,19 @@ mod test {
 
     #[async_trait]
     impl MetaDao for MetaMock {
-        async fn get(&self, m: &str) -> Result<Option<RawMeta>> {
+        async fn get(&self, m: &str) -> DbResult<Option<RawMeta>> {
             Ok(Some(RawMeta::from(Meta::from_string(m)?)))
         }
 
-        async fn insert(&self, _define: &RawMeta) -> Result<usize> {
+        async fn insert(&self, _define: &RawMeta) -> DbResult<usize> {
             unimplemented!()
         }
 
-        async fn update_flag(&self, _meta_str: &str, _flag_f: i32) -> Result<usize> {
+        async fn update_flag(&self, _meta_str: &str, _flag_f: i32) -> DbResult<usize> {
             unimplemented!()
         }
 
-        async fn delete(&self, _m: &Meta) -> Result<usize> {
+        async fn delete(&self, _m: &Meta) -> DbResult<usize> {
             unimplemented!()
         }
     }
@@ -1,3 +1,15 @@
+use std::env;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use rand::Rng;
+
+use nature_common::{NatureError, Result};
+
+#[cfg(all(feature = "mysql", feature = "sqlite"))]
+compile_error!("feature `mysql` and `sqlite` can't be enabled at the same time");
+
 #[cfg(feature = "mysql")]
 pub use self::mysql::*;
 #[cfg(feature = "sqlite")]
@@ -16,4 +28,204 @@ mod sqlite {
 #[cfg(feature = "mysql")]
 pub static CONN_STR: &str = "mysql://root@localhost/nature";
 #[cfg(feature = "sqlite")]
-pub static CONN_STR: &str = "nature.sqlite";
\ No newline at end of file
+pub static CONN_STR: &str = "nature.sqlite";
+/// Settings used to initialize the database, see `MySql::init` and `Sqlite::init`.
+///
+/// `Sqlite` holds only one connection, so it uses `connect_timeout` as busy timeout and ignores
+/// the pool, TLS and retry settings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DbConfig {
+    pub url: String,
+    pub min_connections: usize,
+    pub max_connections: usize,
+    /// how long to wait for a connection before give up
+    pub connect_timeout: Option<Duration>,
+    /// idle connection over `min_connections` will be closed after this time
+    pub idle_timeout: Option<Duration>,
+    pub tls: Option<TlsConfig>,
+    pub retry: DbRetryPolicy,
+}
+
+/// How to retry the transient failures: connection lost, deadlock and lock wait timeout.
+/// The delay before the nth retry is `base_delay * 2^n` capped by `max_delay`, and a random part
+/// of its second half is cut off, so that the clients failed together won't retry together.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DbRetryPolicy {
+    /// 0 for no retry
+    pub times: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for DbRetryPolicy {
+    fn default() -> Self {
+        DbRetryPolicy {
+            times: 3,
+            base_delay: Duration::from_millis(50),
+            max_delay: Duration::from_secs(2),
+        }
+    }
+}
+
+impl DbRetryPolicy {
+    /// the delay before the `retried + 1`th retry
+    pub fn delay(&self, retried: u32) -> Duration {
+        let delay = self.base_delay.checked_mul(2u32.saturating_pow(retried))
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay);
+        let half = delay / 2;
+        half + half.mul_f64(rand::thread_rng().gen_range(0.0, 1.0))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TlsConfig {
+    pub root_cert_path: Option<PathBuf>,
+    /// client identity
+    pub pkcs12_path: Option<PathBuf>,
+    pub pkcs12_password: Option<String>,
+    pub accept_invalid_certs: bool,
+    pub skip_domain_validation: bool,
+}
+
+impl DbConfig {
+    pub fn new(url: &str) -> Self {
+        DbConfig {
+            url: url.to_string(),
+            min_connections: 10,
+            max_connections: 100,
+            connect_timeout: None,
+            idle_timeout: None,
+            tls: None,
+            retry: DbRetryPolicy::default(),
+        }
+    }
+
+    /// `DATABASE_URL` is required, and the optional:
+    /// `DATABASE_MIN_CONNECTIONS`, `DATABASE_MAX_CONNECTIONS`,
+    /// `DATABASE_CONNECT_TIMEOUT` and `DATABASE_IDLE_TIMEOUT` in seconds,
+    /// `DATABASE_RETRY_TIMES`, `DATABASE_RETRY_DELAY` and `DATABASE_RETRY_MAX_DELAY` in milliseconds.
+    pub fn from_env() -> Result<Self> {
+        let url = match env::var("DATABASE_URL") {
+            Ok(url) => url,
+            Err(_) => return Err(NatureError::EnvironmentError("DATABASE_URL must be set".to_string()))
+        };
+        let mut cfg = DbConfig::new(&url);
+        if let Some(min) = env_value("DATABASE_MIN_CONNECTIONS")? {
+            cfg.min_connections = min;
+        }
+        if let Some(max) = env_value("DATABASE_MAX_CONNECTIONS")? {
+            cfg.max_connections = max;
+        }
+        cfg.connect_timeout = env_value("DATABASE_CONNECT_TIMEOUT")?.map(Duration::from_secs);
+        cfg.idle_timeout = env_value("DATABASE_IDLE_TIMEOUT")?.map(Duration::from_secs);
+        if let Some(times) = env_value("DATABASE_RETRY_TIMES")? {
+            cfg.retry.times = times;
+        }
+        if let Some(delay) = env_value("DATABASE_RETRY_DELAY")? {
+            cfg.retry.base_delay = Duration::from_millis(delay);
+        }
+        if let Some(delay) = env_value("DATABASE_RETRY_MAX_DELAY")? {
+            cfg.retry.max_delay = Duration::from_millis(delay);
+        }
+        Ok(cfg)
+    }
+
+    pub fn connections(mut self, min: usize, max: usize) -> Self {
+        self.min_connections = min;
+        self.max_connections = max;
+        self
+    }
+
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    pub fn tls(mut self, tls: TlsConfig) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    pub fn retry(mut self, retry: DbRetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn verify(&self) -> Result<()> {
+        if self.url.is_empty() {
+            return Err(NatureError::VerifyError("database url should not be empty".to_string()));
+        }
+        if self.max_connections == 0 || self.min_connections > self.max_connections {
+            let msg = format!("invalid connections range: {}..{}", self.min_connections, self.max_connections);
+            return Err(NatureError::VerifyError(msg));
+        }
+        if self.retry.base_delay > self.retry.max_delay {
+            return Err(NatureError::VerifyError("retry base delay should not be greater than the max delay".to_string()));
+        }
+        Ok(())
+    }
+}
+
+fn env_value<T: FromStr>(key: &str) -> Result<Option<T>> {
+    match env::var(key) {
+        Err(_) => Ok(None),
+        Ok(v) => match v.parse::<T>() {
+            Ok(v) => Ok(Some(v)),
+            Err(_) => Err(NatureError::EnvironmentError(format!("invalid value for {}: {}", key, v)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn builder_test() {
+        let cfg = DbConfig::new("mysql://root@localhost/nature")
+            .connections(1, 5)
+            .connect_timeout(Duration::from_secs(3))
+            .tls(TlsConfig::default());
+        assert_eq!(cfg.min_connections, 1);
+        assert_eq!(cfg.max_connections, 5);
+        assert_eq!(cfg.connect_timeout, Some(Duration::from_secs(3)));
+        assert_eq!(cfg.idle_timeout, None);
+        assert!(cfg.tls.is_some());
+        assert!(cfg.verify().is_ok());
+    }
+
+    #[test]
+    fn verify_test() {
+        let cfg = DbConfig::new("mysql://root@localhost/nature").connections(5, 1);
+        assert!(matches!(cfg.verify(), Err(NatureError::VerifyError(_))));
+        let cfg = DbConfig::new("").connections(1, 5);
+        assert!(matches!(cfg.verify(), Err(NatureError::VerifyError(_))));
+        let retry = DbRetryPolicy {
+            max_delay: Duration::from_millis(1),
+            ..Default::default()
+        };
+        let cfg = DbConfig::new("mysql://root@localhost/nature").retry(retry);
+        assert!(matches!(cfg.verify(), Err(NatureError::VerifyError(_))));
+    }
+
+    #[test]
+    fn retry_delay_test() {
+        let retry = DbRetryPolicy {
+            times: 5,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(1000),
+        };
+        let delay = retry.delay(0);
+        assert!(delay >= Duration::from_millis(50) && delay <= Duration::from_millis(100));
+        let delay = retry.delay(2);
+        assert!(delay >= Duration::from_millis(200) && delay <= Duration::from_millis(400));
+        let delay = retry.delay(100);
+        assert!(delay >= Duration::from_millis(500) && delay <= Duration::from_millis(1000));
+    }
+}
@@ -0,0 +1,21 @@
+pub use blob_store::*;
+pub use db_error::*;
+pub use instance_dao::*;
+pub use meta_dao::*;
+pub use migration::*;
+pub use relation_dao::*;
+pub use storage::*;
+pub use task_dao::*;
+pub use task_error_dao::*;
+pub use transaction::*;
+
+mod blob_store;
+mod db_error;
+mod instance_dao;
+mod meta_dao;
+mod migration;
+mod relation_dao;
+mod storage;
+mod task_dao;
+mod task_error_dao;
+mod transaction;
@@ -0,0 +1,135 @@
+use std::env;
+use std::sync::{Arc, RwLock};
+
+use tokio::runtime::Handle;
+
+use nature_common::Result;
+
+use crate::LocalBlobStore;
+
+lazy_static! {
+    static ref BLOB_STORE : RwLock<Option<Arc<dyn BlobStore>>> = RwLock::new(None);
+}
+
+/// Saves the content which is too long for `instances.content`, the row keeps the reference only.
+#[async_trait]
+pub trait BlobStore: Sync + Send {
+    /// `key` is unique for each content of each instance, returns the reference to get it back.
+    async fn put(&self, key: &str, content: &str) -> Result<String>;
+    async fn get(&self, reference: &str) -> Result<String>;
+    /// removes the blob of the row which is not saved or is purged, it's ok if there is no such blob.
+    async fn delete(&self, reference: &str) -> Result<()>;
+}
+
+/// Enables the overflow mode: content longer than `INSTANCE_CONTENT_MAX_LENGTH` will be saved to `store`.
+/// It can also be enabled by the env `BLOB_STORE_PATH`, which uses a `LocalBlobStore`.
+pub fn set_blob_store(store: Arc<dyn BlobStore>) {
+    *BLOB_STORE.write().unwrap() = Some(store);
+}
+
+pub(crate) fn get_blob_store() -> Option<Arc<dyn BlobStore>> {
+    if let Some(store) = BLOB_STORE.read().unwrap().as_ref() {
+        return Some(store.clone());
+    }
+    let path = env::var("BLOB_STORE_PATH").ok()?;
+    let mut guard = BLOB_STORE.write().unwrap();
+    let store = guard.get_or_insert_with(|| Arc::new(LocalBlobStore::new(&path)));
+    Some(store.clone())
+}
+
+/// deletes the blobs whose rows are not saved or are purged, the failures are logged only
+/// for nothing refers to them any more.
+pub(crate) async fn remove_blobs(references: Vec<String>) {
+    if references.is_empty() {
+        return;
+    }
+    let store = match get_blob_store() {
+        Some(store) => store,
+        None => return
+    };
+    for one in references {
+        if let Err(e) = store.delete(&one).await {
+            warn!("blob {} is left, err: {}", one, e);
+        }
+    }
+}
+
+/// The blobs saved within a transaction, they are removed unless the transaction is committed.
+#[derive(Default)]
+pub(crate) struct TxBlobs(Vec<String>);
+
+impl TxBlobs {
+    pub fn push(&mut self, reference: Option<String>) {
+        self.0.extend(reference);
+    }
+
+    /// the rows are saved, keep the blobs
+    pub fn commit(&mut self) {
+        self.0.clear();
+    }
+
+    pub async fn remove(&mut self) {
+        remove_blobs(self.0.split_off(0)).await
+    }
+}
+
+impl Drop for TxBlobs {
+    fn drop(&mut self) {
+        if self.0.is_empty() {
+            return;
+        }
+        let references = self.0.split_off(0);
+        match Handle::try_current() {
+            Ok(handle) => drop(handle.spawn(remove_blobs(references))),
+            Err(_) => warn!("blobs are left for no runtime to remove them: {:?}", references)
+        }
+    }
+}
+
+/// Keeps the blobs in memory, all the tests share it for the store is global.
+#[cfg(test)]
+#[derive(Default)]
+pub(crate) struct TestBlobStore {
+    blobs: std::sync::Mutex<std::collections::BTreeMap<String, String>>,
+}
+
+#[cfg(test)]
+impl TestBlobStore {
+    /// the references of the `content` kept
+    pub fn of(&self, content: &str) -> Vec<String> {
+        self.blobs.lock().unwrap().iter().filter(|(_, v)| *v == content).map(|(k, _)| k.clone()).collect()
+    }
+}
+
+#[cfg(test)]
+#[async_trait]
+impl BlobStore for TestBlobStore {
+    async fn put(&self, key: &str, content: &str) -> Result<String> {
+        self.blobs.lock().unwrap().insert(key.to_string(), content.to_string());
+        Ok(key.to_string())
+    }
+
+    async fn get(&self, reference: &str) -> Result<String> {
+        match self.blobs.lock().unwrap().get(reference) {
+            Some(content) => Ok(content.clone()),
+            None => Err(nature_common::NatureError::EnvironmentError(format!("no blob: {}", reference)))
+        }
+    }
+
+    async fn delete(&self, reference: &str) -> Result<()> {
+        self.blobs.lock().unwrap().remove(reference);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) fn init_test_blob_store() -> Arc<TestBlobStore> {
+    lazy_static! {
+        static ref STORE: Arc<TestBlobStore> = {
+            let store = Arc::new(TestBlobStore::default());
+            set_blob_store(store.clone());
+            store
+        };
+    }
+    STORE.clone()
+}
@@ -0,0 +1,89 @@
+use std::fmt;
+
+use nature_common::NatureError;
+
+/// the result of the DAOs, `?` converts it to `nature_common::Result` when the caller needs not
+/// to tell the errors apart.
+pub type DbResult<T> = std::result::Result<T, DbError>;
+
+/// Errors of the persistence layer classified by what the caller can do with them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DbError {
+    /// mysql 1213, the transaction had been rolled back
+    Deadlock(String),
+    /// mysql 1205, sqlite busy or locked
+    LockTimeout(String),
+    /// connection closed or can't be gotten in time
+    ConnectionLost(String),
+    /// mysql 1062, sqlite primary key or unique constraint
+    Duplicated(String),
+    /// mysql 1406, sqlite too big
+    DataTooLong(String),
+    /// the input is invalid, e.g. the state can't be changed to
+    Verify(String),
+    /// other errors of the database server or the environment
+    Environment(String),
+    /// bugs, retry will not help
+    Logical(String),
+}
+
+impl DbError {
+    /// whether the same operation may succeed if it is tried again later
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, DbError::Deadlock(_) | DbError::LockTimeout(_) | DbError::ConnectionLost(_))
+    }
+}
+
+impl fmt::Display for DbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl std::error::Error for DbError {}
+
+impl From<DbError> for NatureError {
+    fn from(err: DbError) -> Self {
+        match err {
+            DbError::Duplicated(msg) => NatureError::DaoDuplicated(msg),
+            DbError::DataTooLong(msg) | DbError::Verify(msg) => NatureError::VerifyError(msg),
+            DbError::Logical(msg) => NatureError::LogicalError(msg),
+            DbError::Deadlock(msg) | DbError::LockTimeout(msg) | DbError::ConnectionLost(msg) | DbError::Environment(msg) =>
+                NatureError::EnvironmentError(msg),
+        }
+    }
+}
+
+/// for the errors raised out of the database, e.g. the models and the codecs
+impl From<NatureError> for DbError {
+    fn from(err: NatureError) -> Self {
+        match err {
+            NatureError::VerifyError(msg) => DbError::Verify(msg),
+            NatureError::LogicalError(msg) => DbError::Logical(msg),
+            NatureError::DaoDuplicated(msg) => DbError::Duplicated(msg),
+            NatureError::SystemError(msg) | NatureError::EnvironmentError(msg) => DbError::Environment(msg),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn convert_test() {
+        let err: NatureError = DbError::Deadlock("1213".to_string()).into();
+        assert_eq!(err, NatureError::EnvironmentError("1213".to_string()));
+        assert!(DbError::Deadlock("1213".to_string()).is_retryable());
+
+        let err: NatureError = DbError::Duplicated("1062".to_string()).into();
+        assert!(matches!(err, NatureError::DaoDuplicated(_)));
+        assert!(!DbError::from(err).is_retryable());
+        let err: NatureError = DbError::DataTooLong("1406".to_string()).into();
+        assert!(matches!(err, NatureError::VerifyError(_)));
+
+        let err = DbError::from(NatureError::VerifyError("bad key".to_string()));
+        assert_eq!(err, DbError::Verify("bad key".to_string()));
+        assert_eq!(NatureError::from(err), NatureError::VerifyError("bad key".to_string()));
+    }
+}
@@ -0,0 +1,554 @@
+use std::collections::VecDeque;
+use std::str::FromStr;
+
+use futures::stream::{self, BoxStream, StreamExt};
+
+use nature_common::*;
+
+use crate::{DbError, DbResult, FlowSelector, Mission, QUERY_SIZE_LIMIT, Retention, SELECTOR_BATCH_LIMIT};
+
+/// condition for the state history of one instance, the versions are inclusive.
+#[derive(Debug, Clone, Default)]
+pub struct HistoryCondition {
+    /// meta|id|para
+    pub ins_key: String,
+    pub version_ge: Option<i32>,
+    pub version_le: Option<i32>,
+    pub limit: i32,
+}
+
+impl HistoryCondition {
+    pub fn new(ins_key: &str) -> Self {
+        HistoryCondition {
+            ins_key: ins_key.to_string(),
+            version_ge: None,
+            version_le: None,
+            limit: *QUERY_SIZE_LIMIT,
+        }
+    }
+
+    /// no more than `QUERY_SIZE_LIMIT`
+    pub fn get_limit(&self) -> i32 {
+        self.limit.min(*QUERY_SIZE_LIMIT).max(0)
+    }
+}
+
+/// an instance with its upstream chain and downstream tree.
+/// Nodes on the upstream chain have no `downstream` and vice versa.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lineage {
+    pub instance: Instance,
+    pub upstream: Option<Box<Lineage>>,
+    pub downstream: Vec<Lineage>,
+}
+
+impl Lineage {
+    pub fn new(instance: Instance) -> Self {
+        Lineage {
+            instance,
+            upstream: None,
+            downstream: vec![],
+        }
+    }
+}
+
+/// result of `InstanceDao::insert_state`
+#[derive(Debug, Clone, PartialEq)]
+pub enum StateInsert {
+    Inserted,
+    /// the latest state is not the one just before the inserting, it is carried here
+    /// so that the caller can retry on it. `None` means there is no state yet.
+    Conflict(Option<Box<Instance>>),
+    /// the `from` of the inserting has made this instance already, e.g. the upstream is redelivered,
+    /// so there is nothing to retry.
+    Repeated(Box<Instance>),
+    /// the version or the `from_key` is held by a deleted instance, see `InstanceDao::delete`,
+    /// it can't be inserted until the instance is restored or purged.
+    Deleted,
+}
+
+/// who soft deleted the instance, saved in `instances.delete_by`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeleteBy {
+    /// by `InstanceDao::delete`, can be restored
+    User = 1,
+    /// by `InstanceDao::retain`, can't be restored
+    Retention = 2,
+}
+
+impl From<DeleteBy> for i8 {
+    fn from(by: DeleteBy) -> Self {
+        by as i8
+    }
+}
+
+impl From<&KeyCondition> for HistoryCondition {
+    fn from(f_para: &KeyCondition) -> Self {
+        Self::new(&f_para.get_key())
+    }
+}
+
+#[async_trait]
+pub trait InstanceDao: Sync + Send {
+    async fn insert(&self, instance: &Instance) -> DbResult<usize>;
+    /// check whether source stored earlier
+    async fn get_by_from(&self, f_para: &IDAndFrom) -> DbResult<Option<Instance>>;
+    async fn get_by_id(&self, f_para: KeyCondition) -> DbResult<Option<Instance>>;
+    /// the newest `state_version` of the key
+    async fn get_last_state(&self, f_para: &KeyCondition) -> DbResult<Option<Instance>>;
+    /// all the state versions of the `ins_key` in ascending order, each with its `from`,
+    /// so that we can see which upstream caused each transition.
+    async fn get_history(&self, f_para: &HistoryCondition) -> DbResult<Vec<Instance>>;
+    /// instances whose `from_key` is `from`, no more than `QUERY_SIZE_LIMIT`
+    async fn get_downstream(&self, from: &FromInstance) -> DbResult<Vec<Instance>>;
+    /// marks all the state versions of the `ins_key` as deleted, they are invisible to the queries
+    /// but still hold their keys: inserting the same version or `from_key` again fails with
+    /// `DaoDuplicated` until they are `restore`d or `purge`d.
+    async fn delete(&self, ins: &Instance) -> DbResult<usize>;
+    /// undo the `delete` of the `ins_key`, the versions deleted by `retain` are not restored.
+    async fn restore(&self, ins: &Instance) -> DbResult<usize>;
+    /// marks the instances of the `meta` out of the `retention` as deleted. The latest version of
+    /// a state instance is always kept, so the next version can follow it.
+    async fn retain(&self, meta: &str, retention: &Retention) -> DbResult<usize>;
+    /// removes the instances which were deleted `delay` seconds ago, they can't be restored any more.
+    async fn purge(&self, delay: i64) -> DbResult<usize>;
+
+    /// returns 1 for the inserted and 0 for the duplicated, in the order of `instances`.
+    async fn insert_batch(&self, instances: &[Instance]) -> DbResult<Vec<usize>> {
+        let mut rtn = Vec::with_capacity(instances.len());
+        for one in instances {
+            rtn.push(match self.insert(one).await {
+                Ok(num) => num,
+                Err(DbError::Duplicated(_)) => 0,
+                Err(e) => return Err(e)
+            });
+        }
+        Ok(rtn)
+    }
+
+    /// compare-and-set: inserts the `instance` only when the latest state version of the key is
+    /// `instance.state_version - 1`, no latest state is taken as version 0.
+    /// The primary key of `instances` makes it atomic since the versions are continuous.
+    async fn insert_state(&self, instance: &Instance) -> DbResult<StateInsert> {
+        if instance.state_version < 1 {
+            return Err(DbError::Verify(format!("state version should be greater than 0: {}", instance.get_key())));
+        }
+        let para = KeyCondition::new(instance.id, &instance.meta, &instance.para, 0);
+        let last = self.get_last_state(&para).await?;
+        let current = last.as_ref().map(|one| one.state_version).unwrap_or(0);
+        if current != instance.state_version - 1 {
+            debug!("state conflict, inserting: {}, current version: {}", instance.get_key(), current);
+            return Ok(StateInsert::Conflict(last.map(Box::new)));
+        }
+        match self.insert(instance).await {
+            Ok(_) => Ok(StateInsert::Inserted),
+            Err(DbError::Duplicated(_)) => state_duplicated(self, instance, &para).await,
+            Err(e) => Err(e)
+        }
+    }
+
+    /// `retain` for each `Retention` set
+    async fn apply_retention(&self) -> DbResult<usize> {
+        let mut rtn = 0;
+        for (meta, retention) in Retention::all() {
+            rtn += self.retain(&meta, &retention).await?;
+        }
+        Ok(rtn)
+    }
+
+    /// walks `depth` generations up along `from` and down by `get_downstream`
+    async fn get_lineage(&self, ins: &Instance, depth: u32) -> DbResult<Lineage> {
+        let mut chain: Vec<Instance> = vec![];
+        let mut from = ins.from.clone();
+        while chain.len() < depth as usize {
+            let up = match &from {
+                Some(f) => self.get_by_id(KeyCondition::from(f)).await?,
+                None => None
+            };
+            match up {
+                Some(up) => {
+                    from = up.from.clone();
+                    chain.push(up);
+                }
+                None => break
+            }
+        }
+        let mut rtn = Lineage::new(ins.clone());
+        rtn.upstream = chain.into_iter().rev().fold(None, |upstream, one| {
+            let mut node = Lineage::new(one);
+            node.upstream = upstream;
+            Some(Box::new(node))
+        });
+        rtn.downstream = self.get_downstream_lineage(ins, depth).await?;
+        Ok(rtn)
+    }
+
+    /// the downstream tree of `ins` within `depth` generations
+    async fn get_downstream_lineage(&self, ins: &Instance, depth: u32) -> DbResult<Vec<Lineage>> {
+        let mut rtn: Vec<Lineage> = vec![];
+        if depth == 0 {
+            return Ok(rtn);
+        }
+        for one in self.get_downstream(&FromInstance::from(ins)).await? {
+            let downstream = self.get_downstream_lineage(&one, depth - 1).await?;
+            let mut node = Lineage::new(one);
+            node.downstream = downstream;
+            rtn.push(node);
+        }
+        Ok(rtn)
+    }
+
+    async fn get_by_key(&self, key: String, spliter: String) -> DbResult<Option<Instance>> {
+        let temp: Vec<&str> = key.split(&spliter).collect();
+        if temp.len() != 4 {
+            return Err(DbError::Verify("error key format for task".to_string()));
+        }
+        let para = KeyCondition {
+            id: temp[1].to_string(),
+            meta: temp[0].to_string(),
+            key_gt: "".to_string(),
+            key_ge: "".to_string(),
+            key_lt: "".to_string(),
+            key_le: "".to_string(),
+            para: temp[2].to_string(),
+            state_version: i32::from_str(temp[3]).map_err(|e| DbError::Verify(e.to_string()))?,
+            time_ge: None,
+            time_lt: None,
+            limit: 1,
+        };
+        self.get_by_id(para).await
+    }
+
+    /// get downstream instance through upstream instance
+    async fn get_last_target(&self, from: &Instance, mission: &mut Mission) -> DbResult<Option<Instance>> {
+        // init for MetaType::loop --------------------
+        if mission.to.get_meta_type() == MetaType::Loop
+            && mission.to.meta_string() == from.meta {
+            if let Some(setting) = mission.to.get_setting() {
+                if setting.only_one {
+                    debug!("make MetaType::Loop as last state for {}", from.meta);
+                    return Ok(Some(from.clone()));
+                }
+            }
+        }
+        // normal ---------------------------
+        if !mission.to.is_state() {
+            return Ok(None);
+        }
+        let para_part = &mission.target_demand.append_para;
+        let para_id = if para_part.len() > 0 {
+            let id = get_para_and_key_from_para(&from.para, para_part)?.0;
+            mission.sys_context.insert(CONTEXT_TARGET_INSTANCE_PARA.to_string(), id.to_string());
+            id
+        } else {
+            "".to_string()
+        };
+        let mut id: ID = match mission.sys_context.get(&*CONTEXT_TARGET_INSTANCE_ID) {
+            // context have target id
+            Some(state_id) => id_from_hex_str(state_id)?,
+            None => 0,
+        };
+        if id == 0 {
+            if mission.use_upstream_id || mission.to.check_master(&from.meta) {
+                mission.sys_context.insert(CONTEXT_TARGET_INSTANCE_ID.to_string(), format!("{:x}", from.id));
+                id = from.id
+            }
+        }
+        let meta = mission.to.meta_string();
+        debug!("get last state for meta {}", &meta);
+        let qc = KeyCondition::new(id, &meta, &para_id, 0);
+        self.get_last_state(&qc).await
+    }
+}
+
+/// tells which row `insert_state` hit: the same `from_key`, a newer state, or a deleted one
+/// which is invisible to the queries.
+async fn state_duplicated<D: InstanceDao + ?Sized>(dao: &D, instance: &Instance, para: &KeyCondition) -> DbResult<StateInsert> {
+    if let Some(from) = &instance.from {
+        let f_para = IDAndFrom {
+            id: instance.id,
+            meta: instance.meta.clone(),
+            from_key: from.to_string(),
+        };
+        if let Some(existing) = dao.get_by_from(&f_para).await?.filter(|one| one.para == instance.para) {
+            debug!("state repeated, inserting: {}, existing: {}", instance.get_key(), existing.get_key());
+            return Ok(StateInsert::Repeated(Box::new(existing)));
+        }
+    }
+    let last = dao.get_last_state(para).await?;
+    match last.as_ref().filter(|one| one.state_version >= instance.state_version) {
+        Some(_) => {
+            debug!("state conflict, inserting: {}", instance.get_key());
+            Ok(StateInsert::Conflict(last.map(Box::new)))
+        }
+        None => {
+            warn!("state is held by a deleted instance, inserting: {}", instance.get_key());
+            Ok(StateInsert::Deleted)
+        }
+    }
+}
+
+/// `KeyCondition` with the cursor of the previous page. `KeyCondition` comes from `nature_common`,
+/// so the cursor is flattened beside it and can be sent back within the same json.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RangeCondition {
+    #[serde(flatten)]
+    pub key: KeyCondition,
+    /// `next_cursor` of the previous page, empty for the first page
+    #[serde(skip_serializing_if = "is_default")]
+    #[serde(default)]
+    pub cursor: String,
+    /// selects by `states`, `context` and `sys_context` the same way as the routing does
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub selector: Option<FlowSelector>,
+}
+
+impl RangeCondition {
+    /// no more than `QUERY_SIZE_LIMIT`
+    pub fn get_limit(&self) -> i32 {
+        self.key.limit.min(*QUERY_SIZE_LIMIT).max(0)
+    }
+
+    /// (ins_key, state_version) of the last instance of the previous page
+    pub fn get_cursor(&self) -> DbResult<Option<(String, i32)>> {
+        if self.cursor.is_empty() {
+            return Ok(None);
+        }
+        let mut parts = self.cursor.rsplitn(2, SEPARATOR_INS_KEY.as_str());
+        match (parts.next(), parts.next()) {
+            (Some(version), Some(key)) => match i32::from_str(version) {
+                Ok(version) => Ok(Some((key.to_string(), version))),
+                Err(_) => Err(DbError::Verify(format!("illegal cursor: {}", self.cursor)))
+            },
+            _ => Err(DbError::Verify(format!("illegal cursor: {}", self.cursor)))
+        }
+    }
+}
+
+impl From<KeyCondition> for RangeCondition {
+    fn from(key: KeyCondition) -> Self {
+        RangeCondition {
+            key,
+            cursor: "".to_string(),
+            selector: None,
+        }
+    }
+}
+
+/// one page of `get_by_key_range`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct InstancePage {
+    pub instances: Vec<Instance>,
+    /// the key of the last instance, or of the last row read if the selector has not filled the page,
+    /// used as `RangeCondition::cursor` to get the next page
+    pub next_cursor: Option<String>,
+    pub has_more: bool,
+}
+
+impl InstancePage {
+    /// `instances` should be queried with `limit + 1` to tell whether there are more.
+    pub fn new(mut instances: Vec<Instance>, condition: &RangeCondition) -> Self {
+        let limit = condition.get_limit() as usize;
+        let has_more = instances.len() > limit;
+        instances.truncate(limit);
+        let next_cursor = match has_more {
+            true => instances.last().map(|one| one.get_key()),
+            false => None
+        };
+        InstancePage {
+            instances,
+            next_cursor,
+            has_more,
+        }
+    }
+}
+
+#[async_trait]
+pub trait KeyRange: Sync + Send {
+    /// the rows after `f_para.cursor` ordered by ins_key and state_version, no more than `limit + 1`.
+    /// `f_para.selector` is not applied.
+    async fn get_range(&self, f_para: &RangeCondition) -> DbResult<Vec<Instance>>;
+
+    /// ordered by ins_key and state_version, the page is no more than `QUERY_SIZE_LIMIT`, which is
+    /// also taken for a `limit` not greater than 0.
+    /// The rows are read batch by batch until the page is filled with the ones matched by `f_para.selector`
+    /// or the range is exhausted. No more than `SELECTOR_BATCH_LIMIT` batches are read, the page may be
+    /// partly filled or even empty then, and `has_more` is set for the caller to continue from `next_cursor`.
+    async fn get_by_key_range(&self, f_para: &RangeCondition) -> DbResult<InstancePage> {
+        let mut condition = f_para.clone();
+        if condition.key.limit <= 0 {
+            condition.key.limit = *QUERY_SIZE_LIMIT;
+        }
+        let selector = match &condition.selector {
+            Some(selector) => selector,
+            None => return Ok(InstancePage::new(self.get_range(&condition).await?, &condition))
+        };
+        let limit = condition.get_limit() as usize;
+        let mut next = condition.clone();
+        let mut rtn: Vec<Instance> = vec![];
+        let mut batches = 0;
+        loop {
+            let batch = self.get_range(&next).await?;
+            let exhausted = batch.len() <= limit;
+            next.cursor = match batch.last() {
+                Some(last) => last.get_key(),
+                None => break
+            };
+            rtn.extend(batch.into_iter().filter(|one| selector.is_match(one)));
+            batches += 1;
+            if exhausted || rtn.len() > limit {
+                break;
+            }
+            if batches >= *SELECTOR_BATCH_LIMIT {
+                debug!("{} batches read for the selector, the page ends at: {}", batches, next.cursor);
+                return Ok(InstancePage {
+                    instances: rtn,
+                    next_cursor: Some(next.cursor),
+                    has_more: true,
+                });
+            }
+        }
+        Ok(InstancePage::new(rtn, &condition))
+    }
+
+    /// Scans the instances after `f_para.cursor` as a stream, `f_para.key.limit` is the page size.
+    /// The next page is queried only after the previous one is consumed,
+    /// so no more than one page is held whatever the range is. The stream ends after an error.
+    fn scan(&self, mut f_para: RangeCondition) -> BoxStream<'_, DbResult<Instance>> {
+        if f_para.key.limit <= 0 {
+            f_para.key.limit = *QUERY_SIZE_LIMIT;
+        }
+        let init: (Option<RangeCondition>, VecDeque<Instance>) = (Some(f_para), VecDeque::new());
+        stream::try_unfold(init, move |(mut next, mut buffer)| async move {
+            while buffer.is_empty() {
+                let condition = match next.take() {
+                    Some(condition) => condition,
+                    None => return Ok(None)
+                };
+                let page = self.get_by_key_range(&condition).await?;
+                next = page.next_cursor.map(|cursor| RangeCondition { cursor, ..condition });
+                buffer.extend(page.instances);
+            }
+            Ok(buffer.pop_front().map(|one| (one, (next, buffer))))
+        }).boxed()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn get_by_key_test() {
+        let rtn = InsMock {}.get_by_key("B:a:1|1".to_string(), "|".to_string()).await;
+        assert_eq!(rtn, Err(DbError::Verify("error key format for task".to_string())));
+
+        let rtn = InsMock {}.get_by_key("B:a:1|a|p|3".to_string(), "|".to_string()).await.unwrap().unwrap();
+        assert_eq!(rtn.id, 10);
+        assert_eq!(rtn.meta, "B:a:1");
+        assert_eq!(rtn.para, "p");
+        assert_eq!(rtn.state_version, 3);
+    }
+
+    #[tokio::test]
+    async fn get_last_target_not_state_test() {
+        let mut mission = Mission {
+            to: Meta::from_string("B:a:1").unwrap(),
+            ..Default::default()
+        };
+        let rtn = InsMock {}.get_last_target(&Instance::default(), &mut mission).await.unwrap();
+        assert_eq!(rtn, None);
+    }
+
+    #[test]
+    fn range_condition_test() {
+        let json = r#"{"id":"","meta":"B:a:1","limit":10,"cursor":"B:a:1|1|p|2","selector":{"state_none":["shipped"]}}"#;
+        let para: RangeCondition = serde_json::from_str(json).unwrap();
+        assert_eq!(para.key.limit, 10);
+        assert!(para.selector.as_ref().unwrap().state_none.contains("shipped"));
+        assert_eq!(para.get_cursor().unwrap(), Some(("B:a:1|1|p".to_string(), 2)));
+        let mut para = RangeCondition::from(para.key);
+        assert_eq!(para.get_cursor().unwrap(), None);
+        para.cursor = "B:a:1|1|p|x".to_string();
+        assert!(para.get_cursor().is_err());
+    }
+
+    #[test]
+    fn instance_page_test() {
+        let list: Vec<Instance> = (0..3).map(|i| Instance {
+            id: i,
+            ..Default::default()
+        }).collect();
+        let mut para = RangeCondition::from(KeyCondition::new(0, "", "", 0));
+        para.key.limit = 2;
+        let page = InstancePage::new(list.clone(), &para);
+        assert!(page.has_more);
+        assert_eq!(page.instances.len(), 2);
+        assert_eq!(page.next_cursor, Some(list[1].get_key()));
+        para.key.limit = 3;
+        let page = InstancePage::new(list.clone(), &para);
+        assert!(!page.has_more);
+        assert_eq!(page.next_cursor, None);
+
+    }
+
+    #[test]
+    fn history_condition_test() {
+        let mut para = HistoryCondition::from(&KeyCondition::new(1, "B:a:1", "p", 3));
+        assert_eq!(para.ins_key, "B:a:1|1|p");
+        para.limit = *QUERY_SIZE_LIMIT + 1;
+        assert_eq!(para.get_limit(), *QUERY_SIZE_LIMIT);
+        para.limit = -1;
+        assert_eq!(para.get_limit(), 0);
+    }
+
+    struct InsMock;
+
+    #[async_trait]
+    impl InstanceDao for InsMock {
+        async fn insert(&self, _instance: &Instance) -> DbResult<usize> {
+            unimplemented!()
+        }
+
+        async fn get_by_from(&self, _f_para: &IDAndFrom) -> DbResult<Option<Instance>> {
+            unimplemented!()
+        }
+
+        async fn get_by_id(&self, f_para: KeyCondition) -> DbResult<Option<Instance>> {
+            let mut rtn = Instance::new("a")?;
+            rtn.id = id_from_hex_str(&f_para.id)?;
+            rtn.para = f_para.para;
+            rtn.state_version = f_para.state_version;
+            Ok(Some(rtn))
+        }
+
+        async fn get_last_state(&self, _f_para: &KeyCondition) -> DbResult<Option<Instance>> {
+            unimplemented!()
+        }
+
+        async fn get_history(&self, _f_para: &HistoryCondition) -> DbResult<Vec<Instance>> {
+            unimplemented!()
+        }
+
+        async fn get_downstream(&self, _from: &FromInstance) -> DbResult<Vec<Instance>> {
+            unimplemented!()
+        }
+
+        async fn delete(&self, _ins: &Instance) -> DbResult<usize> {
+            unimplemented!()
+        }
+
+        async fn restore(&self, _ins: &Instance) -> DbResult<usize> {
+            unimplemented!()
+        }
+
+        async fn retain(&self, _meta: &str, _retention: &Retention) -> DbResult<usize> {
+            unimplemented!()
+        }
+
+        async fn purge(&self, _delay: i64) -> DbResult<usize> {
+            unimplemented!()
+        }
+    }
+}
@@ -0,0 +1,15 @@
+use std::future::Future;
+
+use nature_common::{Meta, Result};
+
+use crate::{DbResult, raw_models::RawMeta};
+
+pub type MetaGetter = fn(&str) -> dyn Future<Output=Result<Option<RawMeta>>>;
+
+#[async_trait]
+pub trait MetaDao: Sync + Send {
+    async fn get(&self, meta_str: &str) -> DbResult<Option<RawMeta>>;
+    async fn insert(&self, define: &RawMeta) -> DbResult<usize>;
+    async fn update_flag(&self, meta_str: &str, flag_f: i32) -> DbResult<usize>;
+    async fn delete(&self, m: &Meta) -> DbResult<usize>;
+}
@@ -0,0 +1,126 @@
+use nature_common::{NatureError, Result};
+
+use crate::DbResult;
+
+/// One version of the schema, `down` reverts what `up` did.
+pub struct Migration {
+    pub version: i32,
+    pub name: &'static str,
+    pub up: &'static str,
+    pub down: &'static str,
+}
+
+pub enum Step {
+    Up(&'static Migration),
+    Down(&'static Migration),
+}
+
+/// Upgrades the schema of the backend, the applied versions are recorded in `schema_version`.
+/// Should be run by only one process at a time.
+#[async_trait]
+pub trait Migrator: Sync + Send {
+    /// sorted by version
+    fn migrations(&self) -> &'static [Migration];
+    /// 0 for an empty database
+    async fn current_version(&self) -> DbResult<i32>;
+    /// upgrades or downgrades to `version`, 0 means remove all. returns the version after migrated.
+    async fn migrate_to(&self, version: i32) -> DbResult<i32>;
+
+    /// upgrades to the newest version
+    async fn migrate(&self) -> DbResult<i32> {
+        let newest = self.migrations().last().map_or(0, |m| m.version);
+        self.migrate_to(newest).await
+    }
+}
+
+pub fn check_version(migrations: &[Migration], version: i32) -> Result<()> {
+    if version == 0 || migrations.iter().any(|m| m.version == version) {
+        Ok(())
+    } else {
+        Err(NatureError::VerifyError(format!("unknown schema version: {}", version)))
+    }
+}
+
+/// the steps from `current` to `target` in executing order
+pub fn plan(migrations: &'static [Migration], current: i32, target: i32) -> Vec<Step> {
+    if current > target {
+        migrations.iter().rev()
+            .filter(|m| m.version <= current && m.version > target)
+            .map(Step::Down)
+            .collect()
+    } else {
+        migrations.iter()
+            .filter(|m| m.version > current && m.version <= target)
+            .map(Step::Up)
+            .collect()
+    }
+}
+
+/// splits the script by `;` at the end of line, for the backend can't execute them at once.
+pub fn statements(script: &str) -> Vec<String> {
+    let mut rtn: Vec<String> = vec![];
+    let mut one = String::new();
+    for line in script.lines() {
+        one.push_str(line);
+        one.push('\n');
+        if line.trim_end().ends_with(';') {
+            rtn.push(one.trim().trim_end_matches(';').to_string());
+            one.clear();
+        }
+    }
+    if !one.trim().is_empty() {
+        rtn.push(one.trim().to_string());
+    }
+    rtn
+}
+
+/// the statements of the `script` with their indexes, except the `done` ones.
+pub fn pending(script: &str, done: &[i32]) -> Vec<(i32, String)> {
+    statements(script).into_iter()
+        .enumerate()
+        .map(|(i, one)| (i as i32, one))
+        .filter(|(i, _)| !done.contains(i))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static MIGRATIONS: [Migration; 2] = [
+        Migration { version: 1, name: "a", up: "", down: "" },
+        Migration { version: 3, name: "b", up: "", down: "" },
+    ];
+
+    fn versions(steps: Vec<Step>) -> Vec<i32> {
+        steps.into_iter().map(|s| match s {
+            Step::Up(m) => m.version,
+            Step::Down(m) => -m.version,
+        }).collect()
+    }
+
+    #[test]
+    fn plan_test() {
+        assert_eq!(versions(plan(&MIGRATIONS, 0, 3)), vec![1, 3]);
+        assert_eq!(versions(plan(&MIGRATIONS, 1, 3)), vec![3]);
+        assert_eq!(versions(plan(&MIGRATIONS, 3, 3)), Vec::<i32>::new());
+        assert_eq!(versions(plan(&MIGRATIONS, 3, 0)), vec![-3, -1]);
+        assert!(check_version(&MIGRATIONS, 2).is_err());
+        assert!(check_version(&MIGRATIONS, 0).is_ok());
+    }
+
+    #[test]
+    fn statements_test() {
+        let script = "CREATE TABLE a (\n  b INT\n);\n\nCREATE INDEX c ON a (b);\n";
+        assert_eq!(statements(script), vec!["CREATE TABLE a (\n  b INT\n)", "CREATE INDEX c ON a (b)"]);
+        assert_eq!(statements("DROP TABLE a"), vec!["DROP TABLE a"]);
+    }
+
+    #[test]
+    fn pending_test() {
+        let script = "ALTER TABLE a ADD COLUMN b INT;\nALTER TABLE a ADD INDEX c (b);\n";
+        assert_eq!(pending(script, &[]).len(), 2);
+        assert_eq!(pending(script, &[0]), vec![(1, "ALTER TABLE a ADD INDEX c (b)".to_string())]);
+        assert!(pending(script, &[0, 1]).is_empty());
+    }
+}
@@ -0,0 +1,17 @@
+use nature_common::Result;
+
+use crate::{DbResult, MetaCache, MetaDao, Relation};
+use crate::raw_models::RawRelation;
+
+pub type Relations = Result<Vec<Relation>>;
+
+#[async_trait]
+pub trait RelationDao: Sync + Send {
+    async fn get_relations<MC, M>(&self, from: &str, meta_cache_getter: &MC, meta_getter: &M) -> Relations
+        where MC: MetaCache, M: MetaDao;
+    async fn insert(&self, one: RawRelation) -> DbResult<usize>;
+    async fn delete(&self, one: RawRelation) -> DbResult<usize>;
+    async fn update_flag(&self, from: &str, to: &str, flag_f: i32) -> DbResult<usize>;
+    async fn insert_by_biz(&self, from: &str, to: &str, url: &str, protocol: &str) -> DbResult<RawRelation>;
+    async fn delete_by_biz(&self, from: &str, to: &str) -> DbResult<usize>;
+}
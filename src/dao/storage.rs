@@ -0,0 +1,18 @@
+use crate::{DbResult, InstanceDao, KeyRange, MetaDao, RelationDao, StorageTx, TaskErrorDao};
+
+/// Bundles all the DAOs of one backend, so that the user can be generic over the backend
+/// instead of binding to a concrete one.
+#[async_trait]
+pub trait Storage: Sync + Send {
+    type Instance: InstanceDao + KeyRange;
+    type Task: TaskErrorDao;
+    type Meta: MetaDao;
+    type Relation: RelationDao;
+    type Tx: StorageTx;
+
+    fn instance(&self) -> &Self::Instance;
+    fn task(&self) -> &Self::Task;
+    fn meta(&self) -> &Self::Meta;
+    fn relation(&self) -> &Self::Relation;
+    async fn begin(&self) -> DbResult<Self::Tx>;
+}
@@ -0,0 +1,265 @@
+use chrono::{Duration, Local, NaiveDateTime};
+use mysql_async::Value;
+
+use nature_common::{NatureError, Result};
+
+use crate::{DbResult, RetryPolicy, TaskState};
+use crate::raw_models::RawTask;
+
+/// the length of `task.lease_owner`
+static LEASE_OWNER_MAX_LENGTH: usize = 64;
+
+#[async_trait]
+pub trait TaskDao: Sync + Send {
+    async fn insert(&self, raw: &RawTask) -> DbResult<usize>;
+    async fn delete(&self, _record_id: &str) -> DbResult<usize>;
+    async fn delete_finished(&self, _delay: i64) -> DbResult<usize>;
+    /// sets the task `Failed` and moves it to `task_error`, returns 0 if it has been finished or cancelled,
+    /// or is in `task_error` already. The task not found, e.g. failed before being saved, is moved as well.
+    async fn raw_to_error(&self, err: &NatureError, raw: &RawTask) -> DbResult<usize>;
+    /// the `Pending` tasks out of any lease and the `Running` ones whose lease expired, i.e. the worker claimed
+    /// them is gone, ordered by `priority` desc then `execute_time`.
+    async fn get_overdue_in(&self, queue: &TaskQueue, delay: i64, _limit: i64) -> DbResult<Vec<RawTask>>;
+    /// only for the `Pending` or `Running` task
+    async fn update_execute_time(&self, _record_id: &str, delay: i64) -> DbResult<usize>;
+    /// from `Pending` or `Running`
+    async fn finish_task(&self, _record_id: &str) -> DbResult<usize>;
+    /// the `Pending` or `Running` task will be retried after `delay` seconds, it is set back to `Pending`
+    /// and its lease is released.
+    async fn increase_times_and_delay(&self, _record_id: &str, delay: i32) -> DbResult<usize>;
+    async fn get(&self, _record_id: &str) -> DbResult<Option<RawTask>>;
+    /// changes the state only if it is still `from`, `VerifyError` if `from` can't be changed to `to`.
+    async fn update_state(&self, task_id: &str, from: TaskState, to: TaskState) -> DbResult<usize>;
+    /// changes the tasks of the `queue` in any of the `from` states to `to` and returns how many are changed,
+    /// `VerifyError` if the `queue` is empty or one of the `from` can't be changed to `to`.
+    async fn update_states(&self, queue: &TaskQueue, from: &[TaskState], to: TaskState) -> DbResult<usize>;
+    /// leases no more than `limit` overdue tasks of the `queue` to the `worker` for `lease` seconds in one statement
+    /// and returns them as `Running`, the tasks leased to any worker can't be claimed again until the lease expires.
+    /// The tasks are picked in the same way as `get_overdue_in`.
+    async fn claim(&self, worker: &str, queue: &TaskQueue, delay: i64, lease: i64, limit: i64) -> DbResult<Vec<RawTask>>;
+    /// extends the lease to `lease` seconds from now, returns 0 if the task is not leased to the `worker` any more
+    /// or is not `Running`.
+    async fn renew_lease(&self, task_id: &str, worker: &str, lease: i64) -> DbResult<usize>;
+    /// gives up the lease of the `Running` task and sets it back to `Pending`, so that it can be claimed by others at once
+    async fn release_lease(&self, task_id: &str, worker: &str) -> DbResult<usize>;
+
+    /// changes the current state to `to`, returns 0 if the task is not found or its state was changed
+    /// by others meanwhile, `VerifyError` if the current state can't be changed to `to`.
+    async fn set_state(&self, task_id: &str, to: TaskState) -> DbResult<usize> {
+        match self.get(task_id).await? {
+            Some(raw) => self.update_state(task_id, raw.task_state, to).await,
+            None => Ok(0)
+        }
+    }
+
+    /// the task will never be executed, whatever state it is in except `Finished`
+    async fn cancel(&self, task_id: &str) -> DbResult<usize> {
+        self.set_state(task_id, TaskState::Cancelled).await
+    }
+
+    /// the pending task is skipped by `get_overdue` and `claim` until it is resumed
+    async fn pause(&self, task_id: &str) -> DbResult<usize> {
+        self.update_state(task_id, TaskState::Pending, TaskState::Paused).await
+    }
+
+    /// returns 0 if the task is not paused
+    async fn resume(&self, task_id: &str) -> DbResult<usize> {
+        self.update_state(task_id, TaskState::Paused, TaskState::Pending).await
+    }
+
+    async fn cancel_in(&self, queue: &TaskQueue) -> DbResult<usize> {
+        self.update_states(queue, TaskState::Cancelled.sources(), TaskState::Cancelled).await
+    }
+
+    async fn pause_in(&self, queue: &TaskQueue) -> DbResult<usize> {
+        self.update_states(queue, &[TaskState::Pending], TaskState::Paused).await
+    }
+
+    async fn resume_in(&self, queue: &TaskQueue) -> DbResult<usize> {
+        self.update_states(queue, &[TaskState::Paused], TaskState::Pending).await
+    }
+
+    /// `get_overdue_in` for all the tasks
+    async fn get_overdue(&self, delay: i64, _limit: i64) -> DbResult<Vec<RawTask>> {
+        self.get_overdue_in(&TaskQueue::default(), delay, _limit).await
+    }
+
+    /// delays the failed task by the `policy`, or sets it `Failed` and moves it to `task_error` with the `err`
+    /// when it has been retried `policy.max_attempts` times.
+    async fn retry_by(&self, raw: &RawTask, policy: &RetryPolicy, err: &NatureError) -> DbResult<TaskRetry> {
+        if policy.is_exhausted(raw.retried_times) {
+            self.raw_to_error(err, raw).await?;
+            debug!("task retry exhausted, KEY: {} FOR: {} TYPE: {}", &raw.task_key, &raw.task_for, raw.task_type);
+            return Ok(TaskRetry::Exhausted);
+        }
+        let delay = policy.delay_of(raw.retried_times);
+        self.increase_times_and_delay(&raw.task_id, delay).await?;
+        Ok(TaskRetry::Delayed(delay))
+    }
+
+    /// returns 1 for the inserted and 0 for the repeated, in the order of `raws`.
+    async fn insert_batch(&self, raws: &[RawTask]) -> DbResult<Vec<usize>> {
+        let mut rtn = Vec::with_capacity(raws.len());
+        for raw in raws {
+            rtn.push(self.insert(raw).await?);
+        }
+        Ok(rtn)
+    }
+}
+
+/// what `TaskDao::retry_by` did to the task
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskRetry {
+    /// will be retried after the seconds
+    Delayed(i32),
+    /// moved to `task_error`
+    Exhausted,
+}
+
+/// The overdue tasks served by a dedicated worker, so the latency-sensitive flows need not wait for
+/// the others, the empty are ignored.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct TaskQueue {
+    /// only the tasks for this meta
+    pub task_for: String,
+    /// only the tasks whose meta of the `task_key` starts with it, e.g. "B:sale/"
+    pub meta_prefix: String,
+}
+
+impl TaskQueue {
+    pub fn is_empty(&self) -> bool {
+        self.task_for.is_empty() && self.meta_prefix.is_empty()
+    }
+
+    /// the conditions to be appended to the `WHERE` clause, with their params
+    pub(crate) fn to_where(&self) -> (String, Vec<(String, Value)>) {
+        let task_for = if self.task_for.is_empty() { "" } else {
+            " and task_for = :task_for"
+        };
+        let meta_prefix = if self.meta_prefix.is_empty() { "" } else {
+            " and task_key like :meta_prefix"
+        };
+        let p = params! {
+            "task_for" => self.task_for.to_string(),
+            "meta_prefix" => format!("{}%", self.meta_prefix),
+        };
+        (format!("{}{}", task_for, meta_prefix), p)
+    }
+
+    /// the same as `to_where`, for the backends without sql
+    pub(crate) fn is_match(&self, raw: &RawTask) -> bool {
+        (self.task_for.is_empty() || raw.task_for == self.task_for)
+            && raw.task_key.starts_with(&self.meta_prefix)
+    }
+}
+
+/// the `WHERE` conditions of `TaskDao::get_overdue_in` and `TaskDao::claim`, see `overdue_params`
+pub(crate) static OVERDUE_WHERE: &str = "execute_time < :execute_time
+    and ((task_state = :pending and (lease_expire is null or lease_expire < :now)) or (task_state = :running and lease_expire < :now))";
+
+/// the params of `OVERDUE_WHERE`
+pub(crate) fn overdue_params(delay: i64) -> Vec<(String, Value)> {
+    params! {
+        "execute_time" => Local::now().checked_add_signed(Duration::seconds(delay)).unwrap().naive_local(),
+        "now" => Local::now().naive_local(),
+        "pending" => i8::from(TaskState::Pending),
+        "running" => i8::from(TaskState::Running),
+    }
+}
+
+/// whether the task is picked by `OVERDUE_WHERE`, for the backends without sql
+pub(crate) fn is_overdue(raw: &RawTask, lease_expire: Option<NaiveDateTime>, delay: i64) -> bool {
+    let execute_time = Local::now().checked_add_signed(Duration::seconds(delay)).unwrap().naive_local();
+    let expired = lease_expire.map(|expire| expire < Local::now().naive_local());
+    raw.execute_time < execute_time && match raw.task_state {
+        TaskState::Pending => expired != Some(false),
+        TaskState::Running => expired == Some(true),
+        _ => false
+    }
+}
+
+/// the codes of the `states` for sql `IN`, e.g. "0, 2"
+pub(crate) fn state_codes(states: &[TaskState]) -> String {
+    states.iter().map(|s| i8::from(*s).to_string()).collect::<Vec<String>>().join(", ")
+}
+
+/// checks the params of `TaskDao::update_states`, so that all the tasks won't be changed by mistake
+pub(crate) fn check_update_states(queue: &TaskQueue, from: &[TaskState], to: TaskState) -> Result<()> {
+    if queue.is_empty() {
+        return Err(NatureError::VerifyError("should select the tasks by `task_for` or `meta_prefix`".to_string()));
+    }
+    if from.is_empty() {
+        return Err(NatureError::VerifyError("no state to be changed from".to_string()));
+    }
+    from.iter().try_for_each(|one| one.check_change_to(to))
+}
+
+/// checks the `worker` and returns when the lease of `lease` seconds expires
+pub(crate) fn lease_expire(worker: &str, lease: i64) -> Result<NaiveDateTime> {
+    if worker.is_empty() || worker.len() > LEASE_OWNER_MAX_LENGTH {
+        let msg = format!("worker id should not be empty or longer than {}: {}", LEASE_OWNER_MAX_LENGTH, worker);
+        return Err(NatureError::VerifyError(msg));
+    }
+    if lease < 1 {
+        return Err(NatureError::VerifyError(format!("lease should be greater than 0: {}", lease)));
+    }
+    Ok(Local::now().checked_add_signed(Duration::seconds(lease)).unwrap().naive_local())
+}
+
+/// condition used by `TaskChecker` to count tasks
+pub struct Condition {
+    pub key_gt: String,
+    pub key_lt: String,
+    pub time_ge: Option<NaiveDateTime>,
+    pub time_lt: Option<NaiveDateTime>,
+    pub state: TaskState,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lease_expire_test() {
+        assert!(lease_expire("worker", 10).unwrap() > Local::now().naive_local());
+        assert!(lease_expire("", 10).is_err());
+        assert!(lease_expire(&"w".repeat(65), 10).is_err());
+        assert!(lease_expire("worker", 0).is_err());
+    }
+
+    #[test]
+    fn is_overdue_test() {
+        let past = Local::now().naive_local() - Duration::seconds(10);
+        let mut raw = RawTask {
+            execute_time: past,
+            ..Default::default()
+        };
+        assert!(is_overdue(&raw, None, 0));
+        assert!(is_overdue(&raw, Some(past), 0));
+        assert!(!is_overdue(&raw, lease_expire("worker", 10).ok(), 0));
+        assert!(!is_overdue(&raw, None, -20));
+        raw.task_state = TaskState::Running;
+        assert!(!is_overdue(&raw, None, 0));
+        assert!(!is_overdue(&raw, lease_expire("worker", 10).ok(), 0));
+        assert!(is_overdue(&raw, Some(past), 0));
+        raw.task_state = TaskState::Failed;
+        assert!(!is_overdue(&raw, None, 0));
+    }
+
+    #[test]
+    fn queue_test() {
+        let raw = RawTask {
+            task_key: "B:sale/order:1|1||0".to_string(),
+            task_for: "B:sale/pay:1".to_string(),
+            ..Default::default()
+        };
+        let mut queue = TaskQueue::default();
+        assert_eq!(queue.to_where().0, "");
+        assert!(queue.is_match(&raw));
+        queue.meta_prefix = "B:sale/".to_string();
+        assert!(queue.is_match(&raw));
+        queue.task_for = "B:sale/other:1".to_string();
+        assert_eq!(queue.to_where().0, " and task_for = :task_for and task_key like :meta_prefix");
+        assert!(!queue.is_match(&raw));
+    }
+}
@@ -0,0 +1,104 @@
+use chrono::NaiveDateTime;
+use mysql_async::Value;
+
+use nature_common::SEPARATOR_INS_KEY;
+
+use crate::{DbResult, QUERY_SIZE_LIMIT, TaskDao, TaskType};
+use crate::raw_models::RawTaskError;
+
+/// condition to select the tasks in `task_error`, the empty and `None` are ignored.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct TaskErrorCondition {
+    /// the meta of the `task_key`
+    pub meta: String,
+    pub key_gt: String,
+    pub key_lt: String,
+    pub task_type: Option<TaskType>,
+    /// for the `create_time` of the task
+    pub time_ge: Option<NaiveDateTime>,
+    pub time_lt: Option<NaiveDateTime>,
+    pub limit: i32,
+}
+
+impl TaskErrorCondition {
+    /// no more than `QUERY_SIZE_LIMIT`
+    pub fn get_limit(&self) -> i32 {
+        self.limit.min(*QUERY_SIZE_LIMIT).max(0)
+    }
+
+    /// the `WHERE` clause with its params which are the same for all the backends
+    pub(crate) fn to_where(&self) -> (String, Vec<(String, Value)>) {
+        let meta = if self.meta.is_empty() { "" } else {
+            " and task_key like :meta"
+        };
+        let key_gt = if self.key_gt.is_empty() { "" } else {
+            " and task_key > :key_gt"
+        };
+        let key_lt = if self.key_lt.is_empty() { "" } else {
+            " and task_key < :key_lt"
+        };
+        let task_type = if self.task_type.is_none() { "" } else {
+            " and task_type = :task_type"
+        };
+        let time_ge = if self.time_ge.is_none() { "" } else {
+            " and create_time >= :time_ge"
+        };
+        let time_lt = if self.time_lt.is_none() { "" } else {
+            " and create_time < :time_lt"
+        };
+        let sql = format!("WHERE 1=1{}{}{}{}{}{}", meta, key_gt, key_lt, task_type, time_ge, time_lt);
+        let mut p = params! {
+            "meta" => format!("{}{}%", self.meta, SEPARATOR_INS_KEY.as_str()),
+            "key_gt" => self.key_gt.to_string(),
+            "key_lt" => self.key_lt.to_string(),
+            "task_type" => self.task_type.map_or(0, i8::from),
+        };
+        if let Some(ge) = self.time_ge {
+            p.push(("time_ge".to_string(), ge.into()));
+        }
+        if let Some(lt) = self.time_lt {
+            p.push(("time_lt".to_string(), lt.into()));
+        }
+        (sql, p)
+    }
+
+    /// the same as `to_where`, for the backends without sql
+    pub(crate) fn is_match(&self, error: &RawTaskError) -> bool {
+        let meta = format!("{}{}", self.meta, SEPARATOR_INS_KEY.as_str());
+        (self.meta.is_empty() || error.task_key.starts_with(&meta))
+            && (self.key_gt.is_empty() || error.task_key > self.key_gt)
+            && (self.key_lt.is_empty() || error.task_key < self.key_lt)
+            && self.task_type.filter(|t| *t != error.task_type).is_none()
+            && self.time_ge.filter(|ge| error.create_time < *ge).is_none()
+            && self.time_lt.filter(|lt| error.create_time >= *lt).is_none()
+    }
+}
+
+/// Reads the tasks moved to `task_error` by `TaskDao::raw_to_error` and brings them back.
+/// It's implemented by the `TaskDao`s since they own both tables.
+#[async_trait]
+pub trait TaskErrorDao: TaskDao {
+    async fn get_error(&self, task_id: &str) -> DbResult<Option<RawTaskError>>;
+    /// ordered by `task_key`, no more than `condition.get_limit()`
+    async fn get_errors(&self, condition: &TaskErrorCondition) -> DbResult<Vec<RawTaskError>>;
+    async fn delete_error(&self, task_id: &str) -> DbResult<usize>;
+    /// deletes all the errors matched, the `limit` is ignored.
+    async fn purge_errors(&self, condition: &TaskErrorCondition) -> DbResult<usize>;
+
+    /// moves the errors back to `task` to be executed at once with `retried_times` reset,
+    /// returns how many of them are inserted, the ones still in `task` are just deleted from `task_error`.
+    async fn requeue(&self, task_ids: &[String]) -> DbResult<usize> {
+        let mut rtn = 0;
+        for task_id in task_ids {
+            let error = match self.get_error(task_id).await? {
+                Some(error) => error,
+                None => continue
+            };
+            // insert first, so the task won't be lost even if the delete failed
+            rtn += self.insert(&error.to_raw()).await?;
+            self.delete_error(task_id).await?;
+            debug!("task requeued, KEY: {} FOR: {} TYPE: {}", &error.task_key, &error.task_for, error.task_type);
+        }
+        Ok(rtn)
+    }
+}
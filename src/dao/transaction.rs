@@ -0,0 +1,19 @@
+use nature_common::Instance;
+
+use crate::{DbResult, raw_models::RawTask};
+
+/// Operations that must be committed together, such as saving an instance, inserting its
+/// downstream tasks and finishing the upstream task.
+///
+/// Nothing is visible to the others before `commit`. Dropping it without `commit` rolls back,
+/// and so does any error returned by the database, after which the transaction can't be used
+/// anymore.
+#[async_trait]
+pub trait StorageTx: Send {
+    async fn insert_instance(&mut self, instance: &Instance) -> DbResult<usize>;
+    /// the same as `TaskDao::insert`, returns 0 for repeated task and the transaction goes on.
+    async fn insert_task(&mut self, raw: &RawTask) -> DbResult<usize>;
+    async fn finish_task(&mut self, task_id: &str) -> DbResult<usize>;
+    async fn commit(self) -> DbResult<()>;
+    async fn rollback(self) -> DbResult<()>;
+}
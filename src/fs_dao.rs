@@ -0,0 +1,103 @@
+//! Keeps the overflowed content in the local file system.
+
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use tokio::fs;
+
+use nature_common::{NatureError, Result};
+
+use crate::BlobStore;
+
+/// one file for each blob, spread into sub directories by the first two characters of the key.
+pub struct LocalBlobStore {
+    root: PathBuf,
+}
+
+impl LocalBlobStore {
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        LocalBlobStore {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+
+    fn path(&self, reference: &str) -> Result<PathBuf> {
+        let legal = !reference.is_empty() && !reference.starts_with('/')
+            && reference.split('/').all(|one| !one.is_empty() && one != "." && one != "..");
+        if !legal {
+            return Err(NatureError::VerifyError(format!("illegal blob reference: {}", reference)));
+        }
+        Ok(self.root.join(reference))
+    }
+}
+
+/// `tokio::fs` runs the file operations in the blocking pool, so they won't stall the async workers.
+#[async_trait]
+impl BlobStore for LocalBlobStore {
+    async fn put(&self, key: &str, content: &str) -> Result<String> {
+        let dir = key.chars().take(2).collect::<String>();
+        let reference = format!("{}/{}", dir, key);
+        let path = self.path(&reference)?;
+        let rtn = async {
+            fs::create_dir_all(self.root.join(&dir)).await?;
+            // a whole file or nothing
+            let tmp = path.with_extension("tmp");
+            fs::write(&tmp, content).await?;
+            fs::rename(&tmp, &path).await
+        }.await;
+        match rtn {
+            Ok(_) => {
+                debug!("saved blob: {}", reference);
+                Ok(reference)
+            }
+            Err(e) => Err(NatureError::EnvironmentError(format!("save blob {} error: {}", reference, e)))
+        }
+    }
+
+    async fn get(&self, reference: &str) -> Result<String> {
+        match fs::read_to_string(self.path(reference)?).await {
+            Ok(content) => Ok(content),
+            Err(e) => Err(NatureError::EnvironmentError(format!("read blob {} error: {}", reference, e)))
+        }
+    }
+
+    async fn delete(&self, reference: &str) -> Result<()> {
+        match fs::remove_file(self.path(reference)?).await {
+            Ok(_) => {
+                debug!("deleted blob: {}", reference);
+                Ok(())
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(NatureError::EnvironmentError(format!("delete blob {} error: {}", reference, e)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::env;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn put_and_get_test() {
+        let root = env::temp_dir().join("nature_db_blob_test");
+        let store = LocalBlobStore::new(&root);
+        let reference = store.put("abc123", "hello").await.unwrap();
+        assert_eq!(reference, "ab/abc123");
+        assert_eq!(store.get(&reference).await.unwrap(), "hello");
+        // overwrite
+        store.put("abc123", "world").await.unwrap();
+        assert_eq!(store.get(&reference).await.unwrap(), "world");
+        assert!(matches!(store.get("ab/none").await, Err(NatureError::EnvironmentError(_))));
+        assert!(matches!(store.get("../etc/passwd").await, Err(NatureError::VerifyError(_))));
+        assert!(matches!(store.get("/etc/passwd").await, Err(NatureError::VerifyError(_))));
+
+        store.delete(&reference).await.unwrap();
+        assert!(store.get(&reference).await.is_err());
+        // deleted already
+        store.delete(&reference).await.unwrap();
+        assert!(matches!(store.delete("../etc/passwd").await, Err(NatureError::VerifyError(_))));
+        let _ = std::fs::remove_dir_all(root);
+    }
+}
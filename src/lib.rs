@@ -2,8 +2,12 @@
 
 #[macro_use]
 extern crate async_trait;
+extern crate base64;
 extern crate chrono;
 extern crate fern;
+#[cfg(feature = "gzip")]
+extern crate flate2;
+extern crate futures;
 #[macro_use]
 extern crate lazy_static;
 #[macro_use]
@@ -12,24 +16,41 @@ extern crate lru_time_cache;
 #[macro_use]
 extern crate mysql_async;
 extern crate nature_common;
+extern crate rand;
+#[cfg(feature = "sqlite")]
+extern crate rusqlite;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 extern crate serde_json;
+#[cfg(feature = "zstd")]
+extern crate zstd_rs;
 
 pub use cache::*;
 pub use conn::*;
+pub use dao::*;
 pub use define::*;
+pub use fs_dao::*;
+pub use memory_dao::*;
 pub use models::*;
+#[cfg(feature = "mysql")]
 pub use mysql_dao::*;
 pub use orm::*;
 pub use raw_models::*;
+#[cfg(feature = "sqlite")]
+pub use sqlite_dao::*;
 
 mod cache;
 mod orm;
+mod dao;
+mod fs_dao;
+mod memory_dao;
+#[cfg(feature = "mysql")]
 mod mysql_dao;
+#[cfg(feature = "sqlite")]
+mod sqlite_dao;
 mod raw_models;
 mod models;
 
 
-mod conn;
\ No newline at end of file
+mod conn;
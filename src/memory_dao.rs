@@ -0,0 +1,30 @@
+//! Keeps everything in process memory and follows the same uniqueness rules as `doc/schema.sql`,
+//! so that flows can be unit-tested without a database.
+
+pub use instance_dao::*;
+pub use meta_dao::*;
+pub use relation_dao::*;
+pub use storage::*;
+pub use task_dao::*;
+pub use transaction::*;
+
+/// simple `like` for the patterns this crate generated, only trailing '%' is used.
+fn like(value: &str, pattern: &str) -> bool {
+    match pattern.strip_suffix('%') {
+        Some(prefix) => value.starts_with(prefix),
+        None => value == pattern
+    }
+}
+
+fn duplicated(key: &str, value: &str) -> crate::DbError {
+    let msg = format!("Duplicate entry '{}' for key '{}'", value, key);
+    warn!("{}", msg);
+    crate::DbError::Duplicated(msg)
+}
+
+mod instance_dao;
+mod meta_dao;
+mod relation_dao;
+mod storage;
+mod task_dao;
+mod transaction;
@@ -0,0 +1,541 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use chrono::{Duration, Local, NaiveDateTime};
+
+use nature_common::*;
+
+use crate::{DbResult, DeleteBy, HistoryCondition, InstanceDao, KeyRange, QUERY_SIZE_LIMIT, RangeCondition, Retention};
+use crate::raw_models::RawInstance;
+
+use super::{duplicated, like};
+
+/// rows are keyed by (ins_key, state_version) which is the primary key of `instances`
+#[derive(Default)]
+pub struct MemInstanceDao {
+    pub(super) rows: Mutex<BTreeMap<(String, i32), Instance>>,
+    /// `delete_time` and `delete_by` of the soft deleted rows, always locked after `rows`
+    deleted: Mutex<BTreeMap<(String, i32), (NaiveDateTime, DeleteBy)>>,
+}
+
+/// checks the primary key and `instances_un`
+pub(super) fn check_unique(rows: &BTreeMap<(String, i32), Instance>, instance: &Instance) -> DbResult<()> {
+    let ins_key = instance.key_no_state();
+    let from = from_key(instance);
+    if rows.contains_key(&(ins_key.clone(), instance.state_version)) {
+        return Err(duplicated("PRIMARY", &format!("{}-{}", ins_key, instance.state_version)));
+    }
+    if rows.iter().any(|(k, v)| k.0 == ins_key && from_key(v) == from) {
+        return Err(duplicated("instances_un", &format!("{}-{}", ins_key, from)));
+    }
+    Ok(())
+}
+
+fn from_key(ins: &Instance) -> String {
+    match &ins.from {
+        None => "".to_string(),
+        Some(from) => from.to_string()
+    }
+}
+
+#[async_trait]
+impl InstanceDao for MemInstanceDao {
+    async fn insert(&self, instance: &Instance) -> DbResult<usize> {
+        // same limitations as the database backends
+        let _ = RawInstance::new(instance)?;
+        let mut rows = self.rows.lock().unwrap();
+        check_unique(&rows, instance)?;
+        rows.insert((instance.key_no_state(), instance.state_version), instance.clone());
+        debug!("Saved instance : {}", instance.get_key());
+        Ok(1)
+    }
+
+    async fn get_by_from(&self, f_para: &IDAndFrom) -> DbResult<Option<Instance>> {
+        let para_like = f_para.para_like();
+        let rows = self.rows.lock().unwrap();
+        let deleted = self.deleted.lock().unwrap();
+        let rtn = rows.iter()
+            .filter(|(k, v)| like(&k.0, &para_like) && from_key(v) == f_para.from_key && !deleted.contains_key(k))
+            .max_by_key(|(k, _)| k.1)
+            .map(|(_, v)| v.clone());
+        Ok(rtn)
+    }
+
+    async fn get_by_id(&self, f_para: KeyCondition) -> DbResult<Option<Instance>> {
+        let key = (f_para.get_key(), f_para.state_version);
+        let rows = self.rows.lock().unwrap();
+        let deleted = self.deleted.lock().unwrap();
+        Ok(rows.get(&key).filter(|_| !deleted.contains_key(&key)).cloned())
+    }
+
+    async fn get_last_state(&self, f_para: &KeyCondition) -> DbResult<Option<Instance>> {
+        let key = f_para.get_key();
+        let rows = self.rows.lock().unwrap();
+        let deleted = self.deleted.lock().unwrap();
+        let rtn = rows.range((key.clone(), i32::MIN)..=(key, i32::MAX))
+            .rev()
+            .find(|(k, _)| !deleted.contains_key(k))
+            .map(|(_, v)| v.clone());
+        Ok(rtn)
+    }
+
+    async fn get_history(&self, f_para: &HistoryCondition) -> DbResult<Vec<Instance>> {
+        let ge = f_para.version_ge.unwrap_or(i32::MIN);
+        let le = f_para.version_le.unwrap_or(i32::MAX);
+        let rows = self.rows.lock().unwrap();
+        let deleted = self.deleted.lock().unwrap();
+        if ge > le {
+            return Ok(vec![]);
+        }
+        let rtn = rows.range((f_para.ins_key.clone(), ge)..=(f_para.ins_key.clone(), le))
+            .filter(|(k, _)| !deleted.contains_key(k))
+            .take(f_para.get_limit() as usize)
+            .map(|(_, v)| v.clone())
+            .collect();
+        Ok(rtn)
+    }
+
+    async fn get_downstream(&self, from: &FromInstance) -> DbResult<Vec<Instance>> {
+        let key = from.to_string();
+        let rows = self.rows.lock().unwrap();
+        let deleted = self.deleted.lock().unwrap();
+        let rtn = rows.iter()
+            .filter(|(k, v)| from_key(v) == key && !deleted.contains_key(k))
+            .take(*QUERY_SIZE_LIMIT as usize)
+            .map(|(_, v)| v.clone())
+            .collect();
+        Ok(rtn)
+    }
+
+    async fn delete(&self, ins: &Instance) -> DbResult<usize> {
+        let key = ins.key_no_state();
+        let now = Local::now().naive_local();
+        let rows = self.rows.lock().unwrap();
+        let mut deleted = self.deleted.lock().unwrap();
+        let before = deleted.len();
+        for (k, _) in rows.range((key.clone(), i32::MIN)..=(key, i32::MAX)) {
+            deleted.entry(k.clone()).or_insert((now, DeleteBy::User));
+        }
+        debug!("instance deleted, id is : {:?}", ins.id);
+        Ok(deleted.len() - before)
+    }
+
+    async fn restore(&self, ins: &Instance) -> DbResult<usize> {
+        let key = ins.key_no_state();
+        let mut deleted = self.deleted.lock().unwrap();
+        let before = deleted.len();
+        deleted.retain(|k, v| k.0 != key || v.1 != DeleteBy::User);
+        debug!("instance restored, id is : {:?}", ins.id);
+        Ok(before - deleted.len())
+    }
+
+    async fn retain(&self, meta: &str, retention: &Retention) -> DbResult<usize> {
+        retention.check()?;
+        let meta_like = format!("{}{}%", meta, SEPARATOR_INS_KEY.as_str());
+        let now = Local::now();
+        let rows = self.rows.lock().unwrap();
+        let mut deleted = self.deleted.lock().unwrap();
+        let alive: Vec<(&(String, i32), &Instance)> = rows.iter()
+            .filter(|(k, _)| like(&k.0, &meta_like) && !deleted.contains_key(k))
+            .collect();
+        let mut max_alive: BTreeMap<&str, i32> = BTreeMap::new();
+        for (k, _) in &alive {
+            let max = max_alive.entry(k.0.as_str()).or_insert(k.1);
+            *max = k.1.max(*max);
+        }
+        let created_before = retention.keep_days
+            .map(|days| now.checked_sub_signed(Duration::days(i64::from(days))).unwrap().timestamp_millis());
+        let out: Vec<(String, i32)> = alive.iter()
+            .filter(|(k, v)| {
+                let old_version = retention.keep_versions.filter(|n| k.1 <= max_alive[k.0.as_str()] - n).is_some();
+                // version 0 is not a state instance, the others keep the latest version
+                let latest = k.1 > 0 && k.1 == max_alive[k.0.as_str()];
+                let old_time = !latest && created_before.filter(|t| v.create_time < *t).is_some();
+                old_version || old_time
+            })
+            .map(|(k, _)| (*k).clone())
+            .collect();
+        for k in &out {
+            deleted.insert(k.clone(), (now.naive_local(), DeleteBy::Retention));
+        }
+        debug!("{} instances of {} are out of retention", out.len(), meta);
+        Ok(out.len())
+    }
+
+    async fn purge(&self, delay: i64) -> DbResult<usize> {
+        let time = Local::now().checked_sub_signed(Duration::seconds(delay)).unwrap().naive_local();
+        let mut rows = self.rows.lock().unwrap();
+        let mut deleted = self.deleted.lock().unwrap();
+        let before = rows.len();
+        deleted.retain(|k, v| {
+            let purge = v.0 < time;
+            if purge {
+                rows.remove(k);
+            }
+            !purge
+        });
+        Ok(before - rows.len())
+    }
+}
+
+#[async_trait]
+impl KeyRange for MemInstanceDao {
+    /// ins_key > and between time range
+    async fn get_range(&self, condition: &RangeCondition) -> DbResult<Vec<Instance>> {
+        let cursor = condition.get_cursor()?;
+        let f_para = &condition.key;
+        let limit = condition.get_limit();
+        let meta = f_para.meta.to_string() + "%";
+        let rows = self.rows.lock().unwrap();
+        let deleted = self.deleted.lock().unwrap();
+        let rtn = rows.iter()
+            .filter(|(k, v)| {
+                let key = k.0.as_str();
+                !deleted.contains_key(k)
+                    && (f_para.meta.is_empty() || like(key, &meta))
+                    && (f_para.key_gt.is_empty() || key > f_para.key_gt.as_str())
+                    && (f_para.key_ge.is_empty() || key >= f_para.key_ge.as_str())
+                    && (f_para.key_lt.is_empty() || key < f_para.key_lt.as_str())
+                    && (f_para.key_le.is_empty() || key <= f_para.key_le.as_str())
+                    && f_para.time_ge.filter(|ge| v.create_time < *ge).is_none()
+                    && f_para.time_lt.filter(|lt| v.create_time >= *lt).is_none()
+                    && cursor.as_ref().filter(|c| (&k.0, k.1) <= (&c.0, c.1)).is_none()
+            })
+            .take(limit as usize + 1)
+            .map(|(_, v)| v.clone())
+            .collect();
+        Ok(rtn)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use futures::TryStreamExt;
+
+    use crate::{DbError, FlowSelector, SELECTOR_BATCH_LIMIT, StateInsert};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn insert_and_query_test() {
+        let dao = MemInstanceDao::default();
+        let mut ins = Instance::new("mem/instance").unwrap();
+        ins.id = 123;
+        ins.content = "hello".to_string();
+        assert_eq!(dao.insert(&ins).await.unwrap(), 1);
+        let rtn = dao.insert(&ins).await;
+        assert!(matches!(rtn, Err(DbError::Duplicated(_))));
+
+        let got = dao.get_by_id(KeyCondition::from(&ins)).await.unwrap().unwrap();
+        assert_eq!(got, ins);
+        let got = dao.get_by_key(ins.get_key(), "|".to_string()).await.unwrap();
+        assert!(got.is_some());
+
+        let mut para = KeyCondition::new(0, "B:mem/instance:1", "", 0);
+        para.id = "".to_string();
+        para.limit = 10;
+        let mut para = RangeCondition::from(para);
+        assert_eq!(dao.get_by_key_range(&para).await.unwrap().instances.len(), 1);
+        para.key.key_gt = ins.key_no_state();
+        assert_eq!(dao.get_by_key_range(&para).await.unwrap().instances.len(), 0);
+
+        assert_eq!(dao.delete(&ins).await.unwrap(), 1);
+        let got = dao.get_by_id(KeyCondition::from(&ins)).await.unwrap();
+        assert!(got.is_none());
+    }
+
+    #[tokio::test]
+    async fn instances_un_test() {
+        let dao = MemInstanceDao::default();
+        let from = FromInstance::from_str("B:from:1|1||0").unwrap();
+        let mut ins = Instance::new("mem/state").unwrap();
+        ins.id = 1;
+        ins.from = Some(from.clone());
+        assert_eq!(dao.insert(&ins).await.unwrap(), 1);
+        // same upstream can't generate another state version
+        ins.state_version = 1;
+        let rtn = dao.insert(&ins).await;
+        assert!(matches!(rtn, Err(DbError::Duplicated(_))));
+
+        ins.from = Some(FromInstance::from_str("B:from:1|1||1").unwrap());
+        assert_eq!(dao.insert(&ins).await.unwrap(), 1);
+        let last = dao.get_last_state(&KeyCondition::from(&ins)).await.unwrap().unwrap();
+        assert_eq!(last.state_version, 1);
+
+        let f_para = IDAndFrom {
+            id: 1,
+            meta: "B:mem/state:1".to_string(),
+            from_key: from.to_string(),
+        };
+        let got = dao.get_by_from(&f_para).await.unwrap().unwrap();
+        assert_eq!(got.state_version, 0);
+
+        let mut para = HistoryCondition::new(&ins.key_no_state());
+        let history = dao.get_history(&para).await.unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].from, Some(from));
+        assert_eq!(history[1].state_version, 1);
+        para.version_ge = Some(1);
+        assert_eq!(dao.get_history(&para).await.unwrap().len(), 1);
+        para.version_le = Some(0);
+        assert_eq!(dao.get_history(&para).await.unwrap().len(), 0);
+    }
+
+    #[tokio::test]
+    async fn lineage_test() {
+        let dao = MemInstanceDao::default();
+        let new = |meta: &str, from: Option<&Instance>| {
+            let mut ins = Instance::new(meta).unwrap();
+            ins.id = 1;
+            ins.from = from.map(FromInstance::from);
+            ins
+        };
+        let order = new("mem/order", None);
+        let pay = new("mem/pay", Some(&order));
+        let bill = new("mem/bill", Some(&pay));
+        let stock = new("mem/stock", Some(&order));
+        for one in &[&order, &pay, &bill, &stock] {
+            dao.insert(one).await.unwrap();
+        }
+
+        let got = dao.get_downstream(&FromInstance::from(&order)).await.unwrap();
+        assert_eq!(got, vec![pay.clone(), stock.clone()]);
+
+        let lineage = dao.get_lineage(&pay, 5).await.unwrap();
+        assert_eq!(lineage.instance, pay);
+        let upstream = lineage.upstream.unwrap();
+        assert_eq!(upstream.instance, order);
+        assert!(upstream.upstream.is_none());
+        assert!(upstream.downstream.is_empty());
+        assert_eq!(lineage.downstream.len(), 1);
+        assert_eq!(lineage.downstream[0].instance, bill);
+
+        let lineage = dao.get_lineage(&order, 1).await.unwrap();
+        assert!(lineage.upstream.is_none());
+        assert_eq!(lineage.downstream.len(), 2);
+        assert!(lineage.downstream.iter().all(|one| one.downstream.is_empty()));
+        assert!(dao.get_lineage(&bill, 0).await.unwrap().upstream.is_none());
+    }
+
+    #[tokio::test]
+    async fn key_range_page_test() {
+        let dao = MemInstanceDao::default();
+        let mut ins = Instance::new("mem/page").unwrap();
+        for id in 1..3 {
+            ins.id = id;
+            for version in 0..2 {
+                ins.state_version = version;
+                ins.from = Some(FromInstance::from_str(&format!("B:from:1|1||{}", version)).unwrap());
+                dao.insert(&ins).await.unwrap();
+            }
+        }
+        let mut para = KeyCondition::new(0, "B:mem/page:1", "", 0);
+        para.limit = 3;
+        let mut para = RangeCondition::from(para);
+        let page = dao.get_by_key_range(&para).await.unwrap();
+        assert!(page.has_more);
+        assert_eq!(page.instances.len(), 3);
+        assert_eq!(page.next_cursor, Some("B:mem/page:1|2||0".to_string()));
+
+        para.cursor = page.next_cursor.unwrap();
+        let page = dao.get_by_key_range(&para).await.unwrap();
+        assert!(!page.has_more);
+        assert_eq!(page.next_cursor, None);
+        assert_eq!(page.instances.len(), 1);
+        assert_eq!(page.instances[0].get_key(), "B:mem/page:1|2||1");
+
+        // scan the whole range one row per page
+        para.cursor = "".to_string();
+        para.key.limit = 1;
+        let all: Vec<Instance> = dao.scan(para.clone()).try_collect().await.unwrap();
+        assert_eq!(all.len(), 4);
+        assert_eq!(all[3].get_key(), "B:mem/page:1|2||1");
+    }
+
+    #[tokio::test]
+    async fn key_range_selector_test() {
+        let dao = MemInstanceDao::default();
+        let mut ins = Instance::new("mem/selector").unwrap();
+        for (id, states) in [(1, vec!["paid"]), (2, vec!["paid", "shipped"]), (3, vec![]), (4, vec!["paid"])].iter() {
+            ins.id = *id;
+            ins.states = states.iter().map(|one| one.to_string()).collect();
+            dao.insert(&ins).await.unwrap();
+        }
+        let mut selector = FlowSelector::default();
+        selector.state_all.insert("paid".to_string());
+        selector.state_none.insert("shipped".to_string());
+        let mut para = KeyCondition::new(0, "B:mem/selector:1", "", 0);
+        para.limit = 2;
+        let mut para = RangeCondition::from(para);
+        para.selector = Some(selector);
+
+        // the page is filled though the second and the third are not selected
+        let page = dao.get_by_key_range(&para).await.unwrap();
+        assert!(!page.has_more);
+        assert_eq!(page.instances.iter().map(|one| one.id).collect::<Vec<ID>>(), vec![1, 4]);
+        para.key.limit = 1;
+        let page = dao.get_by_key_range(&para).await.unwrap();
+        assert!(page.has_more);
+        assert_eq!(page.instances[0].id, 1);
+        assert_eq!(page.next_cursor, Some(page.instances[0].get_key()));
+        let all: Vec<Instance> = dao.scan(para.clone()).try_collect().await.unwrap();
+        assert_eq!(all.iter().map(|one| one.id).collect::<Vec<ID>>(), vec![1, 4]);
+        // 0 is taken as `QUERY_SIZE_LIMIT`
+        para.key.limit = 0;
+        let page = dao.get_by_key_range(&para).await.unwrap();
+        assert!(!page.has_more);
+        assert_eq!(page.instances.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn key_range_batch_limit_test() {
+        let dao = MemInstanceDao::default();
+        let mut ins = Instance::new("mem/batch").unwrap();
+        // the ids have the same length so that the keys are in the same order
+        let last = 0x100 + *SELECTOR_BATCH_LIMIT as ID * 2;
+        for id in 0x100..=last {
+            ins.id = id;
+            if id == last {
+                ins.states.insert("paid".to_string());
+            }
+            dao.insert(&ins).await.unwrap();
+        }
+        let mut selector = FlowSelector::default();
+        selector.state_all.insert("paid".to_string());
+        let mut para = KeyCondition::new(0, "B:mem/batch:1", "", 0);
+        para.limit = 1;
+        let mut para = RangeCondition::from(para);
+        para.selector = Some(selector);
+
+        // each batch reads 2 rows and none of them is selected
+        let page = dao.get_by_key_range(&para).await.unwrap();
+        assert!(page.has_more);
+        assert!(page.instances.is_empty());
+        para.cursor = page.next_cursor.unwrap();
+        let page = dao.get_by_key_range(&para).await.unwrap();
+        assert!(!page.has_more);
+        assert_eq!(page.instances[0].id, last);
+
+        para.cursor = "".to_string();
+        let all: Vec<Instance> = dao.scan(para).try_collect().await.unwrap();
+        assert_eq!(all.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn delete_and_retain_test() {
+        let dao = MemInstanceDao::default();
+        let mut ins = Instance::new("mem/retention").unwrap();
+        ins.id = 1;
+        ins.create_time = Local::now().timestamp_millis();
+        for version in 0..3 {
+            ins.state_version = version;
+            ins.from = Some(FromInstance::from_str(&format!("B:from:1|1||{}", version)).unwrap());
+            dao.insert(&ins).await.unwrap();
+        }
+        let para = KeyCondition::from(&ins);
+        assert_eq!(dao.delete(&ins).await.unwrap(), 3);
+        assert!(dao.get_last_state(&para).await.unwrap().is_none());
+        // the key is still held
+        assert!(matches!(dao.insert(&ins).await, Err(DbError::Duplicated(_))));
+        assert_eq!(dao.restore(&ins).await.unwrap(), 3);
+        assert_eq!(dao.get_last_state(&para).await.unwrap().unwrap().state_version, 2);
+
+        let mut retention = Retention {
+            keep_versions: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(dao.retain("B:mem/retention:1", &retention).await.unwrap(), 1);
+        assert_eq!(dao.retain("B:mem/retention:1", &retention).await.unwrap(), 0);
+        // the version out of the retention is not restored
+        assert_eq!(dao.delete(&ins).await.unwrap(), 2);
+        assert_eq!(dao.restore(&ins).await.unwrap(), 2);
+        let history = dao.get_history(&HistoryCondition::new(&ins.key_no_state())).await.unwrap();
+        assert_eq!(history.iter().map(|one| one.state_version).collect::<Vec<i32>>(), vec![1, 2]);
+
+        let created = Local::now().checked_sub_signed(Duration::days(3)).unwrap().timestamp_millis();
+        let mut old = Instance::new("mem/retention").unwrap();
+        old.id = 2;
+        old.create_time = created;
+        dao.insert(&old).await.unwrap();
+        let mut state = Instance::new("mem/retention").unwrap();
+        state.id = 3;
+        state.create_time = created;
+        for version in 1..3 {
+            state.state_version = version;
+            state.from = Some(FromInstance::from_str(&format!("B:from:1|1||{}", version)).unwrap());
+            dao.insert(&state).await.unwrap();
+        }
+        retention.keep_days = Some(2);
+        Retention::set("B:mem/retention:1", retention).unwrap();
+        assert_eq!(dao.apply_retention().await.unwrap(), 2);
+        assert!(dao.get_by_id(KeyCondition::from(&old)).await.unwrap().is_none());
+        // the latest state version is kept however old it is
+        let last = dao.get_last_state(&KeyCondition::from(&state)).await.unwrap().unwrap();
+        assert_eq!(last.state_version, 2);
+
+        assert_eq!(dao.purge(3600).await.unwrap(), 0);
+        assert_eq!(dao.purge(-1).await.unwrap(), 3);
+        assert_eq!(dao.restore(&old).await.unwrap(), 0);
+        assert_eq!(dao.rows.lock().unwrap().len(), 3);
+        // the key is released
+        assert_eq!(dao.insert(&old).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn retain_deleted_latest_test() {
+        let dao = MemInstanceDao::default();
+        let mut ins = Instance::new("mem/retain_deleted").unwrap();
+        ins.id = 1;
+        ins.create_time = Local::now().checked_sub_signed(Duration::days(3)).unwrap().timestamp_millis();
+        for version in 1..4 {
+            ins.state_version = version;
+            ins.from = Some(FromInstance::from_str(&format!("B:from:1|1||{}", version)).unwrap());
+            dao.insert(&ins).await.unwrap();
+        }
+        dao.deleted.lock().unwrap().insert((ins.key_no_state(), 3), (Local::now().naive_local(), DeleteBy::User));
+        // the latest of the live versions is kept for both the days and the versions
+        let retention = Retention {
+            keep_days: Some(2),
+            keep_versions: Some(1),
+        };
+        assert_eq!(dao.retain("B:mem/retain_deleted:1", &retention).await.unwrap(), 1);
+        let last = dao.get_last_state(&KeyCondition::from(&ins)).await.unwrap().unwrap();
+        assert_eq!(last.state_version, 2);
+    }
+
+    #[tokio::test]
+    async fn insert_state_test() {
+        let dao = MemInstanceDao::default();
+        let mut ins = Instance::new("mem/cas").unwrap();
+        ins.id = 1;
+        assert!(dao.insert_state(&ins).await.is_err());
+        ins.state_version = 2;
+        assert_eq!(dao.insert_state(&ins).await.unwrap(), StateInsert::Conflict(None));
+        ins.state_version = 1;
+        ins.from = Some(FromInstance::from_str("B:from:1|1||1").unwrap());
+        assert_eq!(dao.insert_state(&ins).await.unwrap(), StateInsert::Inserted);
+
+        // the other writer saw version 0 too
+        let mut other = ins.clone();
+        other.from = Some(FromInstance::from_str("B:from:1|1||2").unwrap());
+        assert_eq!(dao.insert_state(&other).await.unwrap(), StateInsert::Conflict(Some(Box::new(ins.clone()))));
+        other.state_version = 2;
+        assert_eq!(dao.insert_state(&other).await.unwrap(), StateInsert::Inserted);
+
+        // the upstream is redelivered after the state moved on
+        let mut again = ins.clone();
+        again.state_version = 3;
+        assert_eq!(dao.insert_state(&again).await.unwrap(), StateInsert::Repeated(Box::new(ins.clone())));
+
+        // the deleted versions are still held
+        dao.delete(&ins).await.unwrap();
+        assert_eq!(dao.insert_state(&ins).await.unwrap(), StateInsert::Deleted);
+        let mut next = ins.clone();
+        next.from = Some(FromInstance::from_str("B:from:1|1||3").unwrap());
+        assert_eq!(dao.insert_state(&next).await.unwrap(), StateInsert::Deleted);
+        dao.purge(-1).await.unwrap();
+        assert_eq!(dao.insert_state(&next).await.unwrap(), StateInsert::Inserted);
+    }
+}
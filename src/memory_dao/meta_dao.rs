@@ -0,0 +1,92 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use nature_common::Meta;
+
+use crate::{DbResult, MetaDao};
+use crate::raw_models::RawMeta;
+
+use super::duplicated;
+
+/// rows are keyed by (meta_type, meta_key, version) which is the primary key of `meta`
+#[derive(Default)]
+pub struct MemMetaDao {
+    rows: Mutex<BTreeMap<(String, String, i32), RawMeta>>,
+}
+
+fn pk(m: &Meta) -> (String, String, i32) {
+    (m.get_meta_type().get_prefix(), m.get_key(), m.version as i32)
+}
+
+#[async_trait]
+impl MetaDao for MemMetaDao {
+    async fn get(&self, meta_str: &str) -> DbResult<Option<RawMeta>> {
+        let m = Meta::from_string(meta_str)?;
+        let rows = self.rows.lock().unwrap();
+        Ok(rows.get(&pk(&m)).filter(|one| one.flag == 1).cloned())
+    }
+
+    async fn insert(&self, define: &RawMeta) -> DbResult<usize> {
+        let key = (define.meta_type.clone(), define.meta_key.clone(), define.version);
+        let mut rows = self.rows.lock().unwrap();
+        if rows.contains_key(&key) {
+            return Err(duplicated("PRIMARY", &format!("{}-{}-{}", key.0, key.1, key.2)));
+        }
+        rows.insert(key, define.clone());
+        debug!("Saved meta : {}:{}:{}", define.meta_type, define.meta_key, define.version);
+        Ok(1)
+    }
+
+    async fn update_flag(&self, meta_str: &str, flag_f: i32) -> DbResult<usize> {
+        let m = Meta::from_string(meta_str)?;
+        let mut rows = self.rows.lock().unwrap();
+        match rows.get_mut(&pk(&m)) {
+            Some(one) => {
+                one.flag = flag_f;
+                Ok(1)
+            }
+            None => Ok(0)
+        }
+    }
+
+    async fn delete(&self, m: &Meta) -> DbResult<usize> {
+        let rtn = self.rows.lock().unwrap().remove(&pk(m));
+        Ok(rtn.map_or(0, |_| 1))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::prelude::*;
+
+    use crate::DbError;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn define_test() {
+        let dao = MemMetaDao::default();
+        let define = RawMeta {
+            meta_type: "B".to_string(),
+            description: Some("description".to_string()),
+            version: 100,
+            states: Some("status".to_string()),
+            fields: Some("fields".to_string()),
+            config: "{}".to_string(),
+            flag: 1,
+            create_time: Local::now().naive_local(),
+            meta_key: "mem".to_string(),
+        };
+        let meta = "B:mem:100";
+
+        assert_eq!(dao.insert(&define).await.unwrap(), 1);
+        let rtn = dao.insert(&define).await;
+        assert!(matches!(rtn, Err(DbError::Duplicated(_))));
+        assert_eq!(dao.get(meta).await.unwrap(), Some(define));
+
+        assert_eq!(dao.update_flag(meta, 0).await.unwrap(), 1);
+        assert_eq!(dao.get(meta).await.unwrap(), None);
+
+        assert_eq!(dao.delete(&Meta::from_string(meta).unwrap()).await.unwrap(), 1);
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use nature_common::Executor;
+
+use crate::{DbResult, MetaCache, MetaDao, Relation, RelationDao, Relations, RelationSettings};
+use crate::raw_models::RawRelation;
+
+use super::duplicated;
+
+/// rows are keyed by (from_meta, to_meta) which is the primary key of `relation`
+#[derive(Default)]
+pub struct MemRelationDao {
+    rows: Mutex<BTreeMap<(String, String), RawRelation>>,
+}
+
+#[async_trait]
+impl RelationDao for MemRelationDao {
+    async fn get_relations<MC, M>(&self, from: &str, meta_cache_getter: &MC, meta_getter: &M) -> Relations
+        where MC: MetaCache, M: MetaDao {
+        let raws: Vec<RawRelation> = self.rows.lock().unwrap().values()
+            .filter(|one| one.from_meta == from && one.flag == 1)
+            .cloned()
+            .collect();
+        let mut rtn: Vec<Relation> = Vec::new();
+        for d in raws {
+            rtn.push(Relation::from_raw(d, meta_cache_getter, meta_getter).await?);
+        }
+        Ok(rtn)
+    }
+
+    async fn insert(&self, one: RawRelation) -> DbResult<usize> {
+        let key = (one.from_meta.clone(), one.to_meta.clone());
+        let mut rows = self.rows.lock().unwrap();
+        if rows.contains_key(&key) {
+            return Err(duplicated("PRIMARY", &format!("{}-{}", key.0, key.1)));
+        }
+        debug!("Saved relation : {} -> {}", one.from_meta, one.to_meta);
+        rows.insert(key, one);
+        Ok(1)
+    }
+
+    async fn delete(&self, one: RawRelation) -> DbResult<usize> {
+        let rtn = self.rows.lock().unwrap().remove(&(one.from_meta.clone(), one.to_meta.clone()));
+        debug!("relation deleted : {} -> {}", one.from_meta, one.to_meta);
+        Ok(rtn.map_or(0, |_| 1))
+    }
+
+    /// `from` and `to`'s form are full_key:version
+    async fn update_flag(&self, from: &str, to: &str, flag_f: i32) -> DbResult<usize> {
+        let mut rows = self.rows.lock().unwrap();
+        match rows.get_mut(&(from.to_string(), to.to_string())) {
+            Some(one) => {
+                one.settings = "".to_string();
+                one.flag = flag_f;
+                Ok(1)
+            }
+            None => Ok(0)
+        }
+    }
+
+    /// `version` will be set to 0
+    async fn insert_by_biz(&self, from: &str, to: &str, url: &str, protocol: &str) -> DbResult<RawRelation> {
+        let one = RawRelation::new(
+            from,
+            to,
+            &RelationSettings {
+                selector: None,
+                executor: Some(Executor {
+                    protocol: nature_common::Protocol::from_str(protocol)?,
+                    url: url.to_string(),
+                    settings: "".to_string(),
+                }),
+                filter_before: vec![],
+                filter_after: vec![],
+                use_upstream_id: false,
+                target: Default::default(),
+                delay: 0,
+                delay_on_para: (0, 0),
+                id_bridge: false,
+                priority: 0,
+                retry: None,
+            },
+        )?;
+        let _ = self.insert(one.clone()).await;
+        Ok(one)
+    }
+
+    async fn delete_by_biz(&self, from: &str, to: &str) -> DbResult<usize> {
+        let row = RawRelation {
+            from_meta: from.to_string(),
+            to_meta: to.to_string(),
+            settings: String::new(),
+            flag: 1,
+        };
+        self.delete(row).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use nature_common::{Meta, Result};
+
+    use crate::{DbError, MemMetaDao};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn relation_test() {
+        let dao = MemRelationDao::default();
+        let meta = "B:from:1";
+        let rtn = dao.get_relations(meta, &MCMock {}, &MemMetaDao::default()).await.unwrap();
+        assert!(rtn.is_empty());
+
+        let one = dao.insert_by_biz(meta, "B:to:1", "url", "http").await.unwrap();
+        let rtn = dao.insert(one).await;
+        assert!(matches!(rtn, Err(DbError::Duplicated(_))));
+        let rtn = dao.get_relations(meta, &MCMock {}, &MemMetaDao::default()).await.unwrap();
+        assert_eq!(rtn.len(), 1);
+
+        assert_eq!(dao.update_flag(meta, "B:to:1", 0).await.unwrap(), 1);
+        let rtn = dao.get_relations(meta, &MCMock {}, &MemMetaDao::default()).await.unwrap();
+        assert!(rtn.is_empty());
+
+        assert_eq!(dao.delete_by_biz(meta, "B:to:1").await.unwrap(), 1);
+    }
+
+    #[derive(Copy, Clone)]
+    struct MCMock;
+
+    #[async_trait]
+    impl MetaCache for MCMock {
+        async fn get<M>(&self, meta_str: &str, _getter: &M) -> Result<Meta> where M: MetaDao {
+            Meta::from_string(meta_str)
+        }
+    }
+}
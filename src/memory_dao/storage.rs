@@ -0,0 +1,41 @@
+use std::sync::Arc;
+
+use crate::{DbResult, MemInstanceDao, MemMetaDao, MemRelationDao, MemTaskDao, MemTx, Storage};
+
+/// Each `MemStorage` owns its own data, so tests won't interfere with each other.
+#[derive(Default)]
+pub struct MemStorage {
+    instance: Arc<MemInstanceDao>,
+    task: Arc<MemTaskDao>,
+    meta: MemMetaDao,
+    relation: MemRelationDao,
+}
+
+#[async_trait]
+impl Storage for MemStorage {
+    type Instance = MemInstanceDao;
+    type Task = MemTaskDao;
+    type Meta = MemMetaDao;
+    type Relation = MemRelationDao;
+    type Tx = MemTx;
+
+    fn instance(&self) -> &Self::Instance {
+        &self.instance
+    }
+
+    fn task(&self) -> &Self::Task {
+        &self.task
+    }
+
+    fn meta(&self) -> &Self::Meta {
+        &self.meta
+    }
+
+    fn relation(&self) -> &Self::Relation {
+        &self.relation
+    }
+
+    async fn begin(&self) -> DbResult<Self::Tx> {
+        Ok(MemTx::new(self.instance.clone(), self.task.clone()))
+    }
+}
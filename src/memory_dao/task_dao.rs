@@ -0,0 +1,504 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use chrono::{Duration, Local, NaiveDateTime};
+
+use nature_common::{NatureError, Result};
+
+use crate::{check_update_states, Condition, DbResult, is_overdue, lease_expire, TaskDao, TaskErrorCondition, TaskErrorDao, TaskQueue, TaskState};
+use crate::raw_models::{RawTask, RawTaskError};
+
+/// `task` and `task_error` are keyed by `task_id`, `task_un` is checked on insert.
+#[derive(Default)]
+pub struct MemTaskDao {
+    pub(super) tasks: Mutex<BTreeMap<String, RawTask>>,
+    errors: Mutex<BTreeMap<String, RawTaskError>>,
+    /// `task_id` to `lease_owner` and `lease_expire`, always locked after `tasks`
+    leases: Mutex<Leases>,
+}
+
+type Leases = BTreeMap<String, (String, NaiveDateTime)>;
+
+/// checks the primary key and `task_un`
+pub(super) fn is_repeated(tasks: &BTreeMap<String, RawTask>, raw: &RawTask) -> bool {
+    tasks.contains_key(&raw.task_id) || tasks.values()
+        .any(|t| t.task_key == raw.task_key && t.task_type == raw.task_type && t.task_for == raw.task_for)
+}
+
+/// the same as `TaskDao::get_overdue_in`
+fn overdue(tasks: &BTreeMap<String, RawTask>, leases: &Leases, queue: &TaskQueue, delay: i64, limit: i64) -> Vec<RawTask> {
+    let mut rtn: Vec<RawTask> = tasks.values()
+        .filter(|t| queue.is_match(t) && is_overdue(t, leases.get(&t.task_id).map(|(_, expire)| *expire), delay))
+        .cloned()
+        .collect();
+    rtn.sort_by_key(|t| (Reverse(t.priority), t.execute_time));
+    rtn.truncate(limit.max(0) as usize);
+    rtn
+}
+
+impl MemTaskDao {
+    /// the same as `TaskChecker::check`
+    pub fn check(&self, cfg: &Condition) -> Result<usize> {
+        let tasks = self.tasks.lock().unwrap();
+        let num = tasks.values()
+            .filter(|t| (cfg.key_gt.is_empty() || t.task_key > cfg.key_gt)
+                && (cfg.key_lt.is_empty() || t.task_key < cfg.key_lt)
+                && cfg.time_ge.filter(|ge| t.execute_time < *ge).is_none()
+                && cfg.time_lt.filter(|lt| t.create_time >= *lt).is_none()
+                && t.task_state == cfg.state)
+            .count();
+        Ok(num)
+    }
+}
+
+#[async_trait]
+impl TaskDao for MemTaskDao {
+    async fn insert(&self, raw: &RawTask) -> DbResult<usize> {
+        let mut tasks = self.tasks.lock().unwrap();
+        if is_repeated(&tasks, raw) {
+            warn!("==== task repeated. KEY: {} FOR: {} TYPE: {}", &raw.task_key, &raw.task_for, raw.task_type);
+            return Ok(0);
+        }
+        tasks.insert(raw.task_id.clone(), raw.clone());
+        debug!("---- saved task KEY: {} FOR: {} TYPE: {}", &raw.task_key, &raw.task_for, raw.task_type);
+        Ok(1)
+    }
+
+    async fn delete(&self, _record_id: &str) -> DbResult<usize> {
+        let rtn = self.tasks.lock().unwrap().remove(_record_id);
+        self.leases.lock().unwrap().remove(_record_id);
+        Ok(rtn.map_or(0, |_| 1))
+    }
+
+    /// delete finished task after `delay` seconds
+    async fn delete_finished(&self, _delay: i64) -> DbResult<usize> {
+        let _time = Local::now().checked_sub_signed(Duration::seconds(_delay)).unwrap().naive_local();
+        let mut tasks = self.tasks.lock().unwrap();
+        let before = tasks.len();
+        tasks.retain(|_, t| !(t.execute_time < _time && t.task_state == TaskState::Finished));
+        self.leases.lock().unwrap().retain(|id, _| tasks.contains_key(id));
+        Ok(before - tasks.len())
+    }
+
+    async fn raw_to_error(&self, err: &NatureError, raw: &RawTask) -> DbResult<usize> {
+        {
+            let mut tasks = self.tasks.lock().unwrap();
+            match tasks.get_mut(&raw.task_id) {
+                Some(t) if t.task_state == TaskState::Failed || t.task_state.can_change_to(TaskState::Failed) => {
+                    t.task_state = TaskState::Failed;
+                }
+                Some(_) => {
+                    warn!("==== task can't be failed. KEY: {} FOR: {} TYPE: {}", &raw.task_key, &raw.task_for, raw.task_type);
+                    return Ok(0);
+                }
+                // the one not found, e.g. failed before being saved, is moved too
+                None => {}
+            }
+        }
+        let rd = RawTaskError::from_raw(err, raw);
+        let num = {
+            let mut errors = self.errors.lock().unwrap();
+            let repeated = errors.contains_key(&rd.task_id) || errors.values()
+                .any(|e| e.task_key == rd.task_key && e.task_type == rd.task_type && e.task_for == rd.task_for);
+            if repeated {
+                warn!("==== task error repeated. KEY: {} FOR: {} TYPE: {}", &rd.task_key, &rd.task_for, rd.task_type);
+                0
+            } else {
+                errors.insert(rd.task_id.clone(), rd);
+                1
+            }
+        };
+        self.delete(&raw.task_id).await?;
+        Ok(num)
+    }
+
+    async fn get_overdue_in(&self, queue: &TaskQueue, delay: i64, _limit: i64) -> DbResult<Vec<RawTask>> {
+        let tasks = self.tasks.lock().unwrap();
+        let leases = self.leases.lock().unwrap();
+        Ok(overdue(&tasks, &leases, queue, delay, _limit))
+    }
+
+    async fn update_execute_time(&self, _record_id: &str, delay: i64) -> DbResult<usize> {
+        let _time = Local::now().checked_add_signed(Duration::seconds(delay)).unwrap().naive_local();
+        let mut tasks = self.tasks.lock().unwrap();
+        match tasks.get_mut(_record_id) {
+            Some(t) if t.task_state.is_active() => {
+                t.execute_time = _time;
+                Ok(1)
+            }
+            _ => Ok(0)
+        }
+    }
+
+    async fn finish_task(&self, _record_id: &str) -> DbResult<usize> {
+        let mut tasks = self.tasks.lock().unwrap();
+        match tasks.get_mut(_record_id) {
+            Some(t) if t.task_state.can_change_to(TaskState::Finished) => {
+                t.task_state = TaskState::Finished;
+                Ok(1)
+            }
+            _ => Ok(0)
+        }
+    }
+
+    /// increase one times and delay `delay` seconds
+    async fn increase_times_and_delay(&self, _record_id: &str, delay: i32) -> DbResult<usize> {
+        let _time = Local::now().checked_add_signed(Duration::seconds(delay as i64)).unwrap().naive_local();
+        let mut tasks = self.tasks.lock().unwrap();
+        match tasks.get_mut(_record_id) {
+            Some(t) if t.task_state.is_active() => {
+                t.execute_time = _time;
+                t.retried_times += 1;
+                t.task_state = TaskState::Pending;
+                self.leases.lock().unwrap().remove(_record_id);
+                Ok(1)
+            }
+            _ => Ok(0)
+        }
+    }
+
+    async fn get(&self, _record_id: &str) -> DbResult<Option<RawTask>> {
+        Ok(self.tasks.lock().unwrap().get(_record_id).cloned())
+    }
+
+    async fn update_state(&self, task_id: &str, from: TaskState, to: TaskState) -> DbResult<usize> {
+        from.check_change_to(to)?;
+        let mut tasks = self.tasks.lock().unwrap();
+        match tasks.get_mut(task_id) {
+            Some(t) if t.task_state == from => {
+                t.task_state = to;
+                Ok(1)
+            }
+            _ => Ok(0)
+        }
+    }
+
+    async fn update_states(&self, queue: &TaskQueue, from: &[TaskState], to: TaskState) -> DbResult<usize> {
+        check_update_states(queue, from, to)?;
+        let mut tasks = self.tasks.lock().unwrap();
+        let mut rtn = 0;
+        for t in tasks.values_mut().filter(|t| from.contains(&t.task_state) && queue.is_match(t)) {
+            t.task_state = to;
+            rtn += 1;
+        }
+        Ok(rtn)
+    }
+
+    async fn claim(&self, worker: &str, queue: &TaskQueue, delay: i64, lease: i64, limit: i64) -> DbResult<Vec<RawTask>> {
+        let _expire = lease_expire(worker, lease)?;
+        let mut tasks = self.tasks.lock().unwrap();
+        let mut leases = self.leases.lock().unwrap();
+        let mut rtn = overdue(&tasks, &leases, queue, delay, limit);
+        for t in rtn.iter_mut() {
+            t.task_state = TaskState::Running;
+            tasks.insert(t.task_id.clone(), t.clone());
+            leases.insert(t.task_id.clone(), (worker.to_string(), _expire));
+        }
+        debug!("---- {} tasks claimed by {}", rtn.len(), worker);
+        Ok(rtn)
+    }
+
+    async fn renew_lease(&self, task_id: &str, worker: &str, lease: i64) -> DbResult<usize> {
+        let _expire = lease_expire(worker, lease)?;
+        let tasks = self.tasks.lock().unwrap();
+        if tasks.get(task_id).filter(|t| t.task_state == TaskState::Running).is_none() {
+            return Ok(0);
+        }
+        match self.leases.lock().unwrap().get_mut(task_id) {
+            Some((owner, expire)) if owner == worker => {
+                *expire = _expire;
+                Ok(1)
+            }
+            _ => Ok(0)
+        }
+    }
+
+    async fn release_lease(&self, task_id: &str, worker: &str) -> DbResult<usize> {
+        let mut tasks = self.tasks.lock().unwrap();
+        let task = match tasks.get_mut(task_id) {
+            Some(t) if t.task_state == TaskState::Running => t,
+            _ => return Ok(0)
+        };
+        let mut leases = self.leases.lock().unwrap();
+        match leases.get(task_id) {
+            Some((owner, _)) if owner == worker => {
+                leases.remove(task_id);
+                task.task_state = TaskState::Pending;
+                Ok(1)
+            }
+            _ => Ok(0)
+        }
+    }
+}
+
+
+#[async_trait]
+impl TaskErrorDao for MemTaskDao {
+    async fn get_error(&self, task_id: &str) -> DbResult<Option<RawTaskError>> {
+        Ok(self.errors.lock().unwrap().get(task_id).cloned())
+    }
+
+    async fn get_errors(&self, condition: &TaskErrorCondition) -> DbResult<Vec<RawTaskError>> {
+        let errors = self.errors.lock().unwrap();
+        let mut rtn: Vec<RawTaskError> = errors.values()
+            .filter(|e| condition.is_match(e))
+            .cloned()
+            .collect();
+        rtn.sort_by(|a, b| a.task_key.cmp(&b.task_key));
+        rtn.truncate(condition.get_limit() as usize);
+        Ok(rtn)
+    }
+
+    async fn delete_error(&self, task_id: &str) -> DbResult<usize> {
+        let rtn = self.errors.lock().unwrap().remove(task_id);
+        Ok(rtn.map_or(0, |_| 1))
+    }
+
+    async fn purge_errors(&self, condition: &TaskErrorCondition) -> DbResult<usize> {
+        let mut errors = self.errors.lock().unwrap();
+        let before = errors.len();
+        errors.retain(|_, e| !condition.is_match(e));
+        Ok(before - errors.len())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{RetryPolicy, TaskRetry, TaskType};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn insert_repeat_test() {
+        let dao = MemTaskDao::default();
+        let mut task = RawTask {
+            task_id: "mem_repeat".to_string(),
+            task_key: "mem_repeat".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(dao.insert(&task).await.unwrap(), 1);
+        assert_eq!(dao.insert(&task).await.unwrap(), 0);
+        // task_un
+        task.task_id = "mem_repeat_other".to_string();
+        assert_eq!(dao.insert(&task).await.unwrap(), 0);
+        task.task_id = "mem_repeat".to_string();
+
+        assert!(dao.get("mem_repeat").await.unwrap().is_some());
+        let num = dao.raw_to_error(&NatureError::LogicalError("my test".to_string()), &task).await.unwrap();
+        assert_eq!(num, 1);
+        assert!(dao.get("mem_repeat").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn finish_and_overdue_test() {
+        let dao = MemTaskDao::default();
+        let task = RawTask {
+            task_id: "mem_finish".to_string(),
+            task_key: "mem_finish".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(dao.insert(&task).await.unwrap(), 1);
+        assert_eq!(dao.get_overdue(1, 100).await.unwrap().len(), 1);
+        assert_eq!(dao.increase_times_and_delay("mem_finish", 100).await.unwrap(), 1);
+        assert_eq!(dao.get_overdue(1, 100).await.unwrap().len(), 0);
+        assert_eq!(dao.get("mem_finish").await.unwrap().unwrap().retried_times, 1);
+        assert_eq!(dao.update_execute_time("mem_finish", -10).await.unwrap(), 1);
+
+        assert_eq!(dao.finish_task("mem_finish").await.unwrap(), 1);
+        assert_eq!(dao.finish_task("mem_finish").await.unwrap(), 0);
+        assert_eq!(dao.get_overdue(1, 100).await.unwrap().len(), 0);
+        let condition = Condition {
+            key_gt: "".to_string(),
+            key_lt: "".to_string(),
+            time_ge: None,
+            time_lt: None,
+            state: TaskState::Finished,
+        };
+        assert_eq!(dao.check(&condition).unwrap(), 1);
+        assert_eq!(dao.delete_finished(0).await.unwrap(), 1);
+        assert_eq!(dao.check(&condition).unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn claim_test() {
+        let dao = MemTaskDao::default();
+        for i in 0..3 {
+            let task = RawTask {
+                task_id: format!("mem_claim_{}", i),
+                task_key: format!("mem_claim_{}", i),
+                ..Default::default()
+            };
+            dao.insert(&task).await.unwrap();
+        }
+        let claimed = dao.claim("worker_a", &TaskQueue::default(), 1, 100, 2).await.unwrap();
+        assert_eq!(claimed.len(), 2);
+        assert_eq!(claimed[0].task_state, TaskState::Running);
+        assert_eq!(dao.get(&claimed[0].task_id).await.unwrap().unwrap().task_state, TaskState::Running);
+        let others = dao.claim("worker_b", &TaskQueue::default(), 1, 100, 10).await.unwrap();
+        assert_eq!(others.len(), 1);
+        assert!(!claimed.iter().any(|t| t.task_id == others[0].task_id));
+        assert_eq!(dao.claim("worker_b", &TaskQueue::default(), 1, 100, 10).await.unwrap().len(), 0);
+        assert_eq!(dao.get_overdue(1, 100).await.unwrap().len(), 0);
+
+        let id = &claimed[0].task_id;
+        assert_eq!(dao.renew_lease(id, "worker_b", 100).await.unwrap(), 0);
+        assert_eq!(dao.renew_lease(id, "worker_a", 100).await.unwrap(), 1);
+        assert_eq!(dao.release_lease(id, "worker_b").await.unwrap(), 0);
+        assert_eq!(dao.release_lease(id, "worker_a").await.unwrap(), 1);
+        assert_eq!(dao.get(id).await.unwrap().unwrap().task_state, TaskState::Pending);
+        assert_eq!(dao.claim("worker_b", &TaskQueue::default(), 1, 100, 10).await.unwrap()[0].task_id, *id);
+        assert!(dao.claim("", &TaskQueue::default(), 1, 100, 10).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn priority_test() {
+        let dao = MemTaskDao::default();
+        for (key, task_for, priority) in &[("B:mem/low:1|1||0", "B:to:1", 0), ("B:mem/high:1|1||0", "B:to:1", 5), ("B:other:1|1||0", "B:fast:1", 1)] {
+            let mut task = RawTask::from_str("data", key, TaskType::Store, task_for).unwrap();
+            task.priority = *priority;
+            dao.insert(&task).await.unwrap();
+        }
+        let overdue = dao.get_overdue(1, 100).await.unwrap();
+        let keys: Vec<&str> = overdue.iter().map(|t| t.task_key.as_str()).collect();
+        assert_eq!(keys, vec!["B:mem/high:1|1||0", "B:other:1|1||0", "B:mem/low:1|1||0"]);
+
+        let mut queue = TaskQueue {
+            task_for: "B:fast:1".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(dao.get_overdue_in(&queue, 1, 100).await.unwrap()[0].task_key, "B:other:1|1||0");
+        queue = TaskQueue::default();
+        queue.meta_prefix = "B:mem/".to_string();
+        let claimed = dao.claim("worker", &queue, 1, 100, 1).await.unwrap();
+        assert_eq!(claimed[0].task_key, "B:mem/high:1|1||0");
+        assert_eq!(dao.get_overdue_in(&queue, 1, 100).await.unwrap()[0].task_key, "B:mem/low:1|1||0");
+    }
+
+    #[tokio::test]
+    async fn retry_by_test() {
+        let dao = MemTaskDao::default();
+        let err = NatureError::EnvironmentError("executor is down".to_string());
+        let policy = RetryPolicy {
+            max_attempts: 1,
+            ..Default::default()
+        };
+        let task = RawTask::from_str("data", "B:mem/retry:1|1||0", TaskType::Store, "B:to:1").unwrap();
+        dao.insert(&task).await.unwrap();
+        assert_eq!(dao.retry_by(&task, &policy, &err).await.unwrap(), TaskRetry::Delayed(2));
+        let task = dao.get(&task.task_id).await.unwrap().unwrap();
+        assert_eq!(task.retried_times, 1);
+        assert_eq!(dao.retry_by(&task, &policy, &err).await.unwrap(), TaskRetry::Exhausted);
+        assert!(dao.get(&task.task_id).await.unwrap().is_none());
+        assert!(dao.get_error(&task.task_id).await.unwrap().is_some());
+
+        // the cancelled is neither delayed nor failed
+        let task = RawTask::from_str("data", "B:mem/retry:1|2||0", TaskType::Store, "B:to:1").unwrap();
+        dao.insert(&task).await.unwrap();
+        dao.cancel(&task.task_id).await.unwrap();
+        assert_eq!(dao.increase_times_and_delay(&task.task_id, 10).await.unwrap(), 0);
+        assert_eq!(dao.update_execute_time(&task.task_id, 10).await.unwrap(), 0);
+        assert_eq!(dao.raw_to_error(&err, &task).await.unwrap(), 0);
+        assert_eq!(dao.get(&task.task_id).await.unwrap().unwrap().task_state, TaskState::Cancelled);
+        assert!(dao.get_error(&task.task_id).await.unwrap().is_none());
+
+        // the one not saved is still moved to task_error
+        let task = RawTask::from_str("data", "B:mem/retry:1|3||0", TaskType::Store, "B:to:1").unwrap();
+        assert_eq!(dao.raw_to_error(&err, &task).await.unwrap(), 1);
+        assert!(dao.get_error(&task.task_id).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn state_test() {
+        let dao = MemTaskDao::default();
+        let task = RawTask::from_str("data", "B:mem/state:1|1||0", TaskType::Convert, "B:to:1").unwrap();
+        dao.insert(&task).await.unwrap();
+        assert_eq!(dao.set_state(&task.task_id, TaskState::Running).await.unwrap(), 1);
+        assert_eq!(dao.get_overdue(1, 100).await.unwrap().len(), 0);
+        // changed by others
+        assert_eq!(dao.update_state(&task.task_id, TaskState::Pending, TaskState::Running).await.unwrap(), 0);
+        assert!(dao.set_state(&task.task_id, TaskState::Paused).await.is_err());
+        assert_eq!(dao.finish_task(&task.task_id).await.unwrap(), 1);
+        assert!(dao.set_state(&task.task_id, TaskState::Pending).await.is_err());
+        assert_eq!(dao.set_state("none", TaskState::Pending).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn cancel_and_pause_test() {
+        let dao = MemTaskDao::default();
+        let mut ids = vec![];
+        for key in &["B:mem/pause:1|1||0", "B:mem/pause:1|2||0", "B:mem/cancel:1|1||0"] {
+            let task = RawTask::from_str("data", key, TaskType::Convert, "B:down:1").unwrap();
+            dao.insert(&task).await.unwrap();
+            ids.push(task.task_id);
+        }
+        let mut queue = TaskQueue::default();
+        assert!(dao.pause_in(&queue).await.is_err());
+        queue.meta_prefix = "B:mem/pause:1".to_string();
+        assert_eq!(dao.pause_in(&queue).await.unwrap(), 2);
+        let overdue = dao.get_overdue(1, 100).await.unwrap();
+        assert_eq!(overdue.len(), 1);
+        assert_eq!(overdue[0].task_id, ids[2]);
+        assert_eq!(dao.cancel(&ids[2]).await.unwrap(), 1);
+        assert_eq!(dao.get_overdue(1, 100).await.unwrap().len(), 0);
+
+        assert_eq!(dao.resume(&ids[0]).await.unwrap(), 1);
+        assert_eq!(dao.resume(&ids[0]).await.unwrap(), 0);
+        assert_eq!(dao.get_overdue(1, 100).await.unwrap().len(), 1);
+        queue = TaskQueue::default();
+        queue.task_for = "B:down:1".to_string();
+        assert_eq!(dao.resume_in(&queue).await.unwrap(), 1);
+        assert_eq!(dao.cancel_in(&queue).await.unwrap(), 2);
+        assert_eq!(dao.get(&ids[1]).await.unwrap().unwrap().task_state, TaskState::Cancelled);
+        assert_eq!(dao.pause(&ids[1]).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn insert_batch_test() {
+        let dao = MemTaskDao::default();
+        let task = RawTask {
+            task_id: "mem_batch".to_string(),
+            ..Default::default()
+        };
+        let mut other = task.clone();
+        other.task_id = "mem_batch_other".to_string();
+        other.task_key = "mem_batch_other".to_string();
+        assert_eq!(dao.insert_batch(&[task.clone(), task, other]).await.unwrap(), vec![1, 0, 1]);
+    }
+
+    #[tokio::test]
+    async fn task_error_test() {
+        let dao = MemTaskDao::default();
+        let err = NatureError::LogicalError("my test".to_string());
+        for (key, task_type) in &[("B:mem/error:1|1||0", TaskType::Store), ("B:mem/error:1|2||0", TaskType::Convert), ("B:mem/other:1|1||0", TaskType::Store)] {
+            let mut task = RawTask::from_str("data", key, *task_type, "B:to:1").unwrap();
+            task.retried_times = 5;
+            dao.insert(&task).await.unwrap();
+            dao.raw_to_error(&err, &task).await.unwrap();
+        }
+        let mut condition = TaskErrorCondition {
+            limit: 10,
+            ..Default::default()
+        };
+        assert_eq!(dao.get_errors(&condition).await.unwrap().len(), 3);
+        condition.meta = "B:mem/error:1".to_string();
+        let errors = dao.get_errors(&condition).await.unwrap();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].task_for, "B:to:1");
+        condition.task_type = Some(TaskType::Convert);
+        assert_eq!(dao.get_errors(&condition).await.unwrap().len(), 1);
+
+        let ids: Vec<String> = errors.iter().map(|e| e.task_id.clone()).collect();
+        assert_eq!(dao.requeue(&ids).await.unwrap(), 2);
+        let task = dao.get(&ids[0]).await.unwrap().unwrap();
+        assert_eq!(task.retried_times, 0);
+        assert_eq!(task.task_for, "B:to:1");
+        assert!(dao.get_error(&ids[0]).await.unwrap().is_none());
+        assert_eq!(dao.requeue(&ids).await.unwrap(), 0);
+
+        condition = TaskErrorCondition::default();
+        condition.key_gt = "B:mem/other:1".to_string();
+        assert_eq!(dao.purge_errors(&condition).await.unwrap(), 1);
+        assert_eq!(dao.get_errors(&TaskErrorCondition::default()).await.unwrap().len(), 0);
+    }
+}
@@ -0,0 +1,175 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use nature_common::Instance;
+
+use crate::{DbError, DbResult, MemInstanceDao, MemTaskDao, StorageTx, TaskState};
+use crate::raw_models::{RawInstance, RawTask};
+
+use super::instance_dao::check_unique;
+use super::task_dao::is_repeated;
+
+/// Buffers the changes and applies them all at once in `commit`.
+pub struct MemTx {
+    instance: Arc<MemInstanceDao>,
+    task: Arc<MemTaskDao>,
+    instances: BTreeMap<(String, i32), Instance>,
+    tasks: BTreeMap<String, RawTask>,
+    finished: Vec<String>,
+    available: bool,
+}
+
+impl MemTx {
+    pub fn new(instance: Arc<MemInstanceDao>, task: Arc<MemTaskDao>) -> Self {
+        MemTx {
+            instance,
+            task,
+            instances: BTreeMap::new(),
+            tasks: BTreeMap::new(),
+            finished: vec![],
+            available: true,
+        }
+    }
+
+    fn check_available(&self) -> DbResult<()> {
+        match self.available {
+            true => Ok(()),
+            false => Err(DbError::Logical("transaction is not available".to_string()))
+        }
+    }
+}
+
+#[async_trait]
+impl StorageTx for MemTx {
+    async fn insert_instance(&mut self, instance: &Instance) -> DbResult<usize> {
+        self.check_available()?;
+        let rtn = RawInstance::new(instance).map_err(DbError::from)
+            .and_then(|_| check_unique(&self.instance.rows.lock().unwrap(), instance))
+            .and_then(|_| check_unique(&self.instances, instance));
+        if let Err(e) = rtn {
+            self.available = false;
+            return Err(e);
+        }
+        self.instances.insert((instance.key_no_state(), instance.state_version), instance.clone());
+        Ok(1)
+    }
+
+    async fn insert_task(&mut self, raw: &RawTask) -> DbResult<usize> {
+        self.check_available()?;
+        if is_repeated(&self.task.tasks.lock().unwrap(), raw) || is_repeated(&self.tasks, raw) {
+            warn!("==== task repeated. KEY: {} FOR: {} TYPE: {}", &raw.task_key, &raw.task_for, raw.task_type);
+            return Ok(0);
+        }
+        self.tasks.insert(raw.task_id.clone(), raw.clone());
+        Ok(1)
+    }
+
+    async fn finish_task(&mut self, task_id: &str) -> DbResult<usize> {
+        self.check_available()?;
+        if let Some(t) = self.tasks.get_mut(task_id) {
+            if !t.task_state.can_change_to(TaskState::Finished) {
+                return Ok(0);
+            }
+            t.task_state = TaskState::Finished;
+            return Ok(1);
+        }
+        if self.finished.iter().any(|one| one == task_id) {
+            return Ok(0);
+        }
+        match self.task.tasks.lock().unwrap().get(task_id) {
+            Some(t) if t.task_state.can_change_to(TaskState::Finished) => {
+                self.finished.push(task_id.to_string());
+                Ok(1)
+            }
+            _ => Ok(0)
+        }
+    }
+
+    async fn commit(mut self) -> DbResult<()> {
+        self.check_available()?;
+        let mut rows = self.instance.rows.lock().unwrap();
+        let mut tasks = self.task.tasks.lock().unwrap();
+        // others may have committed the same keys in the meantime
+        for ins in self.instances.values() {
+            check_unique(&rows, ins)?;
+        }
+        rows.append(&mut self.instances);
+        for (id, t) in std::mem::take(&mut self.tasks) {
+            if !is_repeated(&tasks, &t) {
+                tasks.insert(id, t);
+            }
+        }
+        for id in &self.finished {
+            if let Some(t) = tasks.get_mut(id) {
+                if t.task_state.can_change_to(TaskState::Finished) {
+                    t.task_state = TaskState::Finished;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn rollback(self) -> DbResult<()> {
+        self.check_available()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{InstanceDao, MemStorage, Storage, TaskDao};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn commit_test() {
+        let storage = MemStorage::default();
+        let old = RawTask {
+            task_id: "old".to_string(),
+            task_key: "old".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(storage.task().insert(&old).await.unwrap(), 1);
+        let new = RawTask {
+            task_id: "new".to_string(),
+            task_key: "new".to_string(),
+            ..Default::default()
+        };
+        let ins = Instance::new("mem/tx").unwrap();
+
+        let mut tx = storage.begin().await.unwrap();
+        assert_eq!(tx.insert_instance(&ins).await.unwrap(), 1);
+        let mut news = vec![new.clone(), old.clone()];
+        RawTask::save_batch_tx(&mut news, "old", &mut tx).await.unwrap();
+        assert_eq!(news, vec![new]);
+        // invisible before commit
+        assert!(storage.task().get("new").await.unwrap().is_none());
+        assert_eq!(storage.task().get("old").await.unwrap().unwrap().task_state, TaskState::Pending);
+        tx.commit().await.unwrap();
+
+        assert!(storage.task().get("new").await.unwrap().is_some());
+        assert_eq!(storage.task().get("old").await.unwrap().unwrap().task_state, TaskState::Finished);
+        assert!(storage.instance().get_last_state(&(&ins).into()).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn rollback_test() {
+        let storage = MemStorage::default();
+        let ins = Instance::new("mem/tx").unwrap();
+        let task = RawTask {
+            task_id: "t".to_string(),
+            ..Default::default()
+        };
+
+        let mut tx = storage.begin().await.unwrap();
+        assert_eq!(tx.insert_task(&task).await.unwrap(), 1);
+        assert_eq!(tx.insert_instance(&ins).await.unwrap(), 1);
+        assert!(matches!(tx.insert_instance(&ins).await, Err(DbError::Duplicated(_))));
+        assert!(tx.commit().await.is_err());
+        assert!(storage.task().get("t").await.unwrap().is_none());
+
+        let mut tx = storage.begin().await.unwrap();
+        assert_eq!(tx.insert_task(&task).await.unwrap(), 1);
+        tx.rollback().await.unwrap();
+        assert!(storage.task().get("t").await.unwrap().is_none());
+    }
+}
@@ -2,13 +2,19 @@ pub use self::flow_selector::*;
 pub use self::mission::*;
 pub use self::relation::*;
 pub use self::relation_setting::*;
+pub use self::retention::*;
+pub use self::retry_policy::*;
+pub use self::task_state::*;
 pub use self::task_type::*;
 
 pub mod flow_selector;
 pub mod define;
 pub mod task_type;
+pub mod task_state;
 pub mod mission;
 pub mod relation;
 pub mod relation_setting;
+pub mod retention;
+pub mod retry_policy;
 pub mod flow_tool;
 pub mod relation_target;
@@ -15,4 +15,34 @@ lazy_static! {
         env::var("QUERY_SIZE_LIMIT").unwrap_or_else(|_| "1000".to_string()).parse::<i32>().unwrap()
     };
 
+    /// batches read by one `get_by_key_range` with a selector, the caller continues from `next_cursor` after that
+    pub static ref SELECTOR_BATCH_LIMIT : usize = {
+        env::var("SELECTOR_BATCH_LIMIT").unwrap_or_else(|_| "10".to_string()).parse::<usize>().unwrap()
+    };
+
+    /// rows per multi-row `INSERT`
+    pub static ref BATCH_INSERT_SIZE : usize = batch_insert_size(&env::var("BATCH_INSERT_SIZE").unwrap_or_else(|_| "100".to_string()));
+
+}
+
+/// at least 1, for it is used as the size of `chunks`
+fn batch_insert_size(value: &str) -> usize {
+    let size = value.parse::<i64>().unwrap();
+    if size < 1 {
+        warn!("BATCH_INSERT_SIZE should be greater than 0, 1 is used instead of {}", size);
+        return 1;
+    }
+    size as usize
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn batch_insert_size_test() {
+        assert_eq!(batch_insert_size("100"), 100);
+        assert_eq!(batch_insert_size("0"), 1);
+        assert_eq!(batch_insert_size("-5"), 1);
+    }
 }
@@ -1,5 +1,8 @@
 use std::collections::HashSet;
-use nature_common::is_default;
+
+use nature_common::{Instance, is_default};
+
+use crate::models::flow_tool::{context_check, state_check};
 
 /// select an upstream
 #[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
@@ -33,10 +36,40 @@ pub struct FlowSelector {
     pub sys_context_none: HashSet<String>,
 }
 
+impl FlowSelector {
+    /// whether the instance satisfies all the conditions, the same as the routing does
+    pub fn is_match(&self, ins: &Instance) -> bool {
+        context_check(&ins.context, &self.context_none, &self.context_all, &self.context_any)
+            && context_check(&ins.sys_context, &self.sys_context_none, &self.sys_context_all, &self.sys_context_any)
+            && state_check(&ins.states, &self.state_none, &self.state_all, &self.state_any)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
+    #[test]
+    fn is_match_test() {
+        let mut ins = Instance::default();
+        ins.states.insert("paid".to_string());
+        let mut se = FlowSelector::default();
+        assert!(se.is_match(&ins));
+        se.state_all.insert("paid".to_string());
+        se.state_none.insert("shipped".to_string());
+        assert!(se.is_match(&ins));
+        ins.states.insert("shipped".to_string());
+        assert!(!se.is_match(&ins));
+
+        let mut se = FlowSelector::default();
+        se.sys_context_any.insert("target.id".to_string());
+        assert!(!se.is_match(&ins));
+        ins.sys_context.insert("target.id".to_string(), "1".to_string());
+        assert!(se.is_match(&ins));
+        se.context_all.insert("a".to_string());
+        assert!(!se.is_match(&ins));
+    }
+
     #[test]
     fn selector_serder_test() {
         let mut se = FlowSelector {
@@ -5,7 +5,7 @@ use chrono::{Local, TimeZone};
 
 use nature_common::{CONTEXT_DYNAMIC_PARA, DynamicConverter, Executor, get_para_and_key_from_para, Instance, is_default, Meta, MetaType, Result};
 
-use crate::{MetaCache, MetaDao, Relation};
+use crate::{MetaCache, MetaDao, Relation, RetryPolicy};
 use crate::flow_tool::ContextChecker;
 use crate::flow_tool::StateChecker;
 use crate::models::relation_target::RelationTarget;
@@ -21,6 +21,10 @@ pub struct Mission {
     pub delay: i32,
     pub sys_context: HashMap<String, String>,
     pub id_bridge: bool,
+    /// from `RelationSettings.priority`, for the `RawTask` of this mission
+    pub priority: i8,
+    /// from `RelationSettings.retry`
+    pub retry: Option<RetryPolicy>,
 }
 
 #[derive(Debug, Clone, Default, Deserialize, Serialize)]
@@ -48,6 +52,12 @@ pub struct MissionRaw {
     #[serde(skip_serializing_if = "is_default")]
     #[serde(default)]
     pub id_bridge: bool,
+    #[serde(skip_serializing_if = "is_default")]
+    #[serde(default)]
+    pub priority: i8,
+    #[serde(skip_serializing_if = "is_default")]
+    #[serde(default)]
+    pub retry: Option<RetryPolicy>,
 }
 
 impl From<Mission> for MissionRaw {
@@ -62,6 +72,8 @@ impl From<Mission> for MissionRaw {
             delay: input.delay,
             sys_context: input.sys_context,
             id_bridge: input.id_bridge,
+            priority: input.priority,
+            retry: input.retry,
         }
     }
 }
@@ -98,6 +110,8 @@ impl Mission {
                 delay: d.delay,
                 sys_context: Default::default(),
                 id_bridge: false,
+                priority: 0,
+                retry: None,
             };
             missions.push(mission)
         }
@@ -147,6 +161,8 @@ impl Mission {
             delay: raw.delay,
             sys_context: raw.sys_context.clone(),
             id_bridge: raw.id_bridge,
+            priority: raw.priority,
+            retry: raw.retry.clone(),
         };
         Ok(rtn)
     }
@@ -190,6 +206,8 @@ impl From<Relation> for Mission {
             delay: 0,
             sys_context: Default::default(),
             id_bridge: r.id_bridge,
+            priority: r.priority,
+            retry: r.retry.clone(),
         }
     }
 }
@@ -313,10 +331,12 @@ mod test {
         relation.use_upstream_id = true;
         relation.target = target;
         relation.delay = 2;
+        relation.priority = 3;
         let relations = vec![relation];
         let rtn = Mission::get_by_instance(&Instance::default(), &relations, context_check, state_check);
         let rtn = &rtn[0];
         assert_eq!(rtn.delay, 2);
+        assert_eq!(rtn.priority, 3);
         assert_eq!(rtn.executor, executor);
         assert_eq!(rtn.to, meta);
         assert_eq!(rtn.use_upstream_id, true);
@@ -3,7 +3,7 @@ use std::string::ToString;
 
 use nature_common::{Executor, Meta, NatureError, Protocol, Result};
 
-use crate::{FlowSelector, MetaCache, MetaDao, RawRelation, RelationSettings};
+use crate::{FlowSelector, MetaCache, MetaDao, RawRelation, RelationSettings, RetryPolicy};
 use crate::models::relation_target::RelationTarget;
 
 #[derive(Debug, Clone, Default, PartialEq)]
@@ -19,6 +19,8 @@ pub struct Relation {
     pub delay: i32,
     pub delay_on_pare: (i32, u8),
     pub id_bridge: bool,
+    pub priority: i8,
+    pub retry: Option<RetryPolicy>,
 }
 
 impl Iterator for Relation {
@@ -40,6 +42,9 @@ impl Relation {
                 return Err(NatureError::VerifyError(msg));
             }
         };
+        if let Some(retry) = &settings.retry {
+            retry.check()?;
+        }
         let selector = &settings.selector;
         let m_to = Relation::check_converter(&val.to_meta, meta_cache_getter, meta_getter, &settings).await?;
         let rtn = match settings.executor {
@@ -61,6 +66,8 @@ impl Relation {
                     delay: settings.delay,
                     delay_on_pare: settings.delay_on_para,
                     id_bridge: settings.id_bridge,
+                    priority: settings.priority,
+                    retry: settings.retry,
                 }
             }
             None => Relation {
@@ -75,6 +82,8 @@ impl Relation {
                 delay: settings.delay,
                 delay_on_pare: settings.delay_on_para,
                 id_bridge: settings.id_bridge,
+                priority: settings.priority,
+                retry: settings.retry,
             }
         };
         debug!("load {}", val.get_string());
@@ -115,7 +124,7 @@ mod test_from_raw {
 
     use nature_common::Protocol;
 
-    use crate::RawMeta;
+    use crate::{DbResult, RawMeta};
 
     use super::*;
 
@@ -163,6 +172,8 @@ mod test_from_raw {
             delay: 0,
             delay_on_para: (0, 0),
             id_bridge: false,
+            priority: 0,
+            retry: None,
         };
         let raw = RawRelation {
             from_meta: "B:from:1".to_string(),
@@ -207,19 +218,19 @@ mod test_from_raw {
 
     #[async_trait]
     impl MetaDao for MetaMock {
-        async fn get(&self, m: &str) -> Result<Option<RawMeta>> {
+        async fn get(&self, m: &str) -> DbResult<Option<RawMeta>> {
             Ok(Some(RawMeta::from(Meta::from_string(m)?)))
         }
 
-        async fn insert(&self, _define: &RawMeta) -> Result<usize> {
+        async fn insert(&self, _define: &RawMeta) -> DbResult<usize> {
             unimplemented!()
         }
 
-        async fn update_flag(&self, _meta_str: &str, _flag_f: i32) -> Result<usize> {
+        async fn update_flag(&self, _meta_str: &str, _flag_f: i32) -> DbResult<usize> {
             unimplemented!()
         }
 
-        async fn delete(&self, _m: &Meta) -> Result<usize> {
+        async fn delete(&self, _m: &Meta) -> DbResult<usize> {
             unimplemented!()
         }
     }
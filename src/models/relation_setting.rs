@@ -1,6 +1,6 @@
 use nature_common::{Executor, is_default};
 
-use crate::FlowSelector;
+use crate::{FlowSelector, RetryPolicy};
 use crate::relation_target::RelationTarget;
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
@@ -41,6 +41,14 @@ pub struct RelationSettings {
     #[serde(skip_serializing_if = "is_default")]
     #[serde(default)]
     pub id_bridge: bool,
+    /// the tasks of this relation are executed before the ones with lower priority when they are overdue
+    #[serde(skip_serializing_if = "is_default")]
+    #[serde(default)]
+    pub priority: i8,
+    /// how to retry the failed tasks of this relation, the retry worker decides if it's not set.
+    #[serde(skip_serializing_if = "is_default")]
+    #[serde(default)]
+    pub retry: Option<RetryPolicy>,
 }
 
 #[cfg(test)]
@@ -49,6 +57,8 @@ mod test {
 
     use nature_common::{Protocol, TargetState};
 
+    use crate::Backoff;
+
     use super::*;
 
     #[test]
@@ -122,6 +132,29 @@ mod test {
         assert_eq!(res_obj, setting);
     }
 
+    #[test]
+    fn priority_test() {
+        let setting = RelationSettings {
+            priority: 5,
+            ..Default::default()
+        };
+        let result = serde_json::to_string(&setting).unwrap();
+        let res_str = r#"{"priority":5}"#;
+        assert_eq!(result, res_str);
+        let res_obj: RelationSettings = serde_json::from_str(res_str).unwrap();
+        assert_eq!(res_obj, setting);
+    }
+
+    #[test]
+    fn retry_test() {
+        let res_str = r#"{"retry":{"max_attempts":3,"backoff":"fixed","delay":10,"max_delay":10}}"#;
+        let res_obj: RelationSettings = serde_json::from_str(res_str).unwrap();
+        let retry = res_obj.retry.clone().unwrap();
+        assert_eq!(retry.backoff, Backoff::Fixed);
+        assert_eq!(retry.delay_of(2), 10);
+        assert_eq!(serde_json::to_string(&res_obj).unwrap(), res_str);
+    }
+
     #[test]
     fn target_state() {
         let state = TargetState { add: Some(vec!["new".to_string()]), remove: None, need_all: Default::default(), need_any: Default::default(), need_none: Default::default() };
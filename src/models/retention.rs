@@ -0,0 +1,74 @@
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+
+use nature_common::{is_default, NatureError, Result};
+
+lazy_static! {
+    static ref RETENTIONS : RwLock<BTreeMap<String, Retention>> = RwLock::new(BTreeMap::new());
+}
+
+/// How long the instances of a meta are kept, the instances out of it will be marked as deleted by
+/// `InstanceDao::retain` and removed by `InstanceDao::purge` later.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
+pub struct Retention {
+    /// keep the newest N state versions of each instance, the versions are taken as continuous.
+    #[serde(skip_serializing_if = "is_default")]
+    #[serde(default)]
+    pub keep_versions: Option<i32>,
+    /// drop the instances created X days ago
+    #[serde(skip_serializing_if = "is_default")]
+    #[serde(default)]
+    pub keep_days: Option<i32>,
+}
+
+impl Retention {
+    /// the `retention` replaces the one set before for the `meta`
+    pub fn set(meta: &str, retention: Retention) -> Result<()> {
+        retention.check()?;
+        RETENTIONS.write().unwrap().insert(meta.to_string(), retention);
+        Ok(())
+    }
+
+    /// the retention set for the `meta`
+    pub fn of(meta: &str) -> Option<Retention> {
+        RETENTIONS.read().unwrap().get(meta).cloned()
+    }
+
+    /// all the retentions set, ordered by meta
+    pub fn all() -> Vec<(String, Retention)> {
+        RETENTIONS.read().unwrap().iter().map(|(k, v)| (k.clone(), v.clone())).collect()
+    }
+
+    pub fn check(&self) -> Result<()> {
+        if self.keep_versions.filter(|n| *n < 1).is_some() {
+            return Err(NatureError::VerifyError("keep_versions should be greater than 0".to_string()));
+        }
+        if self.keep_days.filter(|n| *n < 1).is_some() {
+            return Err(NatureError::VerifyError("keep_days should be greater than 0".to_string()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn set_test() {
+        assert_eq!(serde_json::to_string(&Retention::default()).unwrap(), "{}");
+        let retention: Retention = serde_json::from_str(r#"{"keep_versions":3}"#).unwrap();
+        assert_eq!(retention.keep_versions, Some(3));
+        Retention::set("B:retention/set:1", retention.clone()).unwrap();
+        assert_eq!(Retention::of("B:retention/set:1"), Some(retention));
+        assert!(Retention::all().iter().any(|(meta, _)| meta == "B:retention/set:1"));
+        assert_eq!(Retention::of("B:retention/none:1"), None);
+
+        let wrong = Retention {
+            keep_days: Some(0),
+            ..Default::default()
+        };
+        assert!(Retention::set("B:retention/wrong:1", wrong).is_err());
+        assert_eq!(Retention::of("B:retention/wrong:1"), None);
+    }
+}
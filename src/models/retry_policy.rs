@@ -0,0 +1,112 @@
+use rand::Rng;
+
+use nature_common::{NatureError, Result};
+
+/// how the delay grows with the retried times
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Backoff {
+    /// always `RetryPolicy.delay`
+    Fixed,
+    /// `RetryPolicy.delay * 2^n` for the nth retry
+    Exponential,
+    /// the same as `Exponential` but a random part of its second half is cut off,
+    /// so that the tasks failed together won't be retried together.
+    Jitter,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff::Exponential
+    }
+}
+
+/// How the failed tasks of a relation are retried, see `TaskDao::retry_by`.
+/// The delays are in seconds.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// the task is moved to `task_error` when it fails after retried so many times, 0 for no retry
+    pub max_attempts: i16,
+    pub backoff: Backoff,
+    /// the delay of the first retry
+    pub delay: i32,
+    pub max_delay: i32,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 5,
+            backoff: Backoff::default(),
+            delay: 2,
+            max_delay: 3600,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// whether the task retried `retried` times should not be retried any more
+    pub fn is_exhausted(&self, retried: i16) -> bool {
+        retried >= self.max_attempts
+    }
+
+    /// the delay before the `retried + 1`th retry
+    pub fn delay_of(&self, retried: i16) -> i32 {
+        let exponential = || {
+            let times = 2i32.saturating_pow(retried.max(0) as u32);
+            self.delay.saturating_mul(times).min(self.max_delay)
+        };
+        match self.backoff {
+            Backoff::Fixed => self.delay.min(self.max_delay),
+            Backoff::Exponential => exponential(),
+            Backoff::Jitter => {
+                let delay = exponential();
+                let half = delay / 2;
+                delay - rand::thread_rng().gen_range(0, half + 1)
+            }
+        }
+    }
+
+    pub fn check(&self) -> Result<()> {
+        if self.max_attempts < 0 {
+            return Err(NatureError::VerifyError("max_attempts should not be less than 0".to_string()));
+        }
+        if self.delay < 0 || self.max_delay < self.delay {
+            let msg = format!("retry delay should be in [0, max_delay], delay: {}, max_delay: {}", self.delay, self.max_delay);
+            return Err(NatureError::VerifyError(msg));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn delay_test() {
+        let mut policy = RetryPolicy::default();
+        assert_eq!(policy.delay_of(0), 2);
+        assert_eq!(policy.delay_of(3), 16);
+        assert_eq!(policy.delay_of(100), 3600);
+        policy.backoff = Backoff::Fixed;
+        assert_eq!(policy.delay_of(3), 2);
+        policy.backoff = Backoff::Jitter;
+        let delay = policy.delay_of(3);
+        assert!((8..=16).contains(&delay));
+
+        assert!(!policy.is_exhausted(4));
+        assert!(policy.is_exhausted(5));
+        policy.max_delay = 1;
+        assert!(policy.check().is_err());
+    }
+
+    #[test]
+    fn serde_test() {
+        let policy: RetryPolicy = serde_json::from_str(r#"{"max_attempts":3,"backoff":"jitter"}"#).unwrap();
+        assert_eq!(policy.max_attempts, 3);
+        assert_eq!(policy.backoff, Backoff::Jitter);
+        assert_eq!(policy.max_delay, 3600);
+    }
+}
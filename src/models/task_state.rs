@@ -0,0 +1,119 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use nature_common::{NatureError, Result};
+
+/// The lifecycle of the task saved as `task.task_state`, serialized as its code.
+/// `Pending` and `Finished` keep the codes used before the others were added.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(into = "i8", try_from = "i8")]
+pub enum TaskState {
+    /// waiting to be executed or retried
+    Pending = 0,
+    Finished = 1,
+    /// claimed by a worker, see `TaskDao::claim`
+    Running = 2,
+    /// the retries are exhausted, it is moved to `task_error` and never fetched as overdue
+    Failed = 3,
+    Cancelled = 4,
+    /// skipped by the retry workers until it is resumed
+    Paused = 5,
+}
+
+impl Default for TaskState {
+    fn default() -> Self {
+        TaskState::Pending
+    }
+}
+
+impl TryFrom<i8> for TaskState {
+    type Error = NatureError;
+
+    fn try_from(value: i8) -> Result<Self> {
+        match value {
+            0 => Ok(TaskState::Pending),
+            1 => Ok(TaskState::Finished),
+            2 => Ok(TaskState::Running),
+            3 => Ok(TaskState::Failed),
+            4 => Ok(TaskState::Cancelled),
+            5 => Ok(TaskState::Paused),
+            _ => Err(NatureError::VerifyError(format!("undefined [{}] for `TaskState`", value)))
+        }
+    }
+}
+
+impl From<TaskState> for i8 {
+    fn from(state: TaskState) -> Self {
+        state as i8
+    }
+}
+
+/// the code, the same as it is saved
+impl fmt::Display for TaskState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", i8::from(*self))
+    }
+}
+
+impl TaskState {
+    /// waiting to be executed or being executed, only these are delayed or retried
+    pub const ACTIVE: &'static [TaskState] = &[TaskState::Pending, TaskState::Running];
+
+    /// the states which can be changed to this one, `Finished` and `Cancelled` are never changed.
+    pub fn sources(self) -> &'static [TaskState] {
+        use TaskState::*;
+        match self {
+            Pending => &[Running, Failed, Paused],
+            Running => &[Pending],
+            Finished => &[Pending, Running],
+            Failed => &[Pending, Running],
+            Cancelled => &[Pending, Running, Failed, Paused],
+            Paused => &[Pending],
+        }
+    }
+
+    pub fn is_active(self) -> bool {
+        TaskState::ACTIVE.contains(&self)
+    }
+
+    pub fn can_change_to(self, to: TaskState) -> bool {
+        to.sources().contains(&self)
+    }
+
+    /// `VerifyError` if it can't be changed to `to`
+    pub fn check_change_to(self, to: TaskState) -> Result<()> {
+        match self.can_change_to(to) {
+            true => Ok(()),
+            false => Err(NatureError::VerifyError(format!("task state can't be changed from {:?} to {:?}", self, to)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn change_test() {
+        assert!(TaskState::Pending.can_change_to(TaskState::Running));
+        assert!(TaskState::Running.can_change_to(TaskState::Finished));
+        assert!(TaskState::Paused.can_change_to(TaskState::Pending));
+        assert!(!TaskState::Paused.can_change_to(TaskState::Running));
+        assert!(!TaskState::Pending.can_change_to(TaskState::Pending));
+        assert!(TaskState::Finished.check_change_to(TaskState::Pending).is_err());
+        assert!(TaskState::Cancelled.check_change_to(TaskState::Pending).is_err());
+        assert!(TaskState::Running.is_active());
+        assert!(!TaskState::Failed.is_active());
+        assert!(!TaskState::Paused.is_active());
+    }
+
+    #[test]
+    fn code_test() {
+        for code in 0..6 {
+            assert_eq!(i8::from(TaskState::try_from(code).unwrap()), code);
+        }
+        assert!(TaskState::try_from(6).is_err());
+        assert_eq!(serde_json::to_string(&TaskState::Paused).unwrap(), "5");
+        assert_eq!(serde_json::from_str::<TaskState>("1").unwrap(), TaskState::Finished);
+    }
+}
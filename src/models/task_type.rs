@@ -1,13 +1,23 @@
 use std::convert::TryFrom;
+use std::fmt;
 
 use nature_common::NatureError;
 
+/// saved as `task.task_type`, serialized as its code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(into = "i8", try_from = "i8")]
 pub enum TaskType {
     Store = 1,
     Convert = 2,
     Batch = 11,
 }
 
+impl Default for TaskType {
+    fn default() -> Self {
+        TaskType::Store
+    }
+}
+
 impl TryFrom<i8> for TaskType {
     type Error = NatureError;
 
@@ -20,3 +30,30 @@ impl TryFrom<i8> for TaskType {
         }
     }
 }
+
+impl From<TaskType> for i8 {
+    fn from(task_type: TaskType) -> Self {
+        task_type as i8
+    }
+}
+
+/// the code, the same as it is saved
+impl fmt::Display for TaskType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", i8::from(*self))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn code_test() {
+        assert_eq!(i8::from(TaskType::Batch), 11);
+        assert_eq!(TaskType::try_from(2).unwrap(), TaskType::Convert);
+        assert!(TaskType::try_from(3).is_err());
+        assert_eq!(serde_json::to_string(&TaskType::Convert).unwrap(), "2");
+        assert!(serde_json::from_str::<TaskType>("0").is_err());
+    }
+}
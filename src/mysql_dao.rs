@@ -1,98 +1,306 @@
-use std::env;
+use std::sync::RwLock;
+use std::time::Duration;
 
-use mysql_async::{Conn, Params, Pool, Row};
+use futures::channel::mpsc;
+use futures::executor;
+use futures::SinkExt;
+use futures::stream::{BoxStream, StreamExt};
+use mysql_async::{Conn, Opts, OptsBuilder, Params, Pool, PoolConstraints, PoolOptions, Row, SslOpts};
 use mysql_async::error::{DriverError, Error};
 use mysql_async::prelude::*;
 
 pub use instance_dao::*;
 pub use meta_dao::*;
+pub use migration::*;
 use nature_common::{NatureError, Result};
 pub use relation_dao::*;
+pub use storage::*;
 pub use task_dao::*;
+pub use transaction::*;
+
+use crate::{DbConfig, DbError, DbResult, DbRetryPolicy};
 
 pub mod task_check;
 
 lazy_static! {
-   static ref POOL : Pool = get_conn();
+   static ref POOL : RwLock<Option<DbPool>> = RwLock::new(None);
+}
+
+#[derive(Clone)]
+struct DbPool {
+    pool: Pool,
+    connect_timeout: Option<Duration>,
+    retry: DbRetryPolicy,
 }
 
 pub struct MySql;
 
 impl MySql {
-    /// i(nsert) d(elete) u(pdate)
-    pub async fn idu<Q, P>(query: Q, params: P) -> Result<usize>
+    /// Should be called once before any other operation,
+    /// otherwise the pool will be created from `DbConfig::from_env` on first use.
+    pub fn init(cfg: &DbConfig) -> Result<()> {
+        let pool = new_pool(cfg)?;
+        let mut guard = POOL.write().unwrap();
+        if guard.is_some() {
+            return Err(NatureError::LogicalError("database had been initialized".to_string()));
+        }
+        *guard = Some(pool);
+        Ok(())
+    }
+
+    /// i(nsert) d(elete) u(pdate), the transient failures are retried by `DbConfig::retry`
+    /// only when the statement is known not applied, see `idu_idempotent` for the others.
+    pub async fn idu<Q, P>(query: Q, params: P) -> DbResult<usize>
         where
             Q: AsRef<str>,
             P: Into<Params>,
     {
-        let conn = MySql::get_conn().await?;
-        match conn.prep_exec(query, params).await {
-            Ok(num) => {
-                Ok(num.affected_rows() as usize)
-            }
-            Err(e) => return Err(MysqlError(e).into())
+        MySql::idu_retry(query, params, false).await
+    }
+
+    /// same as `idu` but the statement will be retried even if it may have been applied
+    /// before the connection lost, so it must be safe to apply more than once.
+    pub async fn idu_idempotent<Q, P>(query: Q, params: P) -> DbResult<usize>
+        where
+            Q: AsRef<str>,
+            P: Into<Params>,
+    {
+        MySql::idu_retry(query, params, true).await
+    }
+
+    async fn idu_retry<Q, P>(query: Q, params: P, idempotent: bool) -> DbResult<usize>
+        where
+            Q: AsRef<str>,
+            P: Into<Params>,
+    {
+        let db = get_pool()?;
+        let params: Params = params.into();
+        let mut retried = 0;
+        loop {
+            let err = match MySql::connect(&db).await {
+                Ok(conn) => match conn.prep_exec(query.as_ref(), params.clone()).await {
+                    Ok(num) => return Ok(num.affected_rows() as usize),
+                    Err(e) => match DbError::from(MysqlError(e)) {
+                        // it may have been applied before the connection lost
+                        DbError::ConnectionLost(msg) if !idempotent => return Err(DbError::ConnectionLost(msg)),
+                        e => e
+                    }
+                },
+                Err(e) => e
+            };
+            retried = backoff(&db.retry, err, retried).await?;
         }
     }
 
-    pub async fn fetch<Q, P, F, U>(query: Q, params: P, mut fun: F) -> Result<Vec<U>>
+    /// retried by `DbConfig::retry` on the transient failures
+    pub async fn fetch<Q, P, F, U>(query: Q, params: P, mut fun: F) -> DbResult<Vec<U>>
         where
             Q: AsRef<str>,
             P: Into<Params>,
             F: FnMut(Row) -> U,
     {
-        let conn = MySql::get_conn().await?;
-        match conn.prep_exec(query, params).await {
-            Ok(rtn) => {
-                match rtn.map_and_drop(|one| fun(one)).await {
-                    Ok((_, rtn)) => Ok(rtn),
-                    Err(e) => Err(MysqlError(e).into())
-                }
-            }
-            Err(e) => Err(MysqlError(e).into())
+        let db = get_pool()?;
+        let params: Params = params.into();
+        let mut retried = 0;
+        loop {
+            let err = match MySql::connect(&db).await {
+                Ok(conn) => match conn.prep_exec(query.as_ref(), params.clone()).await {
+                    Ok(rtn) => {
+                        match rtn.map_and_drop(&mut fun).await {
+                            Ok((_, rtn)) => return Ok(rtn),
+                            Err(e) => DbError::from(MysqlError(e))
+                        }
+                    }
+                    Err(e) => DbError::from(MysqlError(e))
+                },
+                Err(e) => e
+            };
+            retried = backoff(&db.retry, err, retried).await?;
         }
     }
 
+    /// Same as `fetch` but the rows are yielded one by one, no more than `buffer` of them are held
+    /// in memory whatever the result is. The rows are read on the blocking pool, which waits
+    /// while the buffer is full, so the reading goes at the pace of the consumer.
+    /// Only the query is retried by `DbConfig::retry`, an error after the first row ends the stream.
+    /// The rows left are still read but dropped if the stream is dropped before the end.
+    pub fn fetch_stream<F, U>(query: String, params: Params, buffer: usize, mut fun: F) -> BoxStream<'static, DbResult<U>>
+        where
+            F: FnMut(Row) -> U + Send + 'static,
+            U: Send + 'static,
+    {
+        let (mut tx, rx) = mpsc::channel(buffer.max(1));
+        let handle = tokio::runtime::Handle::current();
+        tokio::task::spawn_blocking(move || handle.block_on(async move {
+            let mut sink = tx.clone();
+            let rtn = async {
+                let db = get_pool()?;
+                let mut retried = 0;
+                let result = loop {
+                    let err = match MySql::connect(&db).await {
+                        Ok(conn) => match conn.prep_exec(query.as_str(), params.clone()).await {
+                            Ok(result) => break result,
+                            Err(e) => DbError::from(MysqlError(e))
+                        },
+                        Err(e) => e
+                    };
+                    retried = backoff(&db.retry, err, retried).await?;
+                };
+                let mut closed = false;
+                let rtn = result.for_each_and_drop(|row| {
+                    if !closed {
+                        closed = executor::block_on(sink.send(Ok(fun(row)))).is_err();
+                    }
+                }).await;
+                rtn.map(|_| ()).map_err(|e| DbError::from(MysqlError(e)))
+            }.await;
+            if let Err(e) = rtn {
+                let _ = tx.send(Err(e)).await;
+            }
+        }));
+        rx.boxed()
+    }
+
+    async fn get_conn() -> DbResult<Conn> {
+        MySql::connect(&get_pool()?).await
+    }
 
-    async fn get_conn() -> Result<Conn> {
-        match POOL.get_conn().await {
+    async fn connect(db: &DbPool) -> DbResult<Conn> {
+        let rtn = match db.connect_timeout {
+            None => db.pool.get_conn().await,
+            Some(timeout) => match tokio::time::timeout(timeout, db.pool.get_conn()).await {
+                Ok(rtn) => rtn,
+                Err(_) => {
+                    let msg = format!("get connection timeout after {:?}", timeout);
+                    warn!("{}", msg);
+                    return Err(DbError::ConnectionLost(msg));
+                }
+            }
+        };
+        match rtn {
             Ok(conn) => Ok(conn),
             Err(e) => Err(MysqlError(e).into())
         }
     }
 }
 
-fn get_conn() -> Pool {
-    let database_url = env::var("DATABASE_URL")
-        .expect("DATABASE_URL must be set");
-    Pool::new(database_url)
+/// returns the retried times after waiting for the next retry, or the `err` if it should not be retried.
+async fn backoff(retry: &DbRetryPolicy, err: DbError, retried: u32) -> DbResult<u32> {
+    if !err.is_retryable() || retried >= retry.times {
+        return Err(err);
+    }
+    let delay = retry.delay(retried);
+    warn!("retry {} of {} after {:?}", retried + 1, retry.times, delay);
+    tokio::time::delay_for(delay).await;
+    Ok(retried + 1)
+}
+
+fn get_pool() -> Result<DbPool> {
+    if let Some(db) = &*POOL.read().unwrap() {
+        return Ok(db.clone());
+    }
+    let mut guard = POOL.write().unwrap();
+    if guard.is_none() {
+        *guard = Some(new_pool(&DbConfig::from_env()?)?);
+    }
+    Ok(guard.as_ref().unwrap().clone())
+}
+
+fn new_pool(cfg: &DbConfig) -> Result<DbPool> {
+    cfg.verify()?;
+    let opts = match Opts::from_url(&cfg.url) {
+        Ok(opts) => opts,
+        Err(e) => return Err(MysqlError(e.into()).into())
+    };
+    let mut builder = OptsBuilder::from_opts(opts);
+    let constraints = match PoolConstraints::new(cfg.min_connections, cfg.max_connections) {
+        Some(c) => c,
+        None => return Err(NatureError::VerifyError("invalid connections range".to_string()))
+    };
+    let mut pool_options = PoolOptions::with_constraints(constraints);
+    if let Some(idle) = cfg.idle_timeout {
+        pool_options.set_inactive_connection_ttl(idle);
+        builder.conn_ttl(idle);
+    }
+    builder.pool_options(pool_options);
+    if let Some(tls) = &cfg.tls {
+        let mut ssl = SslOpts::default();
+        ssl.set_root_cert_path(tls.root_cert_path.clone())
+            .set_pkcs12_path(tls.pkcs12_path.clone())
+            .set_password(tls.pkcs12_password.clone())
+            .set_danger_accept_invalid_certs(tls.accept_invalid_certs)
+            .set_danger_skip_domain_validation(tls.skip_domain_validation);
+        builder.ssl_opts(ssl);
+    }
+    Ok(DbPool {
+        pool: Pool::new(builder),
+        connect_timeout: cfg.connect_timeout,
+        retry: cfg.retry.clone(),
+    })
 }
 
 
 pub struct MysqlError(mysql_async::error::Error);
 
-impl Into<nature_common::NatureError> for MysqlError {
-    fn into(self) -> NatureError {
-        let msg = format!("database exception: {}", self.0.to_string());
+impl From<MysqlError> for DbError {
+    fn from(err: MysqlError) -> Self {
+        let msg = format!("database exception: {}", err.0);
         warn!("{}", msg);
-        match self.0 {
+        match err.0 {
             Error::Driver(err) => match err {
-                DriverError::ConnectionClosed => NatureError::EnvironmentError(msg),
-                DriverError::PoolDisconnected => NatureError::EnvironmentError(msg),
-                _ => NatureError::LogicalError(msg)
+                DriverError::ConnectionClosed => DbError::ConnectionLost(msg),
+                DriverError::PoolDisconnected => DbError::ConnectionLost(msg),
+                _ => DbError::Logical(msg)
             },
-            Error::Io(_) => NatureError::EnvironmentError(msg),
-            Error::Other(_) => NatureError::LogicalError(msg),
+            Error::Io(_) => DbError::ConnectionLost(msg),
+            Error::Other(_) => DbError::Logical(msg),
             Error::Server(e) => match e.code {
-                1062 => NatureError::DaoDuplicated(msg),
-                _ => NatureError::EnvironmentError(msg)
+                1062 => DbError::Duplicated(msg),
+                1205 => DbError::LockTimeout(msg),
+                1213 => DbError::Deadlock(msg),
+                1406 => DbError::DataTooLong(msg),
+                _ => DbError::Environment(msg)
             }
-            Error::Tls(_) => NatureError::LogicalError(msg),
-            Error::Url(_) => NatureError::LogicalError(msg),
+            Error::Tls(_) => DbError::Logical(msg),
+            Error::Url(_) => DbError::Logical(msg),
         }
     }
 }
 
+impl From<MysqlError> for NatureError {
+    fn from(err: MysqlError) -> Self {
+        DbError::from(err).into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use mysql_async::error::ServerError;
+
+    use super::*;
+
+    #[test]
+    fn mysql_error_test() {
+        let server = |code: u16| MysqlError(Error::Server(ServerError {
+            code,
+            message: "server error".to_string(),
+            state: "HY000".to_string(),
+        }));
+        assert!(matches!(DbError::from(server(1213)), DbError::Deadlock(_)));
+        assert!(matches!(DbError::from(server(1205)), DbError::LockTimeout(_)));
+        assert!(matches!(DbError::from(server(1406)), DbError::DataTooLong(_)));
+        assert!(matches!(DbError::from(server(1146)), DbError::Environment(_)));
+        let err: NatureError = server(1062).into();
+        assert!(matches!(err, NatureError::DaoDuplicated(_)));
+        let lost = DbError::from(MysqlError(Error::Driver(DriverError::PoolDisconnected)));
+        assert!(lost.is_retryable());
+    }
+}
+
 mod instance_dao;
 mod meta_dao;
+mod migration;
 mod relation_dao;
-mod task_dao;
\ No newline at end of file
+mod storage;
+mod task_dao;
+mod transaction;
\ No newline at end of file
@@ -1,31 +1,34 @@
-use std::str::FromStr;
 
 use chrono::{Local, TimeZone};
-use mysql_async::Value;
+use futures::future;
+use futures::stream::{self, BoxStream, StreamExt, TryStreamExt};
+use mysql_async::{Params, Value};
 
 use nature_common::*;
 
-use crate::{Mission, QUERY_SIZE_LIMIT};
+use crate::{BATCH_INSERT_SIZE, DbError, DbResult, DeleteBy, HistoryCondition, InstanceDao, KeyRange, QUERY_SIZE_LIMIT, RangeCondition, remove_blobs, Retention};
 use crate::mysql_dao::MySql;
-use crate::raw_models::RawInstance;
+use crate::raw_models::{INSTANCE_COLUMNS, INSTANCE_FIELDS, multi_row_insert, RawInstance};
 
-#[async_trait]
-pub trait KeyRange: Sync + Send {
-    async fn get_by_key_range(&self, f_para: &KeyCondition) -> Result<Vec<Instance>>;
+lazy_static! {
+    pub static ref D_I: InstanceDaoImpl = InstanceDaoImpl {};
 }
 
 pub struct InstanceDaoImpl;
 
-impl InstanceDaoImpl {
-    pub async fn insert(instance: &Instance) -> Result<usize> {
-        let new = RawInstance::new(instance)?;
+#[async_trait]
+impl InstanceDao for InstanceDaoImpl {
+    async fn insert(&self, instance: &Instance) -> DbResult<usize> {
+        let mut new = RawInstance::new(instance)?;
+        let blob = new.put_blob().await?;
         let sql = r"INSERT INTO instances
-            (ins_key, content, context, states, state_version, create_time, sys_context, from_key)
-            VALUES(:ins_key, :content,:context,:states,:state_version,:create_time,:sys_context,:from_key)";
+            (ins_key, content, context, states, state_version, create_time, sys_context, from_key, content_ref, content_codec)
+            VALUES(:ins_key, :content,:context,:states,:state_version,:create_time,:sys_context,:from_key,:content_ref,:content_codec)";
         let vec: Vec<(String, Value)> = new.into();
         let rtn: usize = match MySql::idu(sql, vec).await {
             Ok(n) => n,
             Err(e) => {
+                remove_blobs(blob.into_iter().collect()).await;
                 return Err(e);
             }
         };
@@ -35,12 +38,12 @@ impl InstanceDaoImpl {
 
     //noinspection RsLiveness
     /// check whether source stored earlier
-    pub async fn get_by_from(f_para: &IDAndFrom) -> Result<Option<Instance>> {
-        let sql = r"SELECT ins_key, content, context, states, state_version, create_time, sys_context, from_key
+    async fn get_by_from(&self, f_para: &IDAndFrom) -> DbResult<Option<Instance>> {
+        let sql = format!("SELECT {}
             FROM instances
-            where ins_key like :para_like and from_key = :from_key
+            where ins_key like :para_like and from_key = :from_key and delete_time is null
             order by state_version desc
-            limit 1";
+            limit 1", INSTANCE_COLUMNS);
         let p = params! {
             "para_like" => f_para.para_like().to_string(),
             "from_key" => f_para.from_key.to_string(),
@@ -48,196 +51,327 @@ impl InstanceDaoImpl {
 
         let rtn = MySql::fetch(sql, p, RawInstance::from).await?;
         match rtn.len() {
-            1 => Ok(Some(rtn[0].to()?)),
+            1 => Ok(Some(rtn[0].to().await?)),
             0 => Ok(None),
-            _ => Err(NatureError::LogicalError("should not return more than one rows".to_string()))
+            _ => Err(DbError::Logical("should not return more than one rows".to_string()))
         }
     }
 
     //noinspection RsLiveness
-    async fn get_last_state(f_para: &KeyCondition) -> Result<Option<Instance>> {
-        let sql = r"SELECT ins_key, content, context, states, state_version, create_time, sys_context, from_key
+    async fn get_last_state(&self, f_para: &KeyCondition) -> DbResult<Option<Instance>> {
+        let sql = format!("SELECT {}
             FROM instances
-            where ins_key = :ins_key
+            where ins_key = :ins_key and delete_time is null
             order by state_version desc
-            limit 1";
+            limit 1", INSTANCE_COLUMNS);
         let p = params! {
             "ins_key" => f_para.get_key(),
         };
         let rtn = MySql::fetch(sql, p, RawInstance::from).await?;
         match rtn.len() {
-            1 => Ok(Some(rtn[0].to()?)),
+            1 => Ok(Some(rtn[0].to().await?)),
             0 => Ok(None),
-            _ => Err(NatureError::LogicalError("should not return more than one rows".to_string()))
+            _ => Err(DbError::Logical("should not return more than one rows".to_string()))
         }
     }
 
-    pub async fn get_by_key(key: String, spliter: String) -> Result<Option<Instance>> {
-        let temp: Vec<&str> = key.split(&spliter).collect();
-        if temp.len() != 4 {
-            return Err(NatureError::VerifyError("error key format for task".to_string()));
-        }
-        let para = KeyCondition {
-            id: temp[1].to_string(),
-            meta: temp[0].to_string(),
-            key_gt: "".to_string(),
-            key_ge: "".to_string(),
-            key_lt: "".to_string(),
-            key_le: "".to_string(),
-            para: temp[2].to_string(),
-            state_version: i32::from_str(temp[3])?,
-            time_ge: None,
-            time_lt: None,
-            limit: 1,
-        };
-        Self::get_by_id(para).await
-    }
-
     //noinspection RsLiveness
-    pub async fn get_by_id(f_para: KeyCondition) -> Result<Option<Instance>> {
-        let sql = r"SELECT ins_key, content, context, states, state_version, create_time, sys_context, from_key
+    async fn get_by_id(&self, f_para: KeyCondition) -> DbResult<Option<Instance>> {
+        let sql = format!("SELECT {}
             FROM instances
-            where ins_key = :ins_key and state_version = :state_version
+            where ins_key = :ins_key and state_version = :state_version and delete_time is null
             order by state_version desc
-            limit 1";
+            limit 1", INSTANCE_COLUMNS);
         let p = params! {
             "ins_key" => f_para.get_key().to_string(),
             "state_version" => f_para.state_version,
         };
         let rtn = MySql::fetch(sql, p, RawInstance::from).await?;
         match rtn.len() {
-            1 => Ok(Some(rtn[0].to()?)),
+            1 => Ok(Some(rtn[0].to().await?)),
             0 => Ok(None),
-            _ => Err(NatureError::LogicalError("should not return more than one rows".to_string()))
+            _ => Err(DbError::Logical("should not return more than one rows".to_string()))
         }
     }
 
-    pub async fn delete(ins: &Instance) -> Result<usize> {
-        let sql = r"DELETE FROM instances
-            WHERE ins_key=:ins_key";
+    async fn get_history(&self, f_para: &HistoryCondition) -> DbResult<Vec<Instance>> {
+        let version_ge = if f_para.version_ge.is_none() { "" } else {
+            " and state_version >= :version_ge"
+        };
+        let version_le = if f_para.version_le.is_none() { "" } else {
+            " and state_version <= :version_le"
+        };
+        let sql = format!("SELECT {}
+            FROM instances
+            where ins_key = :ins_key and delete_time is null{}{}
+            order by state_version
+            limit :limit", INSTANCE_COLUMNS, version_ge, version_le);
+        let p = params! {
+            "ins_key" => f_para.ins_key.to_string(),
+            "version_ge" => f_para.version_ge.unwrap_or(0),
+            "version_le" => f_para.version_le.unwrap_or(0),
+            "limit" => f_para.get_limit(),
+        };
+        let result = MySql::fetch(sql, p, RawInstance::from).await?;
+        let mut rtn: Vec<Instance> = vec![];
+        for one in result {
+            rtn.push(one.to().await?)
+        }
+        Ok(rtn)
+    }
+
+    async fn get_downstream(&self, from: &FromInstance) -> DbResult<Vec<Instance>> {
+        let sql = format!("SELECT {}
+            FROM instances
+            where from_key = :from_key and delete_time is null
+            order by ins_key, state_version
+            limit :limit", INSTANCE_COLUMNS);
         let p = params! {
-            "ins_key" => ins.get_key(),
+            "from_key" => from.to_string(),
+            "limit" => *QUERY_SIZE_LIMIT,
         };
-        let rtn: usize = MySql::idu(sql, p).await?;
+        let result = MySql::fetch(sql, p, RawInstance::from).await?;
+        let mut rtn: Vec<Instance> = vec![];
+        for one in result {
+            rtn.push(one.to().await?)
+        }
+        Ok(rtn)
+    }
+
+    async fn delete(&self, ins: &Instance) -> DbResult<usize> {
+        let sql = r"UPDATE instances
+            SET delete_time = now(), delete_by = :delete_by
+            WHERE ins_key=:ins_key and delete_time is null";
+        let p = params! {
+            "ins_key" => ins.key_no_state(),
+            "delete_by" => i8::from(DeleteBy::User),
+        };
+        let rtn: usize = MySql::idu_idempotent(sql, p).await?;
         debug!("instance deleted, id is : {:?}", ins.id);
         Ok(rtn)
     }
 
-    /// get downstream instance through upstream instance
-    pub async fn get_last_target(from: &Instance, mission: &mut Mission) -> Result<Option<Instance>> {
-        // init for MetaType::loop --------------------
-        if mission.to.get_meta_type() == MetaType::Loop
-            && mission.to.meta_string() == from.meta {
-            if let Some(setting) = mission.to.get_setting() {
-                if setting.only_one {
-                    debug!("make MetaType::Loop as last state for {}", from.meta);
-                    return Ok(Some(from.clone()));
-                }
-            }
+    async fn restore(&self, ins: &Instance) -> DbResult<usize> {
+        let sql = r"UPDATE instances
+            SET delete_time = null, delete_by = 0
+            WHERE ins_key=:ins_key and delete_by = :delete_by";
+        let p = params! {
+            "ins_key" => ins.key_no_state(),
+            "delete_by" => i8::from(DeleteBy::User),
+        };
+        let rtn: usize = MySql::idu_idempotent(sql, p).await?;
+        debug!("instance restored, id is : {:?}", ins.id);
+        Ok(rtn)
+    }
+
+    async fn retain(&self, meta: &str, retention: &Retention) -> DbResult<usize> {
+        retention.check()?;
+        let meta_like = format!("{}{}%", meta, SEPARATOR_INS_KEY.as_str());
+        let mut rtn: usize = 0;
+        if let Some(versions) = retention.keep_versions {
+            let sql = r"UPDATE instances i
+                JOIN (SELECT ins_key, max(state_version) AS max_version
+                    FROM instances
+                    WHERE ins_key like :meta and delete_time is null
+                    GROUP BY ins_key) m ON i.ins_key = m.ins_key
+                SET i.delete_time = now(), i.delete_by = :delete_by
+                WHERE i.delete_time is null and i.state_version <= m.max_version - :versions";
+            let p = params! {
+                "meta" => meta_like.to_string(),
+                "versions" => versions,
+                "delete_by" => i8::from(DeleteBy::Retention),
+            };
+            rtn += MySql::idu_idempotent(sql, p).await?;
         }
-        // normal ---------------------------
-        if !mission.to.is_state() {
-            return Ok(None);
+        if let Some(days) = retention.keep_days {
+            // version 0 is not a state instance, the others keep the latest version
+            let sql = r"UPDATE instances i
+                JOIN (SELECT ins_key, max(state_version) AS max_version
+                    FROM instances
+                    WHERE ins_key like :meta and delete_time is null
+                    GROUP BY ins_key) m ON i.ins_key = m.ins_key
+                SET i.delete_time = now(), i.delete_by = :delete_by
+                WHERE i.delete_time is null and i.create_time < date_sub(now(), interval :days day)
+                    and (i.state_version = 0 or i.state_version < m.max_version)";
+            let p = params! {
+                "meta" => meta_like,
+                "days" => days,
+                "delete_by" => i8::from(DeleteBy::Retention),
+            };
+            rtn += MySql::idu_idempotent(sql, p).await?;
         }
-        let para_part = &mission.target_demand.append_para;
-        let para_id = if para_part.len() > 0 {
-            let id = get_para_and_key_from_para(&from.para, para_part)?.0;
-            mission.sys_context.insert(CONTEXT_TARGET_INSTANCE_PARA.to_string(), id.to_string());
-            id
-        } else {
-            "".to_string()
-        };
-        let mut id: ID = match mission.sys_context.get(&*CONTEXT_TARGET_INSTANCE_ID) {
-            // context have target id
-            Some(state_id) => id_from_hex_str(state_id)?,
-            None => 0,
+        debug!("{} instances of {} are out of retention", rtn, meta);
+        Ok(rtn)
+    }
+
+    async fn purge(&self, delay: i64) -> DbResult<usize> {
+        let sql = r"SELECT content_ref FROM instances
+            WHERE delete_time < date_sub(now(), interval :delay second) and content_ref is not null";
+        let blobs = MySql::fetch(sql, params! {"delay" => delay}, mysql_async::from_row::<String>).await?;
+        let sql = r"DELETE FROM instances
+            WHERE delete_time < date_sub(now(), interval :delay second)";
+        let p = params! {
+            "delay" => delay,
         };
-        if id == 0 {
-            if mission.use_upstream_id || mission.to.check_master(&from.meta) {
-                mission.sys_context.insert(CONTEXT_TARGET_INSTANCE_ID.to_string(), format!("{:x}", from.id));
-                id = from.id
+        let rtn: usize = MySql::idu_idempotent(sql, p).await?;
+        // after the rows, so no row refers to a missing blob
+        remove_blobs(blobs).await;
+        Ok(rtn)
+    }
+
+    /// one multi-row `INSERT` for each `BATCH_INSERT_SIZE` rows, falls back to insert one by one
+    /// to tell which are duplicated.
+    async fn insert_batch(&self, instances: &[Instance]) -> DbResult<Vec<usize>> {
+        let mut rtn = Vec::with_capacity(instances.len());
+        for chunk in instances.chunks(*BATCH_INSERT_SIZE) {
+            let mut rows = Vec::with_capacity(chunk.len());
+            let mut blobs = vec![];
+            for one in chunk {
+                let mut raw = RawInstance::new(one)?;
+                match raw.put_blob().await {
+                    Ok(blob) => blobs.extend(blob),
+                    Err(e) => {
+                        remove_blobs(blobs).await;
+                        return Err(e.into());
+                    }
+                }
+                rows.push(raw.into());
+            }
+            let (values, p) = multi_row_insert(&INSTANCE_FIELDS, rows);
+            let sql = format!("INSERT INTO instances {}", values);
+            let saved = MySql::idu(sql, p).await;
+            if saved.is_err() {
+                remove_blobs(blobs).await;
+            }
+            match saved {
+                Ok(_) => rtn.extend(chunk.iter().map(|_| 1)),
+                Err(DbError::Duplicated(_)) => for one in chunk {
+                    rtn.push(match self.insert(one).await {
+                        Ok(num) => num,
+                        Err(DbError::Duplicated(_)) => 0,
+                        Err(e) => return Err(e)
+                    });
+                },
+                Err(e) => return Err(e)
             }
         }
-        let meta = mission.to.meta_string();
-        debug!("get last state for meta {}", &meta);
-        let qc = KeyCondition::new(id, &meta, &para_id, 0);
-        Self::get_last_state(&qc).await
+        Ok(rtn)
     }
 }
 
 #[async_trait]
 impl KeyRange for InstanceDaoImpl {
     /// ins_key > and between time range
-    async fn get_by_key_range(&self, f_para: &KeyCondition) -> Result<Vec<Instance>> {
-        let key_like = if f_para.meta.is_empty() {
-            ""
-        } else {
-            " and ins_key like :meta"
-        };
-        let key_gt = if f_para.key_gt.eq("") { "" } else {
-            " and ins_key > :key_gt"
-        };
-        let key_ge = if f_para.key_ge.eq("") { "" } else {
-            " and ins_key >= :key_ge"
-        };
-        let key_lt = if f_para.key_lt.eq("") { "" } else {
-            " and ins_key < :key_lt"
-        };
-        let key_le = if f_para.key_le.eq("") { "" } else {
-            " and ins_key <= :key_le"
-        };
-        let time_ge = match f_para.time_ge {
-            Some(_) => " and create_time >= :time_ge",
-            None => ""
-        };
-        let time_ge_v = match f_para.time_ge {
-            Some(ge) => ge,
-            None => 0
-        };
-        let time_lt = match f_para.time_lt {
-            Some(_) => " and create_time < :time_lt",
-            None => ""
-        };
-        let time_lt_v = match f_para.time_lt {
-            Some(lt) => lt,
-            None => 0
-        };
-        let limit = if f_para.limit < *QUERY_SIZE_LIMIT {
-            f_para.limit
-        } else { *QUERY_SIZE_LIMIT };
-        let sql = format!("SELECT ins_key, content, context, states, state_version, create_time, sys_context, from_key
-            FROM instances
-            where 1=1{}{}{}{}{}{}{}
-            order by ins_key
-            limit :limit", time_ge, time_lt, key_gt, key_ge, key_lt, key_le, key_like);
-
-        let p = params! {
-            "meta" => f_para.meta.to_string() + "%",
-            "key_gt" => f_para.key_gt.to_string(),
-            "key_ge" => f_para.key_ge.to_string(),
-            "key_lt" => f_para.key_lt.to_string(),
-            "key_le" => f_para.key_le.to_string(),
-            "time_ge" => Local.timestamp_millis(time_ge_v).naive_local(),
-            "time_lt" => Local.timestamp_millis(time_lt_v).naive_local(),
-            "limit" => limit,
-        };
-        dbg!(&sql);
+    async fn get_range(&self, condition: &RangeCondition) -> DbResult<Vec<Instance>> {
+        let (sql, p) = range_query(condition, Some(condition.get_limit() + 1))?;
         let result = MySql::fetch(sql, p, RawInstance::from).await?;
         let mut rtn: Vec<Instance> = vec![];
         for one in result {
-            rtn.push(one.to()?)
+            rtn.push(one.to().await?)
         }
         Ok(rtn)
     }
+
+    /// the whole range is read by one query through `MySql::fetch_stream`, `f_para.key.limit` is the buffer size.
+    fn scan(&self, mut f_para: RangeCondition) -> BoxStream<'_, DbResult<Instance>> {
+        if f_para.key.limit <= 0 {
+            f_para.key.limit = *QUERY_SIZE_LIMIT;
+        }
+        let (sql, p) = match range_query(&f_para, None) {
+            Ok(query) => query,
+            Err(e) => return stream::once(future::err(e)).boxed()
+        };
+        let selector = f_para.selector;
+        MySql::fetch_stream(sql, p, f_para.key.limit as usize, RawInstance::from)
+            .and_then(|raw| async move { Ok(raw.to().await?) })
+            .try_filter(move |one| future::ready(match &selector {
+                Some(selector) => selector.is_match(one),
+                None => true
+            }))
+            .boxed()
+    }
+}
+
+/// the rows after the cursor of `condition`, all of them if there is no `limit`
+fn range_query(condition: &RangeCondition, limit: Option<i32>) -> DbResult<(String, Params)> {
+    let cursor = condition.get_cursor()?;
+    let f_para = &condition.key;
+    let key_like = if f_para.meta.is_empty() {
+        ""
+    } else {
+        " and ins_key like :meta"
+    };
+    let key_gt = if f_para.key_gt.eq("") { "" } else {
+        " and ins_key > :key_gt"
+    };
+    let key_ge = if f_para.key_ge.eq("") { "" } else {
+        " and ins_key >= :key_ge"
+    };
+    let key_lt = if f_para.key_lt.eq("") { "" } else {
+        " and ins_key < :key_lt"
+    };
+    let key_le = if f_para.key_le.eq("") { "" } else {
+        " and ins_key <= :key_le"
+    };
+    let time_ge = match f_para.time_ge {
+        Some(_) => " and create_time >= :time_ge",
+        None => ""
+    };
+    let time_ge_v = match f_para.time_ge {
+        Some(ge) => ge,
+        None => 0
+    };
+    let time_lt = match f_para.time_lt {
+        Some(_) => " and create_time < :time_lt",
+        None => ""
+    };
+    let time_lt_v = match f_para.time_lt {
+        Some(lt) => lt,
+        None => 0
+    };
+    let after = match cursor {
+        Some(_) => " and (ins_key > :cursor_key or (ins_key = :cursor_key and state_version > :cursor_version))",
+        None => ""
+    };
+    let limit_sql = match limit {
+        Some(_) => " limit :limit",
+        None => ""
+    };
+    let (cursor_key, cursor_version) = cursor.unwrap_or_default();
+    let sql = format!("SELECT {}
+        FROM instances
+        where delete_time is null{}{}{}{}{}{}{}{}
+        order by ins_key, state_version{}", INSTANCE_COLUMNS, time_ge, time_lt, key_gt, key_ge, key_lt, key_le, key_like, after, limit_sql);
+
+    let mut p = params! {
+        "meta" => f_para.meta.to_string() + "%",
+        "key_gt" => f_para.key_gt.to_string(),
+        "key_ge" => f_para.key_ge.to_string(),
+        "key_lt" => f_para.key_lt.to_string(),
+        "key_le" => f_para.key_le.to_string(),
+        "time_ge" => Local.timestamp_millis(time_ge_v).naive_local(),
+        "time_lt" => Local.timestamp_millis(time_lt_v).naive_local(),
+        "cursor_key" => cursor_key,
+        "cursor_version" => cursor_version,
+    };
+    if let Some(limit) = limit {
+        p.push(("limit".to_string(), limit.into()));
+    }
+    Ok((sql, p.into()))
 }
 
 
 #[cfg(test)]
 mod test {
     use std::env;
+    use std::str::FromStr;
 
+    use chrono::Duration;
     use tokio::runtime::Runtime;
 
+    use crate::{CONN_STR, init_test_blob_store, StateInsert};
+    use crate::models::define::INSTANCE_CONTENT_MAX_LENGTH;
+
     use super::*;
 
     #[test]
@@ -245,7 +379,7 @@ mod test {
     fn get_last_state_test() {
         env::set_var("DATABASE_URL", "mysql://root@localhost/nature");
         let para = KeyCondition::new(0, "B:score/trainee/all-subject:1", "002", 0);
-        let result = Runtime::new().unwrap().block_on(InstanceDaoImpl::get_last_state(&para));
+        let result = Runtime::new().unwrap().block_on(D_I.get_last_state(&para));
         let _ = dbg!(result);
     }
 
@@ -266,7 +400,7 @@ mod test {
             time_lt: None,
             limit: 1,
         };
-        let result = Runtime::new().unwrap().block_on(InstanceDaoImpl::get_by_id(para));
+        let result = Runtime::new().unwrap().block_on(D_I.get_by_id(para));
         let _ = dbg!(result);
     }
 
@@ -277,7 +411,7 @@ mod test {
         env::set_var("DATABASE_URL", "mysql://root@localhost/nature");
         let mut ins = Instance::new("sale/order").unwrap();
         ins.id = 760228;
-        let _ = InstanceDaoImpl::insert(&ins).await;
+        let _ = D_I.insert(&ins).await;
 
         let ge_t = 1588508143000;
         let ge = Local.timestamp_millis(ge_t);
@@ -297,7 +431,7 @@ mod test {
         };
         let dao = InstanceDaoImpl {};
 
-        let result = dao.get_by_key_range(&para).await;
+        let result = dao.get_by_key_range(&RangeCondition::from(para)).await;
         dbg!(&result);
         assert!(result.is_ok());
         let vec = result.unwrap();
@@ -328,9 +462,133 @@ mod test {
         };
         let dao = InstanceDaoImpl {};
 
-        let result = dao.get_by_key_range(&para).await;
+        let result = dao.get_by_key_range(&RangeCondition::from(para)).await;
         assert!(result.is_ok());
         let vec = result.unwrap();
         dbg!(&vec);
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    #[ignore]
+    async fn scan_test() {
+        env::set_var("DATABASE_URL", CONN_STR);
+        let mut ins = Instance::new("mysql/scan").unwrap();
+        for id in 1..6 {
+            ins.id = id;
+            let _ = D_I.insert(&ins).await;
+        }
+        let mut para = RangeCondition::from(KeyCondition::new(0, "B:mysql/scan:1", "", 0));
+        para.key.limit = 2;
+        para.cursor = D_I.get_range(&para).await.unwrap()[0].get_key();
+        let all: Vec<Instance> = D_I.scan(para).try_collect().await.unwrap();
+        assert_eq!(all.len(), 4);
+        assert!(all.iter().all(|one| one.meta == "B:mysql/scan:1"));
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn delete_and_retain_test() {
+        env::set_var("DATABASE_URL", CONN_STR);
+        let mut ins = Instance::new("mysql/retention").unwrap();
+        ins.create_time = Local::now().timestamp_millis();
+        for id in 1..4 {
+            ins.id = id;
+            let _ = D_I.delete(&ins).await;
+        }
+        let _ = D_I.purge(-1).await;
+
+        ins.id = 1;
+        for version in 0..3 {
+            ins.state_version = version;
+            ins.from = Some(FromInstance::from_str(&format!("B:from:1|1||{}", version)).unwrap());
+            D_I.insert(&ins).await.unwrap();
+        }
+        let para = KeyCondition::from(&ins);
+        assert_eq!(D_I.delete(&ins).await.unwrap(), 3);
+        assert!(D_I.get_last_state(&para).await.unwrap().is_none());
+        // the keys are still held
+        assert!(matches!(D_I.insert(&ins).await, Err(DbError::Duplicated(_))));
+        assert_eq!(D_I.restore(&ins).await.unwrap(), 3);
+        assert_eq!(D_I.get_last_state(&para).await.unwrap().unwrap().state_version, 2);
+
+        let mut retention = Retention {
+            keep_versions: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(D_I.retain("B:mysql/retention:1", &retention).await.unwrap(), 1);
+        assert_eq!(D_I.retain("B:mysql/retention:1", &retention).await.unwrap(), 0);
+        // the version out of the retention is not restored
+        assert_eq!(D_I.delete(&ins).await.unwrap(), 2);
+        assert_eq!(D_I.restore(&ins).await.unwrap(), 2);
+        let history = D_I.get_history(&HistoryCondition::new(&ins.key_no_state())).await.unwrap();
+        assert_eq!(history.iter().map(|one| one.state_version).collect::<Vec<i32>>(), vec![1, 2]);
+
+        let store = init_test_blob_store();
+        let created = Local::now().checked_sub_signed(Duration::days(3)).unwrap().timestamp_millis();
+        let mut old = Instance::new("mysql/retention").unwrap();
+        old.id = 2;
+        old.content = "r".repeat(*INSTANCE_CONTENT_MAX_LENGTH + 1);
+        old.create_time = created;
+        D_I.insert(&old).await.unwrap();
+        let mut state = Instance::new("mysql/retention").unwrap();
+        state.id = 3;
+        state.create_time = created;
+        for version in 1..3 {
+            state.state_version = version;
+            state.from = Some(FromInstance::from_str(&format!("B:from:1|1||{}", version)).unwrap());
+            D_I.insert(&state).await.unwrap();
+        }
+        retention.keep_versions = None;
+        retention.keep_days = Some(2);
+        assert_eq!(D_I.retain("B:mysql/retention:1", &retention).await.unwrap(), 2);
+        assert!(D_I.get_by_id(KeyCondition::from(&old)).await.unwrap().is_none());
+        // the latest state version is kept however old it is
+        let last = D_I.get_last_state(&KeyCondition::from(&state)).await.unwrap().unwrap();
+        assert_eq!(last.state_version, 2);
+
+        assert_eq!(store.of(&old.content).len(), 1);
+        assert!(D_I.purge(-1).await.unwrap() >= 3);
+        assert_eq!(D_I.restore(&old).await.unwrap(), 0);
+        assert!(store.of(&old.content).is_empty());
+        // the keys are released
+        assert_eq!(D_I.insert(&old).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn insert_state_test() {
+        env::set_var("DATABASE_URL", CONN_STR);
+        let mut ins = Instance::new("mysql/cas").unwrap();
+        ins.id = 1;
+        let _ = D_I.delete(&ins).await;
+        let _ = D_I.purge(-1).await;
+        ins.state_version = 2;
+        assert_eq!(D_I.insert_state(&ins).await.unwrap(), StateInsert::Conflict(None));
+        ins.state_version = 1;
+        ins.from = Some(FromInstance::from_str("B:from:1|1||1").unwrap());
+        assert_eq!(D_I.insert_state(&ins).await.unwrap(), StateInsert::Inserted);
+        let mut other = ins.clone();
+        other.from = Some(FromInstance::from_str("B:from:1|1||2").unwrap());
+        other.state_version = 2;
+        assert_eq!(D_I.insert_state(&other).await.unwrap(), StateInsert::Inserted);
+        // the other writer saw version 0 too
+        let mut late = other.clone();
+        late.from = Some(FromInstance::from_str("B:from:1|1||3").unwrap());
+        late.state_version = 1;
+        let rtn = D_I.insert_state(&late).await.unwrap();
+        assert!(matches!(rtn, StateInsert::Conflict(Some(last)) if last.state_version == 2));
+
+        // the upstream is redelivered after the state moved on
+        let mut again = ins.clone();
+        again.state_version = 3;
+        let rtn = D_I.insert_state(&again).await.unwrap();
+        assert!(matches!(rtn, StateInsert::Repeated(existing) if existing.state_version == 1));
+
+        // the deleted versions are still held
+        D_I.delete(&ins).await.unwrap();
+        assert_eq!(D_I.insert_state(&ins).await.unwrap(), StateInsert::Deleted);
+        assert_eq!(D_I.insert_state(&late).await.unwrap(), StateInsert::Deleted);
+        D_I.purge(-1).await.unwrap();
+        assert_eq!(D_I.insert_state(&late).await.unwrap(), StateInsert::Inserted);
+    }
+}
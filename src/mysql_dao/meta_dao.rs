@@ -1,31 +1,19 @@
-use std::future::Future;
-
 use mysql_async::Value;
 
-use nature_common::{Meta, NatureError, Result};
+use nature_common::Meta;
 
-use crate::MySql;
+use crate::{DbError, DbResult, MetaDao, MySql};
 use crate::raw_models::RawMeta;
 
 lazy_static! {
     pub static ref D_M: MetaDaoImpl = MetaDaoImpl {};
 }
 
-pub type MetaGetter = fn(&str) -> dyn Future<Output=Result<Option<RawMeta>>>;
-
-#[async_trait]
-pub trait MetaDao: Sync + Send {
-    async fn get(&self, meta_str: &str) -> Result<Option<RawMeta>>;
-    async fn insert(&self, define: &RawMeta) -> Result<usize>;
-    async fn update_flag(&self, meta_str: &str, flag_f: i32) -> Result<usize>;
-    async fn delete(&self, m: &Meta) -> Result<usize>;
-}
-
 pub struct MetaDaoImpl;
 
 #[async_trait]
 impl MetaDao for MetaDaoImpl {
-    async fn get(&self, meta_str: &str) -> Result<Option<RawMeta>> {
+    async fn get(&self, meta_str: &str) -> DbResult<Option<RawMeta>> {
         let sql = r"SELECT meta_type, meta_key, description, version, states, fields, config, flag, create_time
             FROM meta
             WHERE meta_type = :meta_type and meta_key = :meta_key and version = :version and flag = 1";
@@ -45,11 +33,11 @@ impl MetaDao for MetaDaoImpl {
                 Ok(Some(meta))
             }
             0 => Ok(None),
-            _ => Err(NatureError::LogicalError("should not return more than one rows".to_string()))
+            _ => Err(DbError::Logical("should not return more than one rows".to_string()))
         }
     }
 
-    async fn insert(&self, define: &RawMeta) -> Result<usize> {
+    async fn insert(&self, define: &RawMeta) -> DbResult<usize> {
         let sql = r"INSERT INTO meta
             (meta_type, meta_key, description, version, states, fields, config, flag, create_time)
             VALUES(:meta_type, :meta_key, :description, :version, :states, :fields, :config, :flag, :create_time)";
@@ -59,7 +47,7 @@ impl MetaDao for MetaDaoImpl {
         Ok(rtn)
     }
 
-    async fn update_flag(&self, meta_str: &str, flag_f: i32) -> Result<usize> {
+    async fn update_flag(&self, meta_str: &str, flag_f: i32) -> DbResult<usize> {
         let sql = r"UPDATE meta
             SET flag=:flag
             WHERE meta_type = :meta_type and meta_key = :meta_key and version = :version";
@@ -71,12 +59,12 @@ impl MetaDao for MetaDaoImpl {
             "version" => m.version,
             "flag" => flag_f,
         };
-        let rtn = MySql::idu(sql, p).await?;
+        let rtn = MySql::idu_idempotent(sql, p).await?;
         debug!("meta flag updated: {}:{}:{}", m.get_meta_type().get_prefix(), m.get_key(), m.version);
         Ok(rtn)
     }
 
-    async fn delete(&self, m: &Meta) -> Result<usize> {
+    async fn delete(&self, m: &Meta) -> DbResult<usize> {
         let sql = r"DELETE FROM meta
             WHERE meta_type = :meta_type and meta_key = :meta_key and version = :version";
 
@@ -86,7 +74,7 @@ impl MetaDao for MetaDaoImpl {
             "version" => m.version,
         };
 
-        let rtn: usize = MySql::idu(sql, p).await?;
+        let rtn: usize = MySql::idu_idempotent(sql, p).await?;
         debug!("meta deleted: {}:{}:{}", m.get_meta_type().get_prefix(), m.get_key(), m.version);
         Ok(rtn)
     }
@@ -134,7 +122,7 @@ mod test {
         let rtn = runtime.block_on(D_M.insert(&define));
         let _ = match rtn {
             Err(err) => match err {
-                NatureError::DaoDuplicated(_) => (),
+                DbError::Duplicated(_) => (),
                 _ => panic!("match error"),
             }
             _ => panic!("match error")
@@ -0,0 +1,156 @@
+use crate::{check_version, DbResult, Migration, Migrator, MySql, pending, plan, Step};
+
+static MIGRATIONS: [Migration; 9] = [
+    Migration {
+        version: 1,
+        name: "init",
+        up: include_str!("../../migrations/mysql/001_init/up.sql"),
+        down: include_str!("../../migrations/mysql/001_init/down.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "instances_from_key",
+        up: include_str!("../../migrations/mysql/002_instances_from_key/up.sql"),
+        down: include_str!("../../migrations/mysql/002_instances_from_key/down.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "instances_content_ref",
+        up: include_str!("../../migrations/mysql/003_instances_content_ref/up.sql"),
+        down: include_str!("../../migrations/mysql/003_instances_content_ref/down.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "codec",
+        up: include_str!("../../migrations/mysql/004_codec/up.sql"),
+        down: include_str!("../../migrations/mysql/004_codec/down.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "instances_delete_time",
+        up: include_str!("../../migrations/mysql/005_instances_delete_time/up.sql"),
+        down: include_str!("../../migrations/mysql/005_instances_delete_time/down.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "task_lease",
+        up: include_str!("../../migrations/mysql/006_task_lease/up.sql"),
+        down: include_str!("../../migrations/mysql/006_task_lease/down.sql"),
+    },
+    Migration {
+        version: 7,
+        name: "task_priority",
+        up: include_str!("../../migrations/mysql/007_task_priority/up.sql"),
+        down: include_str!("../../migrations/mysql/007_task_priority/down.sql"),
+    },
+    Migration {
+        version: 8,
+        name: "instances_delete_by",
+        up: include_str!("../../migrations/mysql/008_instances_delete_by/up.sql"),
+        down: include_str!("../../migrations/mysql/008_instances_delete_by/down.sql"),
+    },
+    Migration {
+        version: 9,
+        name: "task_error_priority",
+        up: include_str!("../../migrations/mysql/009_task_error_priority/up.sql"),
+        down: include_str!("../../migrations/mysql/009_task_error_priority/down.sql"),
+    },
+];
+
+pub struct MigratorImpl;
+
+impl MySql {
+    /// upgrades the schema to the newest version
+    pub async fn migrate() -> DbResult<i32> {
+        MigratorImpl.migrate().await
+    }
+}
+
+impl MigratorImpl {
+    async fn init_version_table(&self) -> DbResult<()> {
+        let sql = r"CREATE TABLE IF NOT EXISTS `schema_version` (
+            `version` INT NOT NULL,
+            `name` VARCHAR(255) NOT NULL,
+            `applied_time` DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            PRIMARY KEY (`version`)
+            ) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4";
+        MySql::idu(sql, ()).await?;
+        let sql = r"CREATE TABLE IF NOT EXISTS `schema_step` (
+            `version` INT NOT NULL,
+            `script` VARCHAR(8) NOT NULL COMMENT 'up or down',
+            `step` INT NOT NULL COMMENT 'index of the statement in the script',
+            PRIMARY KEY (`version`, `script`, `step`)
+            ) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4";
+        MySql::idu(sql, ()).await?;
+        Ok(())
+    }
+
+    /// MySQL commits each DDL at once, so every statement done is recorded in `schema_step`,
+    /// and skipped when the script is run again after a failure.
+    async fn run(&self, version: i32, script: &'static str, sql: &str) -> DbResult<()> {
+        let query = r"SELECT step FROM schema_step WHERE version = :version and script = :script";
+        let done = MySql::fetch(query, params! {"version" => version, "script" => script}, mysql_async::from_row::<i32>).await?;
+        for (step, one) in pending(sql, &done) {
+            MySql::idu(one, ()).await?;
+            let record = r"INSERT INTO schema_step (version, script, step) VALUES(:version, :script, :step)";
+            MySql::idu(record, params! {"version" => version, "script" => script, "step" => step}).await?;
+        }
+        Ok(())
+    }
+
+    async fn clear_steps(&self, version: i32) -> DbResult<()> {
+        let sql = r"DELETE FROM schema_step WHERE version = :version";
+        MySql::idu(sql, params! {"version" => version}).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Migrator for MigratorImpl {
+    fn migrations(&self) -> &'static [Migration] {
+        &MIGRATIONS
+    }
+
+    async fn current_version(&self) -> DbResult<i32> {
+        self.init_version_table().await?;
+        let sql = r"SELECT IFNULL(MAX(version), 0) FROM schema_version";
+        let rtn = MySql::fetch(sql, (), mysql_async::from_row::<i32>).await?;
+        Ok(rtn.into_iter().next().unwrap_or(0))
+    }
+
+    async fn migrate_to(&self, version: i32) -> DbResult<i32> {
+        check_version(&MIGRATIONS, version)?;
+        let current = self.current_version().await?;
+        for step in plan(&MIGRATIONS, current, version) {
+            match step {
+                Step::Up(m) => {
+                    self.run(m.version, "up", m.up).await?;
+                    let sql = r"INSERT INTO schema_version (version, name) VALUES(:version, :name)";
+                    MySql::idu(sql, params! {"version" => m.version, "name" => m.name}).await?;
+                    self.clear_steps(m.version).await?;
+                    info!("schema upgraded to version {}: {}", m.version, m.name);
+                }
+                Step::Down(m) => {
+                    self.run(m.version, "down", m.down).await?;
+                    let sql = r"DELETE FROM schema_version WHERE version = :version";
+                    MySql::idu(sql, params! {"version" => m.version}).await?;
+                    self.clear_steps(m.version).await?;
+                    info!("schema downgraded from version {}: {}", m.version, m.name);
+                }
+            }
+        }
+        Ok(version)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    #[ignore]
+    async fn migrate_test() {
+        let newest = MySql::migrate().await.unwrap();
+        assert_eq!(MigratorImpl.current_version().await.unwrap(), newest);
+    }
+}
@@ -4,28 +4,15 @@ use mysql_async::Value;
 
 use nature_common::Executor;
 
-use crate::{MetaCache, MetaDao, Relation, RelationSettings};
+use crate::{DbResult, MetaCache, MetaDao, Relation, RelationDao, Relations, RelationSettings};
 use crate::raw_models::RawRelation;
 
 use super::*;
 
-pub type Relations = Result<Vec<Relation>>;
-
 lazy_static! {
     pub static ref D_R: RelationDaoImpl = RelationDaoImpl {};
 }
 
-#[async_trait]
-pub trait RelationDao: Sync + Send {
-    async fn get_relations<MC, M>(&self, from: &str, meta_cache_getter: &MC, meta_getter: &M) -> Relations
-        where MC: MetaCache, M: MetaDao;
-    async fn insert(&self, one: RawRelation) -> Result<usize>;
-    async fn delete(&self, one: RawRelation) -> Result<usize>;
-    async fn update_flag(&self, from: &str, to: &str, flag_f: i32) -> Result<usize>;
-    async fn insert_by_biz(&self, from: &str, to: &str, url: &str, protocol: &str) -> Result<RawRelation>;
-    async fn delete_by_biz(&self, from: &str, to: &str) -> Result<usize>;
-}
-
 pub struct RelationDaoImpl;
 
 #[async_trait]
@@ -57,7 +44,7 @@ impl RelationDao for RelationDaoImpl {
             ))
         }
     }
-    async fn insert(&self, one: RawRelation) -> Result<usize> {
+    async fn insert(&self, one: RawRelation) -> DbResult<usize> {
         let sql = r"INSERT INTO nature.relation
             (from_meta, to_meta, settings, flag)
             VALUES(:from_meta, :to_meta, :settings, :flag)";
@@ -67,7 +54,7 @@ impl RelationDao for RelationDaoImpl {
         debug!("Saved relation : {} -> {}", one.from_meta, one.to_meta);
         Ok(rtn)
     }
-    async fn delete(&self, one: RawRelation) -> Result<usize> {
+    async fn delete(&self, one: RawRelation) -> DbResult<usize> {
         let sql = r"DELETE FROM nature.relation
             WHERE from_meta=:from_meta AND to_meta=:to_meta";
 
@@ -76,13 +63,13 @@ impl RelationDao for RelationDaoImpl {
             "to_meta" => one.to_meta.to_string(),
         };
 
-        let rtn: usize = MySql::idu(sql, p).await?;
+        let rtn: usize = MySql::idu_idempotent(sql, p).await?;
         debug!("relation deleted : {} -> {}", one.from_meta, one.to_meta);
         Ok(rtn)
     }
 
     /// `from` and `to`'s form are full_key:version
-    async fn update_flag(&self, from: &str, to: &str, flag_f: i32) -> Result<usize> {
+    async fn update_flag(&self, from: &str, to: &str, flag_f: i32) -> DbResult<usize> {
         let sql = r"UPDATE nature.relation
             SET settings='', flag=:flag
             WHERE from_meta=:from_meta AND to_meta=:to_meta";
@@ -93,13 +80,13 @@ impl RelationDao for RelationDaoImpl {
             "flag" => flag_f,
         };
 
-        let rtn = MySql::idu(sql, p).await?;
+        let rtn = MySql::idu_idempotent(sql, p).await?;
         debug!("relation flag updated: : {} -> {}", from, to);
         Ok(rtn)
     }
 
     /// `version` will be set to 0
-    async fn insert_by_biz(&self, from: &str, to: &str, url: &str, protocol: &str) -> Result<RawRelation> {
+    async fn insert_by_biz(&self, from: &str, to: &str, url: &str, protocol: &str) -> DbResult<RawRelation> {
         let one = RawRelation::new(
             from,
             to,
@@ -117,13 +104,15 @@ impl RelationDao for RelationDaoImpl {
                 delay: 0,
                 delay_on_para: (0, 0),
                 id_bridge: false,
+                priority: 0,
+                retry: None,
             },
         )?;
         let _ = D_R.insert(one.clone()).await;
         Ok(one)
     }
 
-    async fn delete_by_biz(&self, from: &str, to: &str) -> Result<usize> {
+    async fn delete_by_biz(&self, from: &str, to: &str) -> DbResult<usize> {
         let row = RawRelation {
             from_meta: from.to_string(),
             to_meta: to.to_string(),
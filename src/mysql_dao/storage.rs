@@ -0,0 +1,36 @@
+use crate::{D_I, D_M, D_R, D_T, DbResult, InstanceDaoImpl, MetaDaoImpl, MySql, MySqlTx, RelationDaoImpl, Storage, TaskDaoImpl};
+
+lazy_static! {
+    pub static ref D_S: StorageImpl = StorageImpl {};
+}
+
+pub struct StorageImpl;
+
+#[async_trait]
+impl Storage for StorageImpl {
+    type Instance = InstanceDaoImpl;
+    type Task = TaskDaoImpl;
+    type Meta = MetaDaoImpl;
+    type Relation = RelationDaoImpl;
+    type Tx = MySqlTx;
+
+    fn instance(&self) -> &Self::Instance {
+        &D_I
+    }
+
+    fn task(&self) -> &Self::Task {
+        &D_T
+    }
+
+    fn meta(&self) -> &Self::Meta {
+        &D_M
+    }
+
+    fn relation(&self) -> &Self::Relation {
+        &D_R
+    }
+
+    async fn begin(&self) -> DbResult<Self::Tx> {
+        MySql::begin().await
+    }
+}
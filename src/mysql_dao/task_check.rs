@@ -1,7 +1,8 @@
-use chrono::{NaiveDateTime, Local};
+use chrono::Local;
 
 use nature_common::Result;
 
+pub use crate::Condition;
 use crate::MySql;
 
 pub struct TaskChecker;
@@ -42,21 +43,13 @@ impl TaskChecker {
             "task_lt" => cfg.key_lt.to_string(),
             "time_ge" => time_ge_v,
             "time_lt" => time_lt_v,
-            "state" => cfg.state,
+            "state" => i8::from(cfg.state),
         };
         let vec = MySql::fetch(sql, p, mysql_async::from_row).await?;
         Ok(vec[0])
     }
 }
 
-pub struct Condition {
-    pub key_gt: String,
-    pub key_lt: String,
-    pub time_ge: Option<NaiveDateTime>,
-    pub time_lt: Option<NaiveDateTime>,
-    pub state: i8,
-}
-
 #[cfg(test)]
 mod test {
     use std::env;
@@ -65,7 +58,7 @@ mod test {
 
     use nature_common::setup_logger;
 
-    use crate::CONN_STR;
+    use crate::{CONN_STR, TaskState};
 
     use super::*;
 
@@ -79,7 +72,7 @@ mod test {
             key_lt: "".to_string(),
             time_ge: Some(Local::now().naive_local()),
             time_lt: Some(Local::now().naive_local()),
-            state: 1,
+            state: TaskState::Finished,
         };
         let num = TaskChecker::check(&condition).await.unwrap();
         assert_eq!(0, num)
@@ -96,7 +89,7 @@ mod test {
             key_lt: "B:sale/item/count:2|0|".to_string(),
             time_ge: Some(Local.ymd(2020, 8, 7).and_hms(0, 0, 0).naive_local()),
             time_lt: Some(Local::now().naive_local()),
-            state: 1,
+            state: TaskState::Finished,
         };
         let num = TaskChecker::check(&condition).await.unwrap();
         assert_eq!(6, num)
@@ -1,45 +1,34 @@
+use std::convert::TryFrom;
+
 use chrono::{Duration, Local};
 use mysql_async::Value;
 
 use nature_common::{NatureError, Result};
 
-use crate::MySql;
-use crate::raw_models::{RawTask, RawTaskError};
+use crate::{BATCH_INSERT_SIZE, check_update_states, DbError, DbResult, lease_expire, MySql, OVERDUE_WHERE, overdue_params, state_codes, TaskDao, TaskErrorCondition, TaskErrorDao, TaskQueue, TaskState};
+use crate::raw_models::{multi_row_insert, RawTask, RawTaskError, TASK_COLUMNS, TASK_ERROR_COLUMNS, TASK_FIELDS};
 
 lazy_static! {
     pub static ref D_T: TaskDaoImpl = TaskDaoImpl {};
 }
 
-#[async_trait]
-pub trait TaskDao {
-    async fn insert(&self, raw: &RawTask) -> Result<usize>;
-    async fn delete(&self, _record_id: &str) -> Result<usize>;
-    async fn delete_finished(&self, _delay: i64) -> Result<usize>;
-    async fn raw_to_error(&self, err: &NatureError, raw: &RawTask) -> Result<usize>;
-    async fn get_overdue(&self, delay: i64, _limit: i64) -> Result<Vec<RawTask>>;
-    async fn update_execute_time(&self, _record_id: &str, delay: i64) -> Result<usize>;
-    async fn finish_task(&self, _record_id: &str) -> Result<usize>;
-    async fn increase_times_and_delay(&self, _record_id: &str, delay: i32) -> Result<usize>;
-    async fn get(&self, _record_id: &str) -> Result<Option<RawTask>>;
-}
-
 pub struct TaskDaoImpl;
 
 #[async_trait]
 impl TaskDao for TaskDaoImpl {
-    async fn insert(&self, raw: &RawTask) -> Result<usize> {
+    async fn insert(&self, raw: &RawTask) -> DbResult<usize> {
         let sql = r"INSERT INTO task
-            (task_id, task_key, task_type, task_for, task_state, `data`, create_time, execute_time, retried_times)
-            VALUES(:task_id, :task_key, :task_type, :task_for, :task_state, :data, :create_time, :execute_time, :retried_times)";
+            (task_id, task_key, task_type, task_for, task_state, `data`, create_time, execute_time, retried_times, data_codec, priority)
+            VALUES(:task_id, :task_key, :task_type, :task_for, :task_state, :data, :create_time, :execute_time, :retried_times, :data_codec, :priority)";
 
-        let p: Vec<(String, Value)> = raw.clone().into();
+        let p = Vec::<(String, Value)>::try_from(raw.clone())?;
         let num: usize = match MySql::idu(sql, p).await {
             Ok(n) => {
                 debug!("---- saved task KEY: {} FOR: {} TYPE: {}", &raw.task_key, &raw.task_for, raw.task_type);
                 n
             }
             Err(e) => match e {
-                NatureError::DaoDuplicated(_) => {
+                DbError::Duplicated(_) => {
                     warn!("==== task repeated. KEY: {} FOR: {} TYPE: {}", &raw.task_key, &raw.task_for, raw.task_type);
                     0
                 }
@@ -53,7 +42,7 @@ impl TaskDao for TaskDaoImpl {
     }
 
     #[allow(dead_code)]
-    async fn delete(&self, _record_id: &str) -> Result<usize> {
+    async fn delete(&self, _record_id: &str) -> DbResult<usize> {
         let sql = r"DELETE FROM nature.task
             WHERE task_id=:task_id";
 
@@ -61,27 +50,42 @@ impl TaskDao for TaskDaoImpl {
             "task_id" => _record_id,
         };
 
-        let rtn: usize = MySql::idu(sql, p).await?;
+        let rtn: usize = MySql::idu_idempotent(sql, p).await?;
         Ok(rtn)
     }
 
     /// delete finished task after `delay` seconds
-    async fn delete_finished(&self, _delay: i64) -> Result<usize> {
+    async fn delete_finished(&self, _delay: i64) -> DbResult<usize> {
         let sql = r"DELETE FROM task
-            WHERE execute_time < date_sub(now(), interval :delay second) AND task_state = 1";
+            WHERE execute_time < date_sub(now(), interval :delay second) AND task_state = :finished";
 
         let p = params! {
             "delay" => _delay,
+            "finished" => i8::from(TaskState::Finished),
         };
 
-        let rtn: usize = MySql::idu(sql, p).await?;
+        let rtn: usize = MySql::idu_idempotent(sql, p).await?;
         Ok(rtn)
     }
 
-    async fn raw_to_error(&self, err: &NatureError, raw: &RawTask) -> Result<usize> {
+    async fn raw_to_error(&self, err: &NatureError, raw: &RawTask) -> DbResult<usize> {
+        // `Failed` too, so the one left by a failed delete is moved again
+        let sql = format!("UPDATE nature.task
+            SET task_state=:failed
+            WHERE task_id=:task_id and (task_state in ({}) or task_state=:failed)", state_codes(TaskState::Failed.sources()));
+        let p = params! {
+            "failed" => i8::from(TaskState::Failed),
+            "task_id" => raw.task_id.as_str(),
+        };
+        // the one not found, e.g. failed before being saved, is moved too
+        if MySql::idu_idempotent(sql, p).await? == 0 && self.get(&raw.task_id).await?.is_some() {
+            warn!("==== task can't be failed. KEY: {} FOR: {} TYPE: {}", &raw.task_key, &raw.task_for, raw.task_type);
+            return Ok(0);
+        }
+
         let sql = r"INSERT INTO task_error
-            (task_id, task_key, task_type, task_for, `data`, create_time, msg)
-            VALUES(:task_id, :task_key, :task_type, :task_for, :data, :create_time, :msg)";
+            (task_id, task_key, task_type, task_for, `data`, create_time, msg, priority)
+            VALUES(:task_id, :task_key, :task_type, :task_for, :data, :create_time, :msg, :priority)";
 
         let rd = RawTaskError::from_raw(err, raw);
         let p: Vec<(String, Value)> = rd.into();
@@ -90,7 +94,7 @@ impl TaskDao for TaskDaoImpl {
                 self.delete(&raw.task_id).await?;
                 num
             }
-            Err(NatureError::DaoDuplicated(_)) => {
+            Err(DbError::Duplicated(_)) => {
                 self.delete(&raw.task_id).await?;
                 0
             }
@@ -99,44 +103,48 @@ impl TaskDao for TaskDaoImpl {
         Ok(num)
     }
 
-    async fn get_overdue(&self, delay: i64, _limit: i64) -> Result<Vec<RawTask>> {
-        let sql = r"SELECT task_id, task_key, task_type, task_for, task_state, `data`, create_time, execute_time, retried_times
+    async fn get_overdue_in(&self, queue: &TaskQueue, delay: i64, _limit: i64) -> DbResult<Vec<RawTask>> {
+        let (queue_where, queue_p) = queue.to_where();
+        let sql = format!("SELECT {}
             FROM task
-            WHERE execute_time < :execute_time and task_state = 0
-            LIMIT :limit";
+            WHERE {}{}
+            ORDER BY priority desc, execute_time
+            LIMIT :limit", TASK_COLUMNS, OVERDUE_WHERE, queue_where);
 
-        let _execute_time = Local::now().checked_add_signed(Duration::seconds(delay)).unwrap().naive_local();
-        let p = params! {
-            "execute_time" => _execute_time,
+        let mut p = overdue_params(delay);
+        p.extend(params! {
             "limit" => _limit,
-        };
+        });
+        p.extend(queue_p);
 
-        MySql::fetch(sql, p, RawTask::from).await
+        let rtn = MySql::fetch(sql, p, RawTask::try_from).await?;
+        rtn.into_iter().map(|one| Ok(one?.decoded()?)).collect()
     }
 
-    async fn update_execute_time(&self, _record_id: &str, delay: i64) -> Result<usize> {
-        let sql = r"UPDATE nature.task
+    async fn update_execute_time(&self, _record_id: &str, delay: i64) -> DbResult<usize> {
+        let sql = format!("UPDATE nature.task
             SET execute_time=:execute_time
-            WHERE task_id=:task_id";
+            WHERE task_id=:task_id and task_state in ({})", state_codes(TaskState::ACTIVE));
 
         let _time = Local::now().checked_add_signed(Duration::seconds(delay)).unwrap().naive_local();
         let p = params! {
             "execute_time" => _time,
             "task_id" => _record_id,
         };
-        let rtn = MySql::idu(sql, p).await?;
+        let rtn = MySql::idu_idempotent(sql, p).await?;
         Ok(rtn)
     }
 
-    async fn finish_task(&self, _record_id: &str) -> Result<usize> {
-        let sql = r"UPDATE nature.task
-            SET task_state=1
-            WHERE task_id=:task_id and task_state=0";
+    async fn finish_task(&self, _record_id: &str) -> DbResult<usize> {
+        let sql = format!("UPDATE nature.task
+            SET task_state=:finished
+            WHERE task_id=:task_id and task_state in ({})", state_codes(TaskState::Finished.sources()));
 
         let p = params! {
+            "finished" => i8::from(TaskState::Finished),
             "task_id" => _record_id,
         };
-        let rtn = match MySql::idu(sql, p).await {
+        let rtn = match MySql::idu_idempotent(sql, p).await {
             Ok(n) => n,
             Err(e) => {
                 warn!("**** save task error : {}", _record_id);
@@ -147,43 +155,204 @@ impl TaskDao for TaskDaoImpl {
     }
 
     /// increase one times and delay `delay` seconds
-    async fn increase_times_and_delay(&self, _record_id: &str, delay: i32) -> Result<usize> {
-        let sql = r"UPDATE nature.task
-            SET execute_time=:execute_time, retried_times = retried_times+1
-            WHERE task_id=:task_id";
+    async fn increase_times_and_delay(&self, _record_id: &str, delay: i32) -> DbResult<usize> {
+        let sql = format!("UPDATE nature.task
+            SET execute_time=:execute_time, retried_times = retried_times+1, task_state=:pending, lease_owner=NULL, lease_expire=NULL
+            WHERE task_id=:task_id and task_state in ({})", state_codes(TaskState::ACTIVE));
 
         let _time = Local::now().checked_add_signed(Duration::seconds(delay as i64)).unwrap().naive_local();
         let p = params! {
             "execute_time" => _time,
+            "pending" => i8::from(TaskState::Pending),
             "task_id" => _record_id,
         };
         let rtn = MySql::idu(sql, p).await?;
         Ok(rtn)
     }
 
-    async fn get(&self, _record_id: &str) -> Result<Option<RawTask>> {
-        let sql = r"SELECT task_id, task_key, task_type, task_for, task_state, `data`, create_time, execute_time, retried_times
+    async fn get(&self, _record_id: &str) -> DbResult<Option<RawTask>> {
+        let sql = format!("SELECT {}
             FROM task
-            WHERE task_id=:task_id";
+            WHERE task_id=:task_id", TASK_COLUMNS);
 
         let p = params! {
             "task_id" => _record_id,
         };
 
-        let rtn = MySql::fetch(sql, p, RawTask::from).await?;
+        let mut rtn = MySql::fetch(sql, p, RawTask::try_from).await?;
         match rtn.len() {
             0 => Ok(None),
-            1 => Ok(Some(rtn[0].clone())),
-            _ => Err(NatureError::SystemError("should less than 2 record return".to_string())),
+            1 => Ok(Some(rtn.remove(0)?.decoded()?)),
+            _ => Err(DbError::Logical("should less than 2 record return".to_string())),
         }
     }
+
+    async fn update_state(&self, task_id: &str, from: TaskState, to: TaskState) -> DbResult<usize> {
+        from.check_change_to(to)?;
+        let sql = r"UPDATE nature.task
+            SET task_state=:to
+            WHERE task_id=:task_id and task_state=:from";
+
+        let p = params! {
+            "to" => i8::from(to),
+            "task_id" => task_id,
+            "from" => i8::from(from),
+        };
+        let rtn = MySql::idu_idempotent(sql, p).await?;
+        Ok(rtn)
+    }
+
+    async fn update_states(&self, queue: &TaskQueue, from: &[TaskState], to: TaskState) -> DbResult<usize> {
+        check_update_states(queue, from, to)?;
+        let (queue_where, queue_p) = queue.to_where();
+        let sql = format!("UPDATE nature.task
+            SET task_state=:to
+            WHERE task_state in ({}){}", state_codes(from), queue_where);
+
+        let mut p = params! {
+            "to" => i8::from(to),
+        };
+        p.extend(queue_p);
+        let rtn = MySql::idu_idempotent(sql, p).await?;
+        debug!("---- {} tasks changed to {:?}", rtn, to);
+        Ok(rtn)
+    }
+
+    async fn claim(&self, worker: &str, queue: &TaskQueue, delay: i64, lease: i64, limit: i64) -> DbResult<Vec<RawTask>> {
+        let (queue_where, queue_p) = queue.to_where();
+        let sql = format!("UPDATE task
+            SET task_state=:running, lease_owner=:worker, lease_expire=:lease_expire
+            WHERE {}{}
+            ORDER BY priority desc, execute_time
+            LIMIT :limit", OVERDUE_WHERE, queue_where);
+
+        let _expire = lease_expire(worker, lease)?;
+        let mut p = overdue_params(delay);
+        p.extend(params! {
+            "worker" => worker,
+            "lease_expire" => _expire,
+            "limit" => limit,
+        });
+        p.extend(queue_p);
+        let num = MySql::idu(sql, p).await?;
+        if num == 0 {
+            return Ok(vec![]);
+        }
+
+        // `lease_expire` tells this claim apart from the former ones of the same worker
+        let sql = format!("SELECT {}
+            FROM task
+            WHERE lease_owner=:worker and lease_expire=:lease_expire and task_state = :running", TASK_COLUMNS);
+        let p = params! {
+            "worker" => worker,
+            "lease_expire" => _expire,
+            "running" => i8::from(TaskState::Running),
+        };
+        let rtn = MySql::fetch(sql, p, RawTask::try_from).await?;
+        debug!("---- {} tasks claimed by {}", rtn.len(), worker);
+        rtn.into_iter().map(|one| Ok(one?.decoded()?)).collect()
+    }
+
+    async fn renew_lease(&self, task_id: &str, worker: &str, lease: i64) -> DbResult<usize> {
+        let sql = r"UPDATE nature.task
+            SET lease_expire=:lease_expire
+            WHERE task_id=:task_id and lease_owner=:worker and task_state=:running";
+
+        let p = params! {
+            "lease_expire" => lease_expire(worker, lease)?,
+            "task_id" => task_id,
+            "worker" => worker,
+            "running" => i8::from(TaskState::Running),
+        };
+        let rtn = MySql::idu_idempotent(sql, p).await?;
+        Ok(rtn)
+    }
+
+    async fn release_lease(&self, task_id: &str, worker: &str) -> DbResult<usize> {
+        let sql = r"UPDATE nature.task
+            SET task_state=:pending, lease_owner=NULL, lease_expire=NULL
+            WHERE task_id=:task_id and lease_owner=:worker and task_state=:running";
+
+        let p = params! {
+            "pending" => i8::from(TaskState::Pending),
+            "task_id" => task_id,
+            "worker" => worker,
+            "running" => i8::from(TaskState::Running),
+        };
+        let rtn = MySql::idu_idempotent(sql, p).await?;
+        Ok(rtn)
+    }
+
+    /// one multi-row `INSERT` for each `BATCH_INSERT_SIZE` rows, falls back to insert one by one
+    /// to tell which are repeated.
+    async fn insert_batch(&self, raws: &[RawTask]) -> DbResult<Vec<usize>> {
+        let mut rtn = Vec::with_capacity(raws.len());
+        for chunk in raws.chunks(*BATCH_INSERT_SIZE) {
+            let rows = chunk.iter().map(|one| Vec::<(String, Value)>::try_from(one.clone())).collect::<Result<_>>()?;
+            let (values, p) = multi_row_insert(&TASK_FIELDS, rows);
+            let sql = format!("INSERT INTO task {}", values);
+            match MySql::idu(sql, p).await {
+                Ok(_) => rtn.extend(chunk.iter().map(|_| 1)),
+                Err(DbError::Duplicated(_)) => for one in chunk {
+                    rtn.push(self.insert(one).await?);
+                },
+                Err(e) => return Err(e)
+            }
+        }
+        Ok(rtn)
+    }
+}
+
+
+#[async_trait]
+impl TaskErrorDao for TaskDaoImpl {
+    async fn get_error(&self, task_id: &str) -> DbResult<Option<RawTaskError>> {
+        let sql = format!("SELECT {}
+            FROM task_error
+            WHERE task_id=:task_id", TASK_ERROR_COLUMNS);
+        let p = params! {
+            "task_id" => task_id,
+        };
+        let mut rtn = MySql::fetch(sql, p, RawTaskError::try_from).await?;
+        match rtn.len() {
+            0 => Ok(None),
+            1 => Ok(Some(rtn.remove(0)?)),
+            _ => Err(DbError::Logical("should less than 2 record return".to_string())),
+        }
+    }
+
+    async fn get_errors(&self, condition: &TaskErrorCondition) -> DbResult<Vec<RawTaskError>> {
+        let (where_clause, mut p) = condition.to_where();
+        let sql = format!("SELECT {}
+            FROM task_error
+            {}
+            ORDER BY task_key
+            LIMIT :limit", TASK_ERROR_COLUMNS, where_clause);
+        p.push(("limit".to_string(), condition.get_limit().into()));
+        MySql::fetch(sql, p, RawTaskError::try_from).await?.into_iter().collect()
+    }
+
+    async fn delete_error(&self, task_id: &str) -> DbResult<usize> {
+        let sql = r"DELETE FROM task_error
+            WHERE task_id=:task_id";
+        let p = params! {
+            "task_id" => task_id,
+        };
+        MySql::idu_idempotent(sql, p).await
+    }
+
+    async fn purge_errors(&self, condition: &TaskErrorCondition) -> DbResult<usize> {
+        let (where_clause, p) = condition.to_where();
+        let sql = format!("DELETE FROM task_error {}", where_clause);
+        MySql::idu_idempotent(sql, p).await
+    }
 }
 
 #[cfg(test)]
 mod test {
     use std::env;
 
-    use crate::CONN_STR;
+    use crate::{Backoff, CONN_STR, RetryPolicy, TaskRetry, TaskType};
 
     use super::*;
 
@@ -205,4 +374,30 @@ mod test {
         let get_task = D_T.get("lxb").await.unwrap();
         assert!(get_task.is_none());
     }
+
+    #[tokio::test]
+    #[ignore]
+    async fn retry_by_test() {
+        env::set_var("DATABASE_URL", CONN_STR);
+        let err = NatureError::EnvironmentError("executor is down".to_string());
+        let policy = RetryPolicy {
+            max_attempts: 1,
+            backoff: Backoff::Fixed,
+            delay: 10,
+            ..Default::default()
+        };
+        let task = RawTask::from_str("data", "B:mysql/retry:1|1||0", TaskType::Store, "B:to:1").unwrap();
+        D_T.delete(&task.task_id).await.unwrap();
+        D_T.delete_error(&task.task_id).await.unwrap();
+        assert_eq!(D_T.insert(&task).await.unwrap(), 1);
+        assert_eq!(D_T.retry_by(&task, &policy, &err).await.unwrap(), TaskRetry::Delayed(10));
+        let task = D_T.get(&task.task_id).await.unwrap().unwrap();
+        assert_eq!(task.retried_times, 1);
+        assert!(task.execute_time > Local::now().naive_local());
+        assert_eq!(D_T.retry_by(&task, &policy, &err).await.unwrap(), TaskRetry::Exhausted);
+        assert!(D_T.get(&task.task_id).await.unwrap().is_none());
+        let error = D_T.get_error(&task.task_id).await.unwrap().unwrap();
+        assert!(error.msg.contains("executor is down"));
+        D_T.delete_error(&task.task_id).await.unwrap();
+    }
 }
\ No newline at end of file
@@ -0,0 +1,146 @@
+use std::convert::TryFrom;
+
+use mysql_async::{Conn, Params, Transaction, TransactionOptions, Value};
+use mysql_async::prelude::*;
+
+use nature_common::Instance;
+
+use crate::{DbError, DbResult, MySql, state_codes, StorageTx, TaskState, TxBlobs};
+use crate::raw_models::{RawInstance, RawTask};
+
+use super::MysqlError;
+
+/// Holds a connection until `commit` or `rollback`, the blobs saved are removed if it is not committed.
+/// Dropping it without `commit` rolls back, which is done by the pool when the connection goes back.
+pub struct MySqlTx {
+    tx: Option<Transaction<Conn>>,
+    blobs: TxBlobs,
+}
+
+impl MySql {
+    pub async fn begin() -> DbResult<MySqlTx> {
+        let conn = MySql::get_conn().await?;
+        match conn.start_transaction(TransactionOptions::new()).await {
+            Ok(tx) => Ok(MySqlTx { tx: Some(tx), blobs: TxBlobs::default() }),
+            Err(e) => Err(MysqlError(e).into())
+        }
+    }
+}
+
+impl MySqlTx {
+    fn take(&mut self) -> DbResult<Transaction<Conn>> {
+        match self.tx.take() {
+            Some(tx) => Ok(tx),
+            None => Err(DbError::Logical("transaction is not available".to_string()))
+        }
+    }
+
+    async fn idu<P: Into<Params>>(&mut self, query: &str, params: P) -> DbResult<usize> {
+        let tx = self.take()?;
+        let rtn = match tx.prep_exec(query, params).await {
+            Ok(rtn) => rtn,
+            Err(e) => return Err(MysqlError(e).into())
+        };
+        let num = rtn.affected_rows() as usize;
+        match rtn.drop_result().await {
+            Ok(tx) => {
+                self.tx = Some(tx);
+                Ok(num)
+            }
+            Err(e) => Err(MysqlError(e).into())
+        }
+    }
+}
+
+#[async_trait]
+impl StorageTx for MySqlTx {
+    async fn insert_instance(&mut self, instance: &Instance) -> DbResult<usize> {
+        let sql = r"INSERT INTO instances
+            (ins_key, content, context, states, state_version, create_time, sys_context, from_key, content_ref, content_codec)
+            VALUES(:ins_key, :content,:context,:states,:state_version,:create_time,:sys_context,:from_key,:content_ref,:content_codec)";
+        let mut raw = RawInstance::new(instance)?;
+        self.blobs.push(raw.put_blob().await?);
+        let vec: Vec<(String, Value)> = raw.into();
+        let rtn = self.idu(sql, vec).await?;
+        debug!("Saved instance in transaction : {}", instance.get_key());
+        Ok(rtn)
+    }
+
+    async fn insert_task(&mut self, raw: &RawTask) -> DbResult<usize> {
+        // a duplicate-key error would break the transaction, so make it affect nothing instead.
+        let sql = r"INSERT INTO task
+            (task_id, task_key, task_type, task_for, task_state, `data`, create_time, execute_time, retried_times, data_codec, priority)
+            VALUES(:task_id, :task_key, :task_type, :task_for, :task_state, :data, :create_time, :execute_time, :retried_times, :data_codec, :priority)
+            ON DUPLICATE KEY UPDATE task_id = task_id";
+        let p = Vec::<(String, Value)>::try_from(raw.clone())?;
+        let num = self.idu(sql, p).await?;
+        if num == 0 {
+            warn!("==== task repeated. KEY: {} FOR: {} TYPE: {}", &raw.task_key, &raw.task_for, raw.task_type);
+        }
+        Ok(num)
+    }
+
+    async fn finish_task(&mut self, task_id: &str) -> DbResult<usize> {
+        let sql = format!("UPDATE task
+            SET task_state=:finished
+            WHERE task_id=:task_id and task_state in ({})", state_codes(TaskState::Finished.sources()));
+        let p = params! {
+            "finished" => i8::from(TaskState::Finished),
+            "task_id" => task_id,
+        };
+        self.idu(&sql, p).await
+    }
+
+    async fn commit(mut self) -> DbResult<()> {
+        match self.take()?.commit().await {
+            Ok(_) => {
+                self.blobs.commit();
+                Ok(())
+            }
+            Err(e) => Err(MysqlError(e).into())
+        }
+    }
+
+    async fn rollback(mut self) -> DbResult<()> {
+        let rtn = self.take()?.rollback().await;
+        self.blobs.remove().await;
+        match rtn {
+            Ok(_) => Ok(()),
+            Err(e) => Err(MysqlError(e).into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::env;
+
+    use nature_common::KeyCondition;
+
+    use crate::{CONN_STR, D_I, D_T, InstanceDao, TaskDao};
+
+    use super::*;
+
+    #[tokio::test]
+    #[ignore]
+    async fn drop_test() {
+        env::set_var("DATABASE_URL", CONN_STR);
+        let mut ins = Instance::new("mysql/tx/drop").unwrap();
+        ins.id = 1;
+        let task = RawTask {
+            task_id: "mysql_tx_drop".to_string(),
+            task_key: "mysql_tx_drop".to_string(),
+            ..Default::default()
+        };
+        let _ = D_T.delete(&task.task_id).await;
+        let _ = D_I.delete(&ins).await;
+        let _ = D_I.purge(-1).await;
+
+        let mut tx = MySql::begin().await.unwrap();
+        assert_eq!(tx.insert_instance(&ins).await.unwrap(), 1);
+        assert_eq!(tx.insert_task(&task).await.unwrap(), 1);
+        drop(tx);
+        assert!(D_T.get(&task.task_id).await.unwrap().is_none());
+        assert!(D_I.get_by_id(KeyCondition::from(&ins)).await.unwrap().is_none());
+    }
+}
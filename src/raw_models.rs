@@ -1,11 +1,15 @@
+pub use self::codec::*;
 pub use self::task::*;
 pub use self::task_error::*;
 pub use self::instance_raw::*;
 pub use self::relation_raw::*;
 pub use self::meta_raw::*;
+pub(crate) use self::batch::*;
 
+mod codec;
 mod meta_raw;
 mod instance_raw;
 mod task;
 mod relation_raw;
-mod task_error;
\ No newline at end of file
+mod task_error;
+mod batch;
\ No newline at end of file
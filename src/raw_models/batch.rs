@@ -0,0 +1,35 @@
+use mysql_async::Value;
+
+/// Builds "(`a`, `b`) VALUES(:a_0, :b_0),(:a_1, :b_1)" for multi-row insert,
+/// the parameters of each row are renamed with the row index as suffix.
+pub(crate) fn multi_row_insert(columns: &[&str], rows: Vec<Vec<(String, Value)>>) -> (String, Vec<(String, Value)>) {
+    let mut values: Vec<String> = vec![];
+    let mut params: Vec<(String, Value)> = vec![];
+    for (i, row) in rows.into_iter().enumerate() {
+        let names: Vec<String> = columns.iter().map(|c| format!(":{}_{}", c, i)).collect();
+        values.push(format!("({})", names.join(", ")));
+        for (k, v) in row {
+            params.push((format!("{}_{}", k, i), v));
+        }
+    }
+    let columns: Vec<String> = columns.iter().map(|c| format!("`{}`", c)).collect();
+    (format!("({}) VALUES{}", columns.join(", "), values.join(",")), params)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn multi_row_insert_test() {
+        let rows = vec![
+            params! {"a" => 1, "b" => "x"},
+            params! {"a" => 2, "b" => "y"},
+        ];
+        let (sql, params) = multi_row_insert(&["a", "b"], rows);
+        assert_eq!(sql, "(`a`, `b`) VALUES(:a_0, :b_0),(:a_1, :b_1)");
+        let names: Vec<&str> = params.iter().map(|p| p.0.as_str()).collect();
+        assert_eq!(names, vec!["a_0", "b_0", "a_1", "b_1"]);
+        assert_eq!(params[3].1, Value::from("y"));
+    }
+}
@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::sync::RwLock;
+
+use nature_common::{NatureError, Result};
+
+lazy_static! {
+    static ref CODECS : RwLock<HashMap<String, Codec>> = RwLock::new(HashMap::new());
+}
+
+/// How `instances.content` and `task.data` are compressed, it is saved along with the row,
+/// so the rows of different codecs can coexist. The compressed are saved as base64 text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Codec {
+    Plain = 0,
+    Gzip = 1,
+    Zstd = 2,
+}
+
+impl Default for Codec {
+    fn default() -> Self {
+        Codec::Plain
+    }
+}
+
+/// The `codec` item of `meta.config`, the other items belong to `MetaSetting`.
+#[derive(Serialize, Deserialize, Default)]
+struct CodecConfig {
+    #[serde(default)]
+    codec: Codec,
+}
+
+impl TryFrom<i8> for Codec {
+    type Error = NatureError;
+
+    fn try_from(value: i8) -> Result<Self> {
+        match value {
+            0 => Ok(Codec::Plain),
+            1 => Ok(Codec::Gzip),
+            2 => Ok(Codec::Zstd),
+            _ => Err(NatureError::VerifyError(format!("unknown codec: {}", value)))
+        }
+    }
+}
+
+impl From<Codec> for i8 {
+    fn from(codec: Codec) -> Self {
+        codec as i8
+    }
+}
+
+impl Codec {
+    /// the codec in `meta.config`, e.g. `{"codec":"gzip"}`, `Plain` if there is none.
+    pub fn from_config(config: &str) -> Result<Codec> {
+        if config.is_empty() {
+            return Ok(Codec::Plain);
+        }
+        let rtn: CodecConfig = serde_json::from_str(config).map_err(|e| Self::error("parse codec", e))?;
+        Ok(rtn.codec)
+    }
+
+    /// puts the codec into `meta.config`, the meta should be saved by `MetaDao` to take effect,
+    /// so every writer compresses the instances and tasks of the meta the same way.
+    pub fn to_config(&self, config: &str) -> Result<String> {
+        let mut value: serde_json::Value = match config.is_empty() {
+            true => serde_json::json!({}),
+            false => serde_json::from_str(config).map_err(|e| Self::error("parse codec", e))?
+        };
+        match value.as_object_mut() {
+            Some(obj) => {
+                obj.insert("codec".to_string(), serde_json::to_value(self).map_err(|e| Self::error("parse codec", e))?);
+            }
+            None => return Err(NatureError::VerifyError(format!("meta config is not an object: {}", config)))
+        }
+        Ok(value.to_string())
+    }
+
+    /// remembers the codec of the `meta` loaded from `meta.config`, a codec that is not enabled
+    /// by the cargo feature `gzip` or `zstd` makes the writing fail instead of falling back to `Plain`.
+    pub(crate) fn load(meta: &str, config: &str) -> Result<()> {
+        let codec = Self::from_config(config)?;
+        CODECS.write().unwrap().insert(meta.to_string(), codec);
+        Ok(())
+    }
+
+    /// the codec of the `meta` loaded
+    pub fn of(meta: &str) -> Codec {
+        CODECS.read().unwrap().get(meta).cloned().unwrap_or_default()
+    }
+
+    /// whether it is enabled by the cargo feature
+    pub(crate) fn check(&self) -> Result<()> {
+        let enabled = match self {
+            Codec::Plain => true,
+            Codec::Gzip => cfg!(feature = "gzip"),
+            Codec::Zstd => cfg!(feature = "zstd"),
+        };
+        match enabled {
+            true => Ok(()),
+            false => Err(NatureError::EnvironmentError(format!("codec {:?} is not enabled", self)))
+        }
+    }
+
+    pub fn encode(&self, content: &str) -> Result<String> {
+        self.check()?;
+        match self {
+            Codec::Plain => Ok(content.to_string()),
+            _ => {
+                let bytes = self.compress(content.as_bytes()).map_err(|e| Self::error("compress", e))?;
+                Ok(base64::encode(&bytes))
+            }
+        }
+    }
+
+    pub fn decode(&self, content: &str) -> Result<String> {
+        self.check()?;
+        match self {
+            Codec::Plain => Ok(content.to_string()),
+            _ => {
+                let bytes = base64::decode(content).map_err(|e| Self::error("decode base64", e))?;
+                let bytes = self.decompress(&bytes).map_err(|e| Self::error("decompress", e))?;
+                String::from_utf8(bytes).map_err(|e| Self::error("decompress", e))
+            }
+        }
+    }
+
+    fn error<E: ToString>(action: &str, e: E) -> NatureError {
+        NatureError::LogicalError(format!("{} error: {}", action, e.to_string()))
+    }
+
+    #[allow(unused_variables)]
+    fn compress(&self, bytes: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            #[cfg(feature = "gzip")]
+            Codec::Gzip => {
+                use std::io::Write;
+                let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+                encoder.write_all(bytes)?;
+                encoder.finish()
+            }
+            #[cfg(feature = "zstd")]
+            Codec::Zstd => zstd_rs::encode_all(bytes, 0),
+            _ => Ok(bytes.to_vec())
+        }
+    }
+
+    #[allow(unused_variables)]
+    fn decompress(&self, bytes: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            #[cfg(feature = "gzip")]
+            Codec::Gzip => {
+                use std::io::Read;
+                let mut rtn = vec![];
+                flate2::read::GzDecoder::new(bytes).read_to_end(&mut rtn)?;
+                Ok(rtn)
+            }
+            #[cfg(feature = "zstd")]
+            Codec::Zstd => zstd_rs::decode_all(bytes),
+            _ => Ok(bytes.to_vec())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn plain_test() {
+        assert_eq!(Codec::Plain.encode("hello").unwrap(), "hello");
+        assert_eq!(Codec::Plain.decode("hello").unwrap(), "hello");
+        assert_eq!(Codec::try_from(2).unwrap(), Codec::Zstd);
+        assert!(Codec::try_from(3).is_err());
+        assert_eq!(Codec::of("B:codec/none:1"), Codec::Plain);
+    }
+
+    #[test]
+    fn config_test() {
+        assert_eq!(Codec::from_config("").unwrap(), Codec::Plain);
+        assert_eq!(Codec::from_config(r#"{"is_state":true}"#).unwrap(), Codec::Plain);
+        assert_eq!(Codec::from_config(r#"{"is_state":true,"codec":"zstd"}"#).unwrap(), Codec::Zstd);
+        assert!(Codec::from_config(r#"{"codec":"rar"}"#).is_err());
+        let config = Codec::Gzip.to_config(r#"{"is_state":true}"#).unwrap();
+        assert_eq!(Codec::from_config(&config).unwrap(), Codec::Gzip);
+        assert!(config.contains(r#""is_state":true"#));
+        assert_eq!(Codec::Zstd.to_config("").unwrap(), r#"{"codec":"zstd"}"#);
+        Codec::load("B:codec/config:1", &config).unwrap();
+        assert_eq!(Codec::of("B:codec/config:1"), Codec::Gzip);
+    }
+
+    #[test]
+    #[cfg(not(feature = "gzip"))]
+    fn disabled_test() {
+        Codec::load("B:codec/gzip:1", r#"{"codec":"gzip"}"#).unwrap();
+        assert!(Codec::of("B:codec/gzip:1").encode("hello").is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "gzip")]
+    fn gzip_test() {
+        let json = r#"{"a":"aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"}"#;
+        let encoded = Codec::Gzip.encode(json).unwrap();
+        assert!(encoded.len() < json.len());
+        assert_eq!(Codec::Gzip.decode(&encoded).unwrap(), json);
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn zstd_test() {
+        let json = r#"{"a":"aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"}"#;
+        let encoded = Codec::Zstd.encode(json).unwrap();
+        assert!(encoded.len() < json.len());
+        assert_eq!(Codec::Zstd.decode(&encoded).unwrap(), json);
+    }
+}
@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::convert::TryFrom;
 use std::str::FromStr;
 
 use chrono::prelude::*;
@@ -9,8 +10,13 @@ use serde_json;
 
 use nature_common::*;
 
+use crate::{Codec, get_blob_store};
 use crate::models::define::*;
 
+/// columns in the order of `RawInstance` fields
+pub(crate) static INSTANCE_COLUMNS: &str = "ins_key, content, context, states, state_version, create_time, sys_context, from_key, content_ref, content_codec";
+pub(crate) static INSTANCE_FIELDS: [&str; 10] = ["ins_key", "content", "context", "states", "state_version", "create_time", "sys_context", "from_key", "content_ref", "content_codec"];
+
 pub struct RawInstance {
     ins_key: String,
     content: String,
@@ -20,10 +26,14 @@ pub struct RawInstance {
     create_time: NaiveDateTime,
     sys_context: Option<String>,
     from_key: String,
+    /// the content is saved in the `BlobStore` if it is too long, `content` is empty then.
+    content_ref: Option<String>,
+    /// see `Codec`
+    content_codec: i8,
 }
 
 impl RawInstance {
-    pub fn to(&self) -> Result<Instance> {
+    pub async fn to(&self) -> Result<Instance> {
         let from = if self.from_key.eq("") { None } else {
             Some(FromInstance::from_str(&self.from_key)?)
         };
@@ -49,11 +59,19 @@ impl RawInstance {
                 return Err(NatureError::VerifyError(msg));
             }
         };
+        let content = match &self.content_ref {
+            None => self.content.clone(),
+            Some(reference) => match get_blob_store() {
+                Some(store) => store.get(reference).await?,
+                None => return Err(NatureError::EnvironmentError(format!("no blob store to get the content of {}", self.ins_key)))
+            }
+        };
+        let content = Codec::try_from(self.content_codec)?.decode(&content)?;
         Ok(Instance {
             id: key.id,
             data: BizObject {
                 meta: key.meta.clone(),
-                content: self.content.clone(),
+                content,
                 context,
                 sys_context,
                 states,
@@ -65,15 +83,17 @@ impl RawInstance {
         })
     }
 
+    /// The content is compressed by the `Codec` of the meta, if it is still longer than
+    /// `INSTANCE_CONTENT_MAX_LENGTH` it should be moved to the `BlobStore` by `put_blob` before saved.
     pub fn new(instance: &Instance) -> Result<RawInstance> {
+        let codec = Codec::of(&instance.meta);
+        let content = codec.encode(&instance.content)?;
+        if Self::is_overflowed(&content) && get_blob_store().is_none() {
+            return Err(NatureError::SystemError("content's length can' be over : ".to_owned() + &INSTANCE_CONTENT_MAX_LENGTH.to_string()));
+        }
         Ok(RawInstance {
             ins_key: instance.key_no_state(),
-            content: {
-                if instance.content.len() > *INSTANCE_CONTENT_MAX_LENGTH.deref() {
-                    return Err(NatureError::SystemError("content's length can' be over : ".to_owned() + &INSTANCE_CONTENT_MAX_LENGTH.to_string()));
-                }
-                instance.content.clone()
-            },
+            content,
             context: Self::context_to_raw(&instance.context, "context")?,
             states: match instance.states.len() {
                 0 => None,
@@ -86,9 +106,34 @@ impl RawInstance {
                 None => "".to_string(),
                 Some(from) => from.to_string()
             },
+            content_ref: None,
+            content_codec: codec.into(),
         })
     }
 
+    fn is_overflowed(content: &str) -> bool {
+        content.len() > *INSTANCE_CONTENT_MAX_LENGTH.deref()
+    }
+
+    /// Moves the overflowed content to the `BlobStore` and returns the reference.
+    /// The blob should be removed by `remove_blobs` if the row is not saved.
+    pub async fn put_blob(&mut self) -> Result<Option<String>> {
+        if !Self::is_overflowed(&self.content) {
+            return Ok(None);
+        }
+        let store = match get_blob_store() {
+            Some(store) => store,
+            None => return Err(NatureError::SystemError("content's length can' be over : ".to_owned() + &INSTANCE_CONTENT_MAX_LENGTH.to_string()))
+        };
+        // unique for each write, so removing the blob of a failed write won't affect the saved one.
+        let key = format!("{}{}{}{}", self.ins_key, self.state_version, self.content, rand::random::<u64>());
+        let key = format!("{:x}", generate_id(&key)?);
+        let reference = store.put(&key, &self.content).await?;
+        self.content = "".to_string();
+        self.content_ref = Some(reference.clone());
+        Ok(Some(reference))
+    }
+
     fn context_to_raw(context: &HashMap<String, String>, which: &str) -> Result<Option<String>> {
         let ctx_len = context.len();
         if ctx_len > *INSTANCE_CONTEXT_MAX_LENGTH.deref() {
@@ -104,7 +149,7 @@ impl RawInstance {
 
 impl From<Row> for RawInstance {
     fn from(row: Row) -> Self {
-        let (ins_key, content, context, states, state_version, create_time, sys_context, from_key) = mysql_async::from_row(row);
+        let (ins_key, content, context, states, state_version, create_time, sys_context, from_key, content_ref, content_codec) = mysql_async::from_row(row);
         RawInstance {
             ins_key,
             content,
@@ -114,10 +159,32 @@ impl From<Row> for RawInstance {
             create_time,
             sys_context,
             from_key,
+            content_ref,
+            content_codec,
         }
     }
 }
 
+#[cfg(feature = "sqlite")]
+impl TryFrom<&rusqlite::Row<'_>> for RawInstance {
+    type Error = rusqlite::Error;
+
+    fn try_from(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        Ok(RawInstance {
+            ins_key: row.get(0)?,
+            content: row.get(1)?,
+            context: row.get(2)?,
+            states: row.get(3)?,
+            state_version: row.get(4)?,
+            create_time: row.get(5)?,
+            sys_context: row.get(6)?,
+            from_key: row.get(7)?,
+            content_ref: row.get(8)?,
+            content_codec: row.get(9)?,
+        })
+    }
+}
+
 impl Into<Vec<(String, Value)>> for RawInstance {
     fn into(self) -> Vec<(String, Value)> {
         params! {
@@ -129,6 +196,60 @@ impl Into<Vec<(String, Value)>> for RawInstance {
             "create_time" => self.create_time,
             "sys_context" => self.sys_context,
             "from_key" => self.from_key,
+            "content_ref" => self.content_ref,
+            "content_codec" => self.content_codec,
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    #[cfg(feature = "gzip")]
+    use std::convert::TryInto;
+
+    #[cfg(feature = "gzip")]
+    use crate::RawMeta;
+    use crate::{init_test_blob_store, remove_blobs};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn overflow_test() {
+        let store = init_test_blob_store();
+        let mut ins = Instance::new("raw/overflow").unwrap();
+        ins.content = "a".repeat(*INSTANCE_CONTENT_MAX_LENGTH + 1);
+        let mut raw = RawInstance::new(&ins).unwrap();
+        let reference = raw.put_blob().await.unwrap();
+        assert!(raw.content.is_empty());
+        assert_eq!(raw.content_ref, reference);
+        assert_eq!(raw.to().await.unwrap().content, ins.content);
+        // another write of the same instance won't share the blob
+        let mut other = RawInstance::new(&ins).unwrap();
+        assert_ne!(other.put_blob().await.unwrap(), reference);
+        remove_blobs(other.content_ref.into_iter().collect()).await;
+        assert_eq!(store.of(&ins.content), reference.clone().into_iter().collect::<Vec<String>>());
+
+        remove_blobs(reference.into_iter().collect()).await;
+        assert!(raw.to().await.is_err());
+
+        ins.content = "short".to_string();
+        let mut raw = RawInstance::new(&ins).unwrap();
+        assert_eq!(raw.put_blob().await.unwrap(), None);
+        assert_eq!(raw.content, "short");
+        assert!(raw.content_ref.is_none());
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "gzip")]
+    async fn codec_test() {
+        let mut meta = RawMeta::from(Meta::from_string("B:raw/codec:1").unwrap());
+        meta.config = Codec::Gzip.to_config(&meta.config).unwrap();
+        let _: Meta = meta.try_into().unwrap();
+        let mut ins = Instance::new("raw/codec").unwrap();
+        ins.content = "b".repeat(1000);
+        let raw = RawInstance::new(&ins).unwrap();
+        assert!(raw.content.len() < 100);
+        assert_eq!(raw.content_codec, 1);
+        assert_eq!(raw.to().await.unwrap().content, ins.content);
+    }
+}
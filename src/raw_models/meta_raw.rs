@@ -1,3 +1,5 @@
+#[cfg(feature = "sqlite")]
+use std::convert::TryFrom;
 use std::convert::TryInto;
 
 use chrono::prelude::*;
@@ -5,6 +7,8 @@ use mysql_async::{Row, Value};
 
 use nature_common::{Meta, MetaType, NatureError, State};
 
+use crate::Codec;
+
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct RawMeta {
@@ -77,6 +81,7 @@ impl TryInto<Meta> for RawMeta {
             }
         }
         let _ = rtn.set_setting(&self.config)?;
+        Codec::load(&rtn.meta_string(), &self.config)?;
         debug!("get meta:{}", rtn.meta_string());
         Ok(rtn)
     }
@@ -99,6 +104,25 @@ impl From<Row> for RawMeta {
     }
 }
 
+#[cfg(feature = "sqlite")]
+impl TryFrom<&rusqlite::Row<'_>> for RawMeta {
+    type Error = rusqlite::Error;
+
+    fn try_from(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        Ok(RawMeta {
+            meta_type: row.get(0)?,
+            meta_key: row.get(1)?,
+            description: row.get(2)?,
+            version: row.get(3)?,
+            states: row.get(4)?,
+            fields: row.get(5)?,
+            config: row.get(6)?,
+            flag: row.get(7)?,
+            create_time: row.get(8)?,
+        })
+    }
+}
+
 impl Into<Vec<(String, Value)>> for RawMeta {
     fn into(self) -> Vec<(String, Value)> {
         params! {
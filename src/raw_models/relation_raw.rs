@@ -1,3 +1,6 @@
+#[cfg(feature = "sqlite")]
+use std::convert::TryFrom;
+
 use mysql_async::{Row, Value};
 use serde_json;
 
@@ -43,6 +46,20 @@ impl From<Row> for RawRelation {
     }
 }
 
+#[cfg(feature = "sqlite")]
+impl TryFrom<&rusqlite::Row<'_>> for RawRelation {
+    type Error = rusqlite::Error;
+
+    fn try_from(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        Ok(RawRelation {
+            from_meta: row.get(0)?,
+            to_meta: row.get(1)?,
+            settings: row.get(2)?,
+            flag: row.get(3)?,
+        })
+    }
+}
+
 impl Into<Vec<(String, Value)>> for RawRelation {
     fn into(self) -> Vec<(String, Value)> {
         params! {
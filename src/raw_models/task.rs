@@ -1,27 +1,37 @@
 use std::collections::HashSet;
+use std::convert::TryFrom;
 use std::fmt::Debug;
 
 use chrono::prelude::*;
 use lazy_static::__Deref;
-use mysql_async::{Row, Value};
+use mysql_async::{FromValueError, Row, Value};
+use mysql_async::prelude::{ConvIr, FromValue};
 use serde::Serialize;
 
 use nature_common::*;
 
 use crate::models::define::*;
-use crate::TaskDao;
+use crate::{Codec, DbError, DbResult, Mission, Storage, StorageTx, TaskDao, TaskState, TaskType};
+
+/// columns in the order of `RawTask` fields
+pub(crate) static TASK_COLUMNS: &str = "task_id, task_key, task_type, task_for, task_state, `data`, create_time, execute_time, retried_times, data_codec, priority";
+pub(crate) static TASK_FIELDS: [&str; 11] = ["task_id", "task_key", "task_type", "task_for", "task_state", "data", "create_time", "execute_time", "retried_times", "data_codec", "priority"];
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Eq, Hash)]
 pub struct RawTask {
     pub task_id: String,
     pub task_key: String,
-    pub task_type: i8,
+    pub task_type: TaskType,
     pub task_for: String,
-    pub task_state: i8,
+    pub task_state: TaskState,
     pub data: String,
     pub create_time: NaiveDateTime,
     pub execute_time: NaiveDateTime,
     pub retried_times: i16,
+    /// `data` is plain in memory, it is compressed by this `Codec` only when it is saved.
+    pub data_codec: i8,
+    /// the overdue tasks with greater priority are fetched first, see `RelationSettings.priority`
+    pub priority: i8,
 }
 
 impl Default for RawTask {
@@ -29,63 +39,116 @@ impl Default for RawTask {
         RawTask {
             task_id: "".to_string(),
             task_key: "".to_string(),
-            task_type: 0,
+            task_type: TaskType::default(),
             task_for: "".to_string(),
-            task_state: 0,
+            task_state: TaskState::Pending,
             data: "".to_string(),
             create_time: Local::now().naive_local(),
             execute_time: Local::now().naive_local(),
             retried_times: 0,
+            data_codec: 0,
+            priority: 0,
         }
     }
 }
 
 impl RawTask {
-    pub fn new<T: Serialize + Debug>(task: &T, task_key: &str, task_type: i8, task_for: &str) -> Result<RawTask> {
+    pub fn new<T: Serialize + Debug>(task: &T, task_key: &str, task_type: TaskType, task_for: &str) -> Result<RawTask> {
         let json = serde_json::to_string(task)?;
         Self::from_str(&json, task_key, task_type, task_for)
     }
 
-    pub fn from_str(json: &str, task_key: &str, task_type: i8, task_for: &str) -> Result<RawTask> {
+    /// `data` will be compressed by the `Codec` of the meta of the `task_key`,
+    /// it fails if the `Codec` is not enabled.
+    pub fn from_str(json: &str, task_key: &str, task_type: TaskType, task_for: &str) -> Result<RawTask> {
         if json.len() > *TASK_CONTENT_MAX_LENGTH.deref() {
             return Err(NatureError::SystemError("data's length can' be over : ".to_owned() + &TASK_CONTENT_MAX_LENGTH.to_string()));
         }
+        let data_codec = Self::codec_of(task_key);
+        Codec::try_from(data_codec)?.check()?;
         let time = Local::now().naive_local();
-        let id = format!("{}{}{}{}", json, task_key, task_for, task_type);
         Ok(RawTask {
-            task_id: format!("{:x}", generate_id(&id)?),
+            task_id: Self::gen_id(json, task_key, task_type, task_for)?,
             task_key: task_key.to_string(),
             task_type,
             task_for: task_for.to_string(),
-            task_state: 0,
+            task_state: TaskState::Pending,
             data: json.to_string(),
             create_time: time,
             execute_time: time,
             retried_times: 0,
+            data_codec,
+            priority: 0,
         })
     }
 
+    /// the task for the `to` of the `mission`, with the `priority` of its relation
+    pub fn from_mission(json: &str, task_key: &str, task_type: TaskType, mission: &Mission) -> Result<RawTask> {
+        let mut rtn = Self::from_str(json, task_key, task_type, &mission.to.meta_string())?;
+        rtn.priority = mission.priority;
+        Ok(rtn)
+    }
+
+    /// the `Codec` of the meta of the `task_key`
+    pub(crate) fn codec_of(task_key: &str) -> i8 {
+        let meta = task_key.split(SEPARATOR_INS_KEY.as_str()).next().unwrap_or("");
+        Codec::of(meta).into()
+    }
+
+    fn gen_id(json: &str, task_key: &str, task_type: TaskType, task_for: &str) -> Result<String> {
+        let id = format!("{}{}{}{}", json, task_key, task_for, i8::from(task_type));
+        Ok(format!("{:x}", generate_id(&id)?))
+    }
 
     /// for performance reason, one-to-one carry which we can reuse the beginning carry to finish all flows.
     /// That way we need not to communicate with DB for create new and delete old carrier.
-    /// But for failure we must redo from beginning. but I think it has small chance.
-    /// Another disadvantage is the failure information will be attached to the beginning.
+    /// When the carrying task failed, `detach` it, so that only the failed step will be redone.
+    pub fn carry(&mut self, old: &RawTask) {
+        self.task_id = old.task_id.clone(); // the id is used for final finished
+    }
+
+    #[deprecated(since = "0.15.0", note = "the closures are never called, use `carry` instead")]
     pub fn finish_old<FI, FD>(&mut self, old: &RawTask, _dao_insert: FI, _dao_delete: FD) -> Result<usize>
         where FI: Fn(&RawTask) -> Result<usize>,
               FD: Fn(&[u8]) -> Result<usize>
     {
-        // TODO  当遇到错误时如果要结束的 delivery ID 和新的delivery 不一样 需要结束之前的 delivery 并创建新的 delivery
-        self.task_id = old.task_id.clone(); // the id is used for final finished
+        self.carry(old);
         Ok(1)
     }
 
+    /// whether it is carrying the id of the beginning task
+    pub fn is_carrying(&self) -> Result<bool> {
+        let own = Self::gen_id(&self.data, &self.task_key, self.task_type, &self.task_for)?;
+        Ok(own != self.task_id)
+    }
+
+    /// Finishes the carried beginning task and saves this one with its own id in one transaction,
+    /// so the failure information will be attached to the failed step and the retry starts from there.
+    /// Returns the inserted number, 0 if it does not carry others. The id is kept if it fails.
+    pub async fn detach<S>(&mut self, storage: &S) -> Result<usize>
+        where S: Storage
+    {
+        if !self.is_carrying()? {
+            return Ok(0);
+        }
+        let carried = self.task_id.clone();
+        let mut own = self.clone();
+        own.task_id = Self::gen_id(&self.data, &self.task_key, self.task_type, &self.task_for)?;
+        let mut tx = storage.begin().await?;
+        let num = tx.insert_task(&own).await?;
+        tx.finish_task(&carried).await?;
+        tx.commit().await?;
+        self.task_id = own.task_id;
+        debug!("---- task detached from carrier {}, KEY: {} FOR: {} TYPE: {}", carried, &self.task_key, &self.task_for, self.task_type);
+        Ok(num)
+    }
 
     pub async fn save_batch<T>(news: &mut Vec<RawTask>, old_id: &str, task: &T) -> Result<()>
         where T: TaskDao
     {
         let mut will_deleted: HashSet<RawTask> = HashSet::new();
-        for v in news.iter() {
-            let num = task.insert(&v).await?;
+        let nums = task.insert_batch(news).await?;
+        for (v, num) in news.iter().zip(nums) {
             // drop repeated task avoid data consistent problem, retry.exe will pick it up
             if num != 1 {
                 will_deleted.insert(v.clone());
@@ -96,15 +159,43 @@ impl RawTask {
         Ok(())
     }
 
+    /// the same as `save_batch` but within the transaction, so the caller can save the instance
+    /// together and commit them atomically.
+    pub async fn save_batch_tx<T>(news: &mut Vec<RawTask>, old_id: &str, tx: &mut T) -> Result<()>
+        where T: StorageTx
+    {
+        let mut will_deleted: HashSet<RawTask> = HashSet::new();
+        for v in news.iter() {
+            if tx.insert_task(v).await? != 1 {
+                will_deleted.insert(v.clone());
+            }
+        }
+        news.retain(|one| !will_deleted.contains(one));
+        tx.finish_task(old_id).await?;
+        Ok(())
+    }
+
+    /// restores the `data` read from the database
+    pub(crate) fn decoded(mut self) -> Result<RawTask> {
+        self.data = Codec::try_from(self.data_codec)?.decode(&self.data)?;
+        Ok(self)
+    }
+
     pub fn task_string(&self) -> String {
         format!("raw_task: key|type|for {}{}{}", self.task_key, self.task_type, self.task_for)
     }
 }
 
-impl From<Row> for RawTask {
-    fn from(row: Row) -> Self {
-        let (task_id, task_key, task_type, task_for, task_state, data, create_time, execute_time, retried_times) = mysql_async::from_row(row);
-        RawTask {
+/// a row with undefined `task_type` or `task_state` is refused instead of panicking
+impl TryFrom<Row> for RawTask {
+    type Error = DbError;
+
+    fn try_from(row: Row) -> DbResult<Self> {
+        let (task_id, task_key, task_type, task_for, task_state, data, create_time, execute_time, retried_times, data_codec, priority) = match mysql_async::from_row_opt(row) {
+            Ok(columns) => columns,
+            Err(e) => return Err(DbError::Verify(format!("illegal task row: {:?}", e.0)))
+        };
+        Ok(RawTask {
             task_id,
             task_key,
             task_type,
@@ -114,30 +205,115 @@ impl From<Row> for RawTask {
             create_time,
             execute_time,
             retried_times,
-        }
+            data_codec,
+            priority,
+        })
     }
 }
 
-impl Into<Vec<(String, Value)>> for RawTask {
-    fn into(self) -> Vec<(String, Value)> {
-        params! {
-            "task_id" => self.task_id,
-            "task_key" => self.task_key,
-            "task_type" => self.task_type,
-            "task_for" => self.task_for,
-            "task_state" => self.task_state,
-            "data" => self.data,
-            "create_time" => self.create_time,
-            "execute_time" => self.execute_time,
-            "retried_times" => self.retried_times,
+/// reads `TaskType` and `TaskState` from their codes, the undefined codes are refused like the other wrong values.
+pub struct TaskCodeIr<T> {
+    code: T,
+    value: Value,
+}
+
+impl<T: TryFrom<i8>> ConvIr<T> for TaskCodeIr<T> {
+    fn new(v: Value) -> std::result::Result<Self, FromValueError> {
+        let code = mysql_async::from_value_opt::<i8>(v.clone())?;
+        match T::try_from(code) {
+            Ok(code) => Ok(TaskCodeIr { code, value: v }),
+            Err(_) => Err(FromValueError(v))
         }
     }
+
+    fn commit(self) -> T {
+        self.code
+    }
+
+    fn rollback(self) -> Value {
+        self.value
+    }
+}
+
+impl FromValue for TaskType {
+    type Intermediate = TaskCodeIr<TaskType>;
+}
+
+impl FromValue for TaskState {
+    type Intermediate = TaskCodeIr<TaskState>;
+}
+
+#[cfg(feature = "sqlite")]
+fn code_of<T: TryFrom<i8>>(value: rusqlite::types::ValueRef) -> rusqlite::types::FromSqlResult<T> {
+    let code = <i8 as rusqlite::types::FromSql>::column_result(value)?;
+    T::try_from(code).map_err(|_| rusqlite::types::FromSqlError::OutOfRange(code as i64))
+}
+
+#[cfg(feature = "sqlite")]
+impl rusqlite::types::FromSql for TaskType {
+    fn column_result(value: rusqlite::types::ValueRef) -> rusqlite::types::FromSqlResult<Self> {
+        code_of(value)
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl rusqlite::types::FromSql for TaskState {
+    fn column_result(value: rusqlite::types::ValueRef) -> rusqlite::types::FromSqlResult<Self> {
+        code_of(value)
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl TryFrom<&rusqlite::Row<'_>> for RawTask {
+    type Error = rusqlite::Error;
+
+    fn try_from(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        Ok(RawTask {
+            task_id: row.get(0)?,
+            task_key: row.get(1)?,
+            task_type: row.get(2)?,
+            task_for: row.get(3)?,
+            task_state: row.get(4)?,
+            data: row.get(5)?,
+            create_time: row.get(6)?,
+            execute_time: row.get(7)?,
+            retried_times: row.get(8)?,
+            data_codec: row.get(9)?,
+            priority: row.get(10)?,
+        })
+    }
+}
+
+/// `data` is encoded by its `Codec` here, the writing fails if it can't be.
+impl TryFrom<RawTask> for Vec<(String, Value)> {
+    type Error = NatureError;
+
+    fn try_from(raw: RawTask) -> Result<Self> {
+        let data = Codec::try_from(raw.data_codec)?.encode(&raw.data)?;
+        Ok(params! {
+            "task_id" => raw.task_id,
+            "task_key" => raw.task_key,
+            "task_type" => i8::from(raw.task_type),
+            "task_for" => raw.task_for,
+            "task_state" => i8::from(raw.task_state),
+            "data" => data,
+            "create_time" => raw.create_time,
+            "execute_time" => raw.execute_time,
+            "retried_times" => raw.retried_times,
+            "data_codec" => raw.data_codec,
+            "priority" => raw.priority,
+        })
+    }
 }
 
 #[cfg(test)]
 mod test {
     use std::collections::HashSet;
 
+    use crate::MemStorage;
+
+    use super::*;
+
     #[derive(Clone, Eq, Hash, PartialEq)]
     struct MyTest(String);
 
@@ -158,4 +334,78 @@ mod test {
         input.retain(|one| will_deleted.get(&one) != Some(&one));
         assert_eq!(2, input.len());
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn carry_and_detach_test() {
+        let storage = MemStorage::default();
+        let dao = storage.task();
+        let first = RawTask::from_str("first", "B:a:1|1||0", TaskType::Store, "B:b:1").unwrap();
+        assert_eq!(dao.insert(&first).await.unwrap(), 1);
+
+        // one-to-one: carried through without touching the db
+        let mut second = RawTask::from_str("second", "B:b:1|1||0", TaskType::Store, "B:c:1").unwrap();
+        assert!(!second.is_carrying().unwrap());
+        second.carry(&first);
+        let mut third = RawTask::from_str("third", "B:c:1|1||0", TaskType::Store, "B:d:1").unwrap();
+        third.carry(&second);
+        assert_eq!(third.task_id, first.task_id);
+        assert!(third.is_carrying().unwrap());
+
+        // failed at the third
+        assert_eq!(third.detach(&storage).await.unwrap(), 1);
+        assert_ne!(third.task_id, first.task_id);
+        assert!(!third.is_carrying().unwrap());
+        assert_eq!(dao.get(&first.task_id).await.unwrap().unwrap().task_state, TaskState::Finished);
+        assert_eq!(dao.get(&third.task_id).await.unwrap().unwrap().task_state, TaskState::Pending);
+        assert_eq!(third.detach(&storage).await.unwrap(), 0);
+    }
+
+    #[test]
+    fn from_mission_test() {
+        let mission = Mission {
+            to: Meta::from_string("B:b:1").unwrap(),
+            priority: 5,
+            ..Default::default()
+        };
+        let task = RawTask::from_mission("data", "B:a:1|1||0", TaskType::Convert, &mission).unwrap();
+        assert_eq!(task.task_for, "B:b:1");
+        assert_eq!(task.priority, 5);
+        assert_eq!(task.task_id, RawTask::from_str("data", "B:a:1|1||0", TaskType::Convert, "B:b:1").unwrap().task_id);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn finish_old_test() {
+        let first = RawTask::from_str("first", "B:a:1|1||0", TaskType::Store, "B:b:1").unwrap();
+        let mut second = RawTask::from_str("second", "B:b:1|1||0", TaskType::Store, "B:c:1").unwrap();
+        assert_eq!(second.finish_old(&first, |_| Ok(1), |_| Ok(1)).unwrap(), 1);
+        assert_eq!(second.task_id, first.task_id);
+    }
+
+    #[test]
+    #[cfg(not(feature = "gzip"))]
+    fn codec_disabled_test() {
+        Codec::load("B:task/disabled:1", r#"{"codec":"gzip"}"#).unwrap();
+        assert!(RawTask::from_str("data", "B:task/disabled:1|1||0", TaskType::Store, "B:b:1").is_err());
+        let task = RawTask {
+            data_codec: Codec::Gzip.into(),
+            ..Default::default()
+        };
+        assert!(Vec::<(String, Value)>::try_from(task).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "gzip")]
+    fn codec_test() {
+        Codec::load("B:task/codec:1", r#"{"codec":"gzip"}"#).unwrap();
+        let data = "c".repeat(1000);
+        let task = RawTask::from_str(&data, "B:task/codec:1|1||0", TaskType::Store, "B:b:1").unwrap();
+        assert_eq!(task.data_codec, 1);
+        assert_eq!(task.data, data);
+        let params = Vec::<(String, Value)>::try_from(task.clone()).unwrap();
+        let mut saved = task.clone();
+        saved.data = mysql_async::from_value(params[5].1.clone());
+        assert!(saved.data.len() < 100);
+        assert_eq!(saved.decoded().unwrap(), task);
+    }
+}
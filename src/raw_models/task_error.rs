@@ -1,18 +1,26 @@
+use std::convert::TryFrom;
+
 use chrono::prelude::*;
 use mysql_async::{Row, Value};
 
 use nature_common::NatureError;
 
+use crate::{DbError, DbResult, TaskState, TaskType};
 use crate::raw_models::RawTask;
 
+/// columns in the order of `RawTaskError` fields
+pub(crate) static TASK_ERROR_COLUMNS: &str = "task_id, task_key, task_type, task_for, `data`, create_time, msg, priority";
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct RawTaskError {
     pub task_id: String,
     pub task_key: String,
-    pub task_type: i8,
+    pub task_type: TaskType,
     pub task_for: String,
     pub data: String,
     pub create_time: NaiveDateTime,
     pub msg: String,
+    pub priority: i8,
 }
 
 impl RawTaskError {
@@ -24,16 +32,40 @@ impl RawTaskError {
             data: raw.data.clone(),
             create_time: raw.create_time,
             msg: format!("{:?}", err),
-            task_for: "".to_string(),
+            task_for: raw.task_for.clone(),
+            priority: raw.priority,
+        }
+    }
+
+    /// the task to be executed at once with `retried_times` reset
+    pub fn to_raw(&self) -> RawTask {
+        RawTask {
+            task_id: self.task_id.clone(),
+            task_key: self.task_key.clone(),
+            task_type: self.task_type,
+            task_for: self.task_for.clone(),
+            task_state: TaskState::Pending,
+            data: self.data.clone(),
+            create_time: self.create_time,
+            execute_time: Local::now().naive_local(),
+            retried_times: 0,
+            data_codec: RawTask::codec_of(&self.task_key),
+            priority: self.priority,
         }
     }
 }
 
 
-impl From<Row> for RawTaskError {
-    fn from(row: Row) -> Self {
-        let (task_id, task_key, task_type, task_for, data, create_time, msg) = mysql_async::from_row(row);
-        RawTaskError {
+/// a row with undefined `task_type` is refused instead of panicking
+impl TryFrom<Row> for RawTaskError {
+    type Error = DbError;
+
+    fn try_from(row: Row) -> DbResult<Self> {
+        let (task_id, task_key, task_type, task_for, data, create_time, msg, priority) = match mysql_async::from_row_opt(row) {
+            Ok(columns) => columns,
+            Err(e) => return Err(DbError::Verify(format!("illegal task_error row: {:?}", e.0)))
+        };
+        Ok(RawTaskError {
             task_id,
             task_key,
             task_type,
@@ -41,7 +73,26 @@ impl From<Row> for RawTaskError {
             data,
             create_time,
             msg,
-        }
+            priority,
+        })
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl TryFrom<&rusqlite::Row<'_>> for RawTaskError {
+    type Error = rusqlite::Error;
+
+    fn try_from(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        Ok(RawTaskError {
+            task_id: row.get(0)?,
+            task_key: row.get(1)?,
+            task_type: row.get(2)?,
+            task_for: row.get(3)?,
+            data: row.get(4)?,
+            create_time: row.get(5)?,
+            msg: row.get(6)?,
+            priority: row.get(7)?,
+        })
     }
 }
 
@@ -50,11 +101,33 @@ impl Into<Vec<(String, Value)>> for RawTaskError {
         params! {
             "task_id" => self.task_id,
             "task_key" => self.task_key,
-            "task_type" => self.task_type,
+            "task_type" => i8::from(self.task_type),
             "task_for" => self.task_for,
             "data" => self.data,
             "create_time" => self.create_time,
             "msg" => self.msg,
+            "priority" => self.priority,
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn from_raw_test() {
+        let mut raw = RawTask::from_str("data", "B:error:1|1||0", TaskType::Store, "B:to:1").unwrap();
+        raw.retried_times = 3;
+        raw.task_state = TaskState::Failed;
+        raw.priority = 3;
+        let error = RawTaskError::from_raw(&NatureError::LogicalError("wrong".to_string()), &raw);
+        assert_eq!(error.task_for, "B:to:1");
+        let back = error.to_raw();
+        assert_eq!(back.task_id, raw.task_id);
+        assert_eq!(back.task_for, raw.task_for);
+        assert_eq!(back.retried_times, 0);
+        assert_eq!(back.priority, 3);
+        assert_eq!(back.task_state, TaskState::Pending);
+    }
+}
@@ -0,0 +1,222 @@
+use std::sync::Arc;
+
+use mysql_async::{Params, Value};
+use rusqlite::{Connection, Row, Statement, ToSql};
+use rusqlite::ffi::{ErrorCode, SQLITE_CONSTRAINT_PRIMARYKEY, SQLITE_CONSTRAINT_UNIQUE};
+use rusqlite::types::Value as SqliteValue;
+use tokio::sync::{Mutex, OwnedMutexGuard};
+
+pub use instance_dao::*;
+pub use meta_dao::*;
+pub use migration::*;
+use nature_common::{NatureError, Result};
+pub use relation_dao::*;
+pub use storage::*;
+pub use task_dao::*;
+pub use transaction::*;
+
+use crate::{DbConfig, DbError, DbResult};
+
+pub mod task_check;
+
+type ConnGuard = OwnedMutexGuard<Option<Connection>>;
+
+lazy_static! {
+   static ref CONN : Arc<Mutex<Option<Connection>>> = Arc::new(Mutex::new(None));
+}
+
+/// SQLite counterpart of `MySql`, the parameters are built by `params!` the same way,
+/// so the SQL and the raw models can be shared between the backends.
+pub struct Sqlite;
+
+impl Sqlite {
+    /// Should be called once before any other operation,
+    /// otherwise the database will be opened from `DbConfig::from_env` on first use.
+    pub fn init(cfg: &DbConfig) -> Result<()> {
+        let conn = open(cfg)?;
+        let mut guard = match CONN.try_lock() {
+            Ok(guard) => guard,
+            Err(_) => return Err(NatureError::LogicalError("database is in use".to_string()))
+        };
+        if guard.is_some() {
+            return Err(NatureError::LogicalError("database had been initialized".to_string()));
+        }
+        *guard = Some(conn);
+        Ok(())
+    }
+
+    /// i(nsert) d(elete) u(pdate)
+    pub async fn idu<Q, P>(query: Q, params: P) -> DbResult<usize>
+        where
+            Q: AsRef<str>,
+            P: Into<Params>,
+    {
+        let sql = query.as_ref().to_string();
+        let params = to_named(params.into())?;
+        let guard = CONN.clone().lock_owned().await;
+        execute(guard, move |conn| execute_named(conn, &sql, &params)).await.1
+    }
+
+    pub async fn fetch<Q, P, F, U>(query: Q, params: P, mut fun: F) -> DbResult<Vec<U>>
+        where
+            Q: AsRef<str>,
+            P: Into<Params>,
+            F: FnMut(&Row) -> rusqlite::Result<U> + Send + 'static,
+            U: Send + 'static,
+    {
+        let sql = query.as_ref().to_string();
+        let params = to_named(params.into())?;
+        let guard = CONN.clone().lock_owned().await;
+        execute(guard, move |conn| {
+            let mut stmt = conn.prepare_cached(&sql)?;
+            let bound = bind(&stmt, &params)?;
+            let rows = stmt.query_map_named(&bound, |row| fun(row))?;
+            rows.collect::<rusqlite::Result<Vec<U>>>()
+        }).await.1
+    }
+}
+
+/// runs `fun` in the blocking pool and gives the connection back for the following use.
+async fn execute<F, U>(guard: ConnGuard, fun: F) -> (Option<ConnGuard>, DbResult<U>)
+    where
+        F: FnOnce(&Connection) -> rusqlite::Result<U> + Send + 'static,
+        U: Send + 'static,
+{
+    let rtn = tokio::task::spawn_blocking(move || {
+        let mut guard = guard;
+        let rtn = with_conn(&mut guard, fun);
+        (guard, rtn)
+    }).await;
+    match rtn {
+        Ok((guard, rtn)) => (Some(guard), rtn),
+        Err(e) => (None, Err(DbError::Environment(e.to_string())))
+    }
+}
+
+fn with_conn<F, U>(conn: &mut Option<Connection>, fun: F) -> DbResult<U>
+    where F: FnOnce(&Connection) -> rusqlite::Result<U>
+{
+    if conn.is_none() {
+        *conn = Some(open(&DbConfig::from_env()?)?);
+    }
+    match fun(conn.as_ref().unwrap()) {
+        Ok(rtn) => Ok(rtn),
+        Err(e) => Err(SqliteError(e).into())
+    }
+}
+
+fn execute_named(conn: &Connection, sql: &str, params: &[(String, SqliteValue)]) -> rusqlite::Result<usize> {
+    let mut stmt = conn.prepare_cached(sql)?;
+    let bound = bind(&stmt, params)?;
+    stmt.execute_named(&bound)
+}
+
+fn open(cfg: &DbConfig) -> Result<Connection> {
+    cfg.verify()?;
+    let conn = match Connection::open(&cfg.url) {
+        Ok(conn) => conn,
+        Err(e) => return Err(SqliteError(e).into())
+    };
+    if let Some(timeout) = cfg.connect_timeout {
+        if let Err(e) = conn.busy_timeout(timeout) {
+            return Err(SqliteError(e).into());
+        }
+    }
+    Ok(conn)
+}
+
+/// `params!` gives names without the leading ':' which sqlite needs.
+fn to_named(params: Params) -> Result<Vec<(String, SqliteValue)>> {
+    match params {
+        Params::Empty => Ok(vec![]),
+        Params::Named(map) => Ok(map.into_iter()
+            .map(|(k, v)| (format!(":{}", k), to_sqlite_value(v)))
+            .collect()),
+        Params::Positional(_) => Err(NatureError::LogicalError("sqlite only support named parameters".to_string()))
+    }
+}
+
+/// dynamic sql don't use all the parameters, sqlite treat unused one as an error so skip them here.
+fn bind<'a>(stmt: &Statement, params: &'a [(String, SqliteValue)]) -> rusqlite::Result<Vec<(&'a str, &'a dyn ToSql)>> {
+    let mut rtn: Vec<(&'a str, &'a dyn ToSql)> = vec![];
+    for (k, v) in params {
+        if stmt.parameter_index(k)?.is_some() {
+            rtn.push((k.as_str(), v as &dyn ToSql));
+        }
+    }
+    Ok(rtn)
+}
+
+/// date time is saved as fixed width text, so that it can be compared as string.
+fn to_sqlite_value(value: Value) -> SqliteValue {
+    match value {
+        Value::NULL => SqliteValue::Null,
+        Value::Bytes(b) => match String::from_utf8(b) {
+            Ok(s) => SqliteValue::Text(s),
+            Err(e) => SqliteValue::Blob(e.into_bytes())
+        },
+        Value::Int(i) => SqliteValue::Integer(i),
+        Value::UInt(u) => SqliteValue::Integer(u as i64),
+        Value::Float(f) => SqliteValue::Real(f as f64),
+        Value::Double(f) => SqliteValue::Real(f),
+        Value::Date(y, m, d, h, i, s, us) =>
+            SqliteValue::Text(format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:06}", y, m, d, h, i, s, us)),
+        Value::Time(neg, d, h, i, s, us) => {
+            let sign = if neg { "-" } else { "" };
+            SqliteValue::Text(format!("{}{:02}:{:02}:{:02}.{:06}", sign, d * 24 + h as u32, i, s, us))
+        }
+    }
+}
+
+pub struct SqliteError(rusqlite::Error);
+
+impl From<SqliteError> for DbError {
+    fn from(err: SqliteError) -> Self {
+        let msg = format!("database exception: {}", err.0);
+        warn!("{}", msg);
+        match err.0 {
+            rusqlite::Error::SqliteFailure(e, _) => match e.code {
+                ErrorCode::ConstraintViolation => match e.extended_code {
+                    SQLITE_CONSTRAINT_PRIMARYKEY | SQLITE_CONSTRAINT_UNIQUE => DbError::Duplicated(msg),
+                    _ => DbError::Logical(msg)
+                },
+                ErrorCode::DatabaseBusy => DbError::LockTimeout(msg),
+                ErrorCode::DatabaseLocked => DbError::LockTimeout(msg),
+                ErrorCode::TooBig => DbError::DataTooLong(msg),
+                ErrorCode::CannotOpen => DbError::Environment(msg),
+                ErrorCode::SystemIOFailure => DbError::Environment(msg),
+                _ => DbError::Logical(msg)
+            },
+            _ => DbError::Logical(msg),
+        }
+    }
+}
+
+impl From<SqliteError> for NatureError {
+    fn from(err: SqliteError) -> Self {
+        DbError::from(err).into()
+    }
+}
+
+/// all the tests share one in-memory database
+#[cfg(test)]
+pub(crate) fn init_test_db() {
+    use std::sync::Once;
+
+    use crate::Migrator;
+    static INIT: Once = Once::new();
+    INIT.call_once(|| {
+        Sqlite::init(&DbConfig::new(":memory:")).unwrap();
+        let mut guard = CONN.try_lock().unwrap();
+        let newest = MigratorImpl.migrations().last().unwrap().version;
+        with_conn(&mut guard, |conn| migration::migrate_conn(conn, newest)).unwrap();
+    });
+}
+
+mod instance_dao;
+mod meta_dao;
+mod migration;
+mod relation_dao;
+mod storage;
+mod task_dao;
+mod transaction;
@@ -0,0 +1,597 @@
+use std::convert::TryFrom;
+
+use chrono::{Duration, Local, TimeZone};
+use mysql_async::Value;
+
+use nature_common::*;
+
+use crate::{BATCH_INSERT_SIZE, DbError, DbResult, DeleteBy, HistoryCondition, InstanceDao, KeyRange, QUERY_SIZE_LIMIT, RangeCondition, remove_blobs, Retention};
+use crate::raw_models::{INSTANCE_COLUMNS, INSTANCE_FIELDS, multi_row_insert, RawInstance};
+use crate::sqlite_dao::Sqlite;
+
+lazy_static! {
+    pub static ref D_I: InstanceDaoImpl = InstanceDaoImpl {};
+}
+
+pub struct InstanceDaoImpl;
+
+#[async_trait]
+impl InstanceDao for InstanceDaoImpl {
+    async fn insert(&self, instance: &Instance) -> DbResult<usize> {
+        let mut new = RawInstance::new(instance)?;
+        let blob = new.put_blob().await?;
+        let sql = r"INSERT INTO instances
+            (ins_key, content, context, states, state_version, create_time, sys_context, from_key, content_ref, content_codec)
+            VALUES(:ins_key, :content,:context,:states,:state_version,:create_time,:sys_context,:from_key,:content_ref,:content_codec)";
+        let vec: Vec<(String, Value)> = new.into();
+        let rtn: usize = match Sqlite::idu(sql, vec).await {
+            Ok(n) => n,
+            Err(e) => {
+                remove_blobs(blob.into_iter().collect()).await;
+                return Err(e);
+            }
+        };
+        debug!("Saved instance : {}", instance.get_key());
+        Ok(rtn)
+    }
+
+    /// check whether source stored earlier
+    async fn get_by_from(&self, f_para: &IDAndFrom) -> DbResult<Option<Instance>> {
+        let sql = format!("SELECT {}
+            FROM instances
+            where ins_key like :para_like and from_key = :from_key and delete_time is null
+            order by state_version desc
+            limit 1", INSTANCE_COLUMNS);
+        let p = params! {
+            "para_like" => f_para.para_like().to_string(),
+            "from_key" => f_para.from_key.to_string(),
+        };
+
+        let rtn = Sqlite::fetch(sql, p, |row| RawInstance::try_from(row)).await?;
+        match rtn.len() {
+            1 => Ok(Some(rtn[0].to().await?)),
+            0 => Ok(None),
+            _ => Err(DbError::Logical("should not return more than one rows".to_string()))
+        }
+    }
+
+    async fn get_last_state(&self, f_para: &KeyCondition) -> DbResult<Option<Instance>> {
+        let sql = format!("SELECT {}
+            FROM instances
+            where ins_key = :ins_key and delete_time is null
+            order by state_version desc
+            limit 1", INSTANCE_COLUMNS);
+        let p = params! {
+            "ins_key" => f_para.get_key(),
+        };
+        let rtn = Sqlite::fetch(sql, p, |row| RawInstance::try_from(row)).await?;
+        match rtn.len() {
+            1 => Ok(Some(rtn[0].to().await?)),
+            0 => Ok(None),
+            _ => Err(DbError::Logical("should not return more than one rows".to_string()))
+        }
+    }
+
+    async fn get_by_id(&self, f_para: KeyCondition) -> DbResult<Option<Instance>> {
+        let sql = format!("SELECT {}
+            FROM instances
+            where ins_key = :ins_key and state_version = :state_version and delete_time is null
+            order by state_version desc
+            limit 1", INSTANCE_COLUMNS);
+        let p = params! {
+            "ins_key" => f_para.get_key().to_string(),
+            "state_version" => f_para.state_version,
+        };
+        let rtn = Sqlite::fetch(sql, p, |row| RawInstance::try_from(row)).await?;
+        match rtn.len() {
+            1 => Ok(Some(rtn[0].to().await?)),
+            0 => Ok(None),
+            _ => Err(DbError::Logical("should not return more than one rows".to_string()))
+        }
+    }
+
+    async fn get_history(&self, f_para: &HistoryCondition) -> DbResult<Vec<Instance>> {
+        let version_ge = if f_para.version_ge.is_none() { "" } else {
+            " and state_version >= :version_ge"
+        };
+        let version_le = if f_para.version_le.is_none() { "" } else {
+            " and state_version <= :version_le"
+        };
+        let sql = format!("SELECT {}
+            FROM instances
+            where ins_key = :ins_key and delete_time is null{}{}
+            order by state_version
+            limit :limit", INSTANCE_COLUMNS, version_ge, version_le);
+        let p = params! {
+            "ins_key" => f_para.ins_key.to_string(),
+            "version_ge" => f_para.version_ge.unwrap_or(0),
+            "version_le" => f_para.version_le.unwrap_or(0),
+            "limit" => f_para.get_limit(),
+        };
+        let result = Sqlite::fetch(sql, p, |row| RawInstance::try_from(row)).await?;
+        let mut rtn: Vec<Instance> = vec![];
+        for one in result {
+            rtn.push(one.to().await?)
+        }
+        Ok(rtn)
+    }
+
+    async fn get_downstream(&self, from: &FromInstance) -> DbResult<Vec<Instance>> {
+        let sql = format!("SELECT {}
+            FROM instances
+            where from_key = :from_key and delete_time is null
+            order by ins_key, state_version
+            limit :limit", INSTANCE_COLUMNS);
+        let p = params! {
+            "from_key" => from.to_string(),
+            "limit" => *QUERY_SIZE_LIMIT,
+        };
+        let result = Sqlite::fetch(sql, p, |row| RawInstance::try_from(row)).await?;
+        let mut rtn: Vec<Instance> = vec![];
+        for one in result {
+            rtn.push(one.to().await?)
+        }
+        Ok(rtn)
+    }
+
+    async fn delete(&self, ins: &Instance) -> DbResult<usize> {
+        let sql = r"UPDATE instances
+            SET delete_time = :delete_time, delete_by = :delete_by
+            WHERE ins_key=:ins_key and delete_time is null";
+        let p = params! {
+            "ins_key" => ins.key_no_state(),
+            "delete_time" => Local::now().naive_local(),
+            "delete_by" => i8::from(DeleteBy::User),
+        };
+        let rtn: usize = Sqlite::idu(sql, p).await?;
+        debug!("instance deleted, id is : {:?}", ins.id);
+        Ok(rtn)
+    }
+
+    async fn restore(&self, ins: &Instance) -> DbResult<usize> {
+        let sql = r"UPDATE instances
+            SET delete_time = null, delete_by = 0
+            WHERE ins_key=:ins_key and delete_by = :delete_by";
+        let p = params! {
+            "ins_key" => ins.key_no_state(),
+            "delete_by" => i8::from(DeleteBy::User),
+        };
+        let rtn: usize = Sqlite::idu(sql, p).await?;
+        debug!("instance restored, id is : {:?}", ins.id);
+        Ok(rtn)
+    }
+
+    async fn retain(&self, meta: &str, retention: &Retention) -> DbResult<usize> {
+        retention.check()?;
+        let meta_like = format!("{}{}%", meta, SEPARATOR_INS_KEY.as_str());
+        let now = Local::now();
+        let mut rtn: usize = 0;
+        if let Some(versions) = retention.keep_versions {
+            let sql = r"UPDATE instances
+                SET delete_time = :delete_time, delete_by = :delete_by
+                WHERE ins_key like :meta and delete_time is null
+                    and state_version <= (SELECT max(m.state_version) FROM instances m
+                        WHERE m.ins_key = instances.ins_key and m.delete_time is null) - :versions";
+            let p = params! {
+                "meta" => meta_like.to_string(),
+                "versions" => versions,
+                "delete_time" => now.naive_local(),
+                "delete_by" => i8::from(DeleteBy::Retention),
+            };
+            rtn += Sqlite::idu(sql, p).await?;
+        }
+        if let Some(days) = retention.keep_days {
+            // version 0 is not a state instance, the others keep the latest version
+            let sql = r"UPDATE instances
+                SET delete_time = :delete_time, delete_by = :delete_by
+                WHERE ins_key like :meta and delete_time is null and create_time < :create_time
+                    and (state_version = 0 or state_version < (SELECT max(m.state_version) FROM instances m
+                        WHERE m.ins_key = instances.ins_key and m.delete_time is null))";
+            let p = params! {
+                "meta" => meta_like,
+                "create_time" => now.checked_sub_signed(Duration::days(i64::from(days))).unwrap().naive_local(),
+                "delete_time" => now.naive_local(),
+                "delete_by" => i8::from(DeleteBy::Retention),
+            };
+            rtn += Sqlite::idu(sql, p).await?;
+        }
+        debug!("{} instances of {} are out of retention", rtn, meta);
+        Ok(rtn)
+    }
+
+    async fn purge(&self, delay: i64) -> DbResult<usize> {
+        let time = Local::now().checked_sub_signed(Duration::seconds(delay)).unwrap().naive_local();
+        let sql = r"SELECT content_ref FROM instances
+            WHERE delete_time < :delete_time and content_ref is not null";
+        let blobs = Sqlite::fetch(sql, params! {"delete_time" => time}, |row| row.get::<_, String>(0)).await?;
+        let sql = r"DELETE FROM instances
+            WHERE delete_time < :delete_time";
+        let p = params! {
+            "delete_time" => time,
+        };
+        let rtn: usize = Sqlite::idu(sql, p).await?;
+        // after the rows, so no row refers to a missing blob
+        remove_blobs(blobs).await;
+        Ok(rtn)
+    }
+
+    /// one multi-row `INSERT` for each `BATCH_INSERT_SIZE` rows, falls back to insert one by one
+    /// to tell which are duplicated.
+    async fn insert_batch(&self, instances: &[Instance]) -> DbResult<Vec<usize>> {
+        let mut rtn = Vec::with_capacity(instances.len());
+        for chunk in instances.chunks(*BATCH_INSERT_SIZE) {
+            let mut rows = Vec::with_capacity(chunk.len());
+            let mut blobs = vec![];
+            for one in chunk {
+                let mut raw = RawInstance::new(one)?;
+                match raw.put_blob().await {
+                    Ok(blob) => blobs.extend(blob),
+                    Err(e) => {
+                        remove_blobs(blobs).await;
+                        return Err(e.into());
+                    }
+                }
+                rows.push(raw.into());
+            }
+            let (values, p) = multi_row_insert(&INSTANCE_FIELDS, rows);
+            let sql = format!("INSERT INTO instances {}", values);
+            let saved = Sqlite::idu(sql, p).await;
+            if saved.is_err() {
+                remove_blobs(blobs).await;
+            }
+            match saved {
+                Ok(_) => rtn.extend(chunk.iter().map(|_| 1)),
+                Err(DbError::Duplicated(_)) => for one in chunk {
+                    rtn.push(match self.insert(one).await {
+                        Ok(num) => num,
+                        Err(DbError::Duplicated(_)) => 0,
+                        Err(e) => return Err(e)
+                    });
+                },
+                Err(e) => return Err(e)
+            }
+        }
+        Ok(rtn)
+    }
+}
+
+#[async_trait]
+impl KeyRange for InstanceDaoImpl {
+    /// ins_key > and between time range
+    async fn get_range(&self, condition: &RangeCondition) -> DbResult<Vec<Instance>> {
+        let cursor = condition.get_cursor()?;
+        let f_para = &condition.key;
+        let key_like = if f_para.meta.is_empty() {
+            ""
+        } else {
+            " and ins_key like :meta"
+        };
+        let key_gt = if f_para.key_gt.eq("") { "" } else {
+            " and ins_key > :key_gt"
+        };
+        let key_ge = if f_para.key_ge.eq("") { "" } else {
+            " and ins_key >= :key_ge"
+        };
+        let key_lt = if f_para.key_lt.eq("") { "" } else {
+            " and ins_key < :key_lt"
+        };
+        let key_le = if f_para.key_le.eq("") { "" } else {
+            " and ins_key <= :key_le"
+        };
+        let time_ge = match f_para.time_ge {
+            Some(_) => " and create_time >= :time_ge",
+            None => ""
+        };
+        let time_ge_v = match f_para.time_ge {
+            Some(ge) => ge,
+            None => 0
+        };
+        let time_lt = match f_para.time_lt {
+            Some(_) => " and create_time < :time_lt",
+            None => ""
+        };
+        let time_lt_v = match f_para.time_lt {
+            Some(lt) => lt,
+            None => 0
+        };
+        let after = match cursor {
+            Some(_) => " and (ins_key > :cursor_key or (ins_key = :cursor_key and state_version > :cursor_version))",
+            None => ""
+        };
+        let (cursor_key, cursor_version) = cursor.unwrap_or_default();
+        let limit = condition.get_limit();
+        let sql = format!("SELECT {}
+            FROM instances
+            where delete_time is null{}{}{}{}{}{}{}{}
+            order by ins_key, state_version
+            limit :limit", INSTANCE_COLUMNS, time_ge, time_lt, key_gt, key_ge, key_lt, key_le, key_like, after);
+
+        let p = params! {
+            "meta" => f_para.meta.to_string() + "%",
+            "key_gt" => f_para.key_gt.to_string(),
+            "key_ge" => f_para.key_ge.to_string(),
+            "key_lt" => f_para.key_lt.to_string(),
+            "key_le" => f_para.key_le.to_string(),
+            "time_ge" => Local.timestamp_millis(time_ge_v).naive_local(),
+            "time_lt" => Local.timestamp_millis(time_lt_v).naive_local(),
+            "cursor_key" => cursor_key,
+            "cursor_version" => cursor_version,
+            "limit" => limit + 1,
+        };
+        let result = Sqlite::fetch(sql, p, |row| RawInstance::try_from(row)).await?;
+        let mut rtn: Vec<Instance> = vec![];
+        for one in result {
+            rtn.push(one.to().await?)
+        }
+        Ok(rtn)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use futures::TryStreamExt;
+
+    use crate::{init_test_blob_store, StateInsert};
+    use crate::models::define::INSTANCE_CONTENT_MAX_LENGTH;
+    use crate::sqlite_dao::init_test_db;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn insert_and_query_test() {
+        init_test_db();
+        let mut ins = Instance::new("sqlite/instance").unwrap();
+        ins.id = 123;
+        ins.content = "hello".to_string();
+        assert_eq!(D_I.insert(&ins).await.unwrap(), 1);
+        let rtn = D_I.insert(&ins).await;
+        assert!(matches!(rtn, Err(DbError::Duplicated(_))));
+
+        let got = D_I.get_by_id(KeyCondition::from(&ins)).await.unwrap().unwrap();
+        assert_eq!(got.content, "hello");
+        let got = D_I.get_by_key(ins.get_key(), "|".to_string()).await.unwrap();
+        assert!(got.is_some());
+
+        let mut para = KeyCondition::new(0, "B:sqlite/instance:1", "", 0);
+        para.id = "".to_string();
+        para.limit = 10;
+        let rtn = D_I.get_by_key_range(&RangeCondition::from(para)).await.unwrap();
+        assert_eq!(rtn.instances.len(), 1);
+        assert!(!rtn.has_more);
+
+        assert_eq!(D_I.delete(&ins).await.unwrap(), 1);
+        let got = D_I.get_by_id(KeyCondition::from(&ins)).await.unwrap();
+        assert!(got.is_none());
+    }
+
+    #[tokio::test]
+    async fn insert_batch_test() {
+        init_test_db();
+        let list: Vec<Instance> = (1..4).map(|i| {
+            let mut ins = Instance::new("sqlite/batch").unwrap();
+            ins.id = i;
+            ins
+        }).collect();
+        assert_eq!(D_I.insert_batch(&list[1..]).await.unwrap(), vec![1, 1]);
+        assert_eq!(D_I.insert_batch(&list).await.unwrap(), vec![1, 0, 0]);
+        assert!(D_I.get_by_id(KeyCondition::from(&list[0])).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn get_history_test() {
+        init_test_db();
+        let mut ins = Instance::new("sqlite/history").unwrap();
+        ins.id = 1;
+        for i in 0..3 {
+            ins.state_version = i;
+            ins.from = Some(FromInstance::from_str(&format!("B:from:1|1||{}", i)).unwrap());
+            assert_eq!(D_I.insert(&ins).await.unwrap(), 1);
+        }
+        let mut para = HistoryCondition::new(&ins.key_no_state());
+        let history = D_I.get_history(&para).await.unwrap();
+        assert_eq!(history.iter().map(|one| one.state_version).collect::<Vec<i32>>(), vec![0, 1, 2]);
+        assert_eq!(history[1].from.as_ref().unwrap().to_string(), "B:from:1|1||1");
+        para.version_ge = Some(1);
+        para.version_le = Some(1);
+        assert_eq!(D_I.get_history(&para).await.unwrap().len(), 1);
+        para.version_le = None;
+        para.limit = 1;
+        assert_eq!(D_I.get_history(&para).await.unwrap()[0].state_version, 1);
+    }
+
+    #[tokio::test]
+    async fn get_downstream_test() {
+        init_test_db();
+        let mut order = Instance::new("sqlite/lineage/order").unwrap();
+        order.id = 1;
+        let mut pay = Instance::new("sqlite/lineage/pay").unwrap();
+        pay.id = 1;
+        pay.from = Some(FromInstance::from(&order));
+        D_I.insert(&order).await.unwrap();
+        D_I.insert(&pay).await.unwrap();
+
+        let got = D_I.get_downstream(&FromInstance::from(&order)).await.unwrap();
+        assert_eq!(got.len(), 1);
+        assert_eq!(got[0].meta, pay.meta);
+        let lineage = D_I.get_lineage(&pay, 3).await.unwrap();
+        assert_eq!(lineage.upstream.unwrap().instance.meta, order.meta);
+        assert!(lineage.downstream.is_empty());
+    }
+
+    #[tokio::test]
+    async fn key_range_page_test() {
+        init_test_db();
+        let mut ins = Instance::new("sqlite/page").unwrap();
+        for id in 1..3 {
+            ins.id = id;
+            for version in 0..2 {
+                ins.state_version = version;
+                ins.from = Some(FromInstance::from_str(&format!("B:from:1|1||{}", version)).unwrap());
+                D_I.insert(&ins).await.unwrap();
+            }
+        }
+        let mut para = KeyCondition::new(0, "B:sqlite/page:1", "", 0);
+        para.limit = 3;
+        let mut para = RangeCondition::from(para);
+        let page = D_I.get_by_key_range(&para).await.unwrap();
+        assert!(page.has_more);
+        assert_eq!(page.next_cursor, Some("B:sqlite/page:1|2||0".to_string()));
+
+        para.cursor = page.next_cursor.unwrap();
+        let page = D_I.get_by_key_range(&para).await.unwrap();
+        assert!(!page.has_more);
+        assert_eq!(page.instances.iter().map(|one| one.get_key()).collect::<Vec<String>>(), vec!["B:sqlite/page:1|2||1"]);
+
+        para.cursor = "".to_string();
+        para.key.limit = 3;
+        let mut stream = D_I.scan(para);
+        let mut num = 0;
+        while let Some(one) = stream.try_next().await.unwrap() {
+            assert_eq!(one.meta, "B:sqlite/page:1");
+            num += 1;
+        }
+        assert_eq!(num, 4);
+    }
+
+    #[tokio::test]
+    async fn delete_and_retain_test() {
+        init_test_db();
+        let mut ins = Instance::new("sqlite/retention").unwrap();
+        ins.id = 1;
+        ins.create_time = Local::now().timestamp_millis();
+        for version in 0..3 {
+            ins.state_version = version;
+            ins.from = Some(FromInstance::from_str(&format!("B:from:1|1||{}", version)).unwrap());
+            D_I.insert(&ins).await.unwrap();
+        }
+        let para = KeyCondition::from(&ins);
+        assert_eq!(D_I.delete(&ins).await.unwrap(), 3);
+        assert!(D_I.get_last_state(&para).await.unwrap().is_none());
+        // the keys are still held
+        assert!(matches!(D_I.insert(&ins).await, Err(DbError::Duplicated(_))));
+        assert_eq!(D_I.restore(&ins).await.unwrap(), 3);
+        assert_eq!(D_I.get_last_state(&para).await.unwrap().unwrap().state_version, 2);
+
+        let mut retention = Retention {
+            keep_versions: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(D_I.retain("B:sqlite/retention:1", &retention).await.unwrap(), 1);
+        assert_eq!(D_I.retain("B:sqlite/retention:1", &retention).await.unwrap(), 0);
+        // the version out of the retention is not restored
+        assert_eq!(D_I.delete(&ins).await.unwrap(), 2);
+        assert_eq!(D_I.restore(&ins).await.unwrap(), 2);
+        let history = D_I.get_history(&HistoryCondition::new(&ins.key_no_state())).await.unwrap();
+        assert_eq!(history.iter().map(|one| one.state_version).collect::<Vec<i32>>(), vec![1, 2]);
+
+        let store = init_test_blob_store();
+        let created = Local::now().checked_sub_signed(Duration::days(3)).unwrap().timestamp_millis();
+        let mut old = Instance::new("sqlite/retention").unwrap();
+        old.id = 2;
+        old.content = "r".repeat(*INSTANCE_CONTENT_MAX_LENGTH + 1);
+        old.create_time = created;
+        D_I.insert(&old).await.unwrap();
+        let mut state = Instance::new("sqlite/retention").unwrap();
+        state.id = 3;
+        state.create_time = created;
+        for version in 1..3 {
+            state.state_version = version;
+            state.from = Some(FromInstance::from_str(&format!("B:from:1|1||{}", version)).unwrap());
+            D_I.insert(&state).await.unwrap();
+        }
+        retention.keep_versions = None;
+        retention.keep_days = Some(2);
+        assert_eq!(D_I.retain("B:sqlite/retention:1", &retention).await.unwrap(), 2);
+        assert!(D_I.get_by_id(KeyCondition::from(&old)).await.unwrap().is_none());
+        // the latest state version is kept however old it is
+        let last = D_I.get_last_state(&KeyCondition::from(&state)).await.unwrap().unwrap();
+        assert_eq!(last.state_version, 2);
+
+        assert_eq!(store.of(&old.content).len(), 1);
+        assert!(D_I.purge(-1).await.unwrap() >= 3);
+        assert_eq!(D_I.restore(&old).await.unwrap(), 0);
+        assert!(store.of(&old.content).is_empty());
+        // the keys are released
+        assert_eq!(D_I.insert(&old).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn retain_deleted_latest_test() {
+        init_test_db();
+        let mut ins = Instance::new("sqlite/retain_deleted").unwrap();
+        ins.id = 1;
+        ins.create_time = Local::now().checked_sub_signed(Duration::days(3)).unwrap().timestamp_millis();
+        for version in 1..4 {
+            ins.state_version = version;
+            ins.from = Some(FromInstance::from_str(&format!("B:from:1|1||{}", version)).unwrap());
+            D_I.insert(&ins).await.unwrap();
+        }
+        let sql = r"UPDATE instances SET delete_time = :delete_time, delete_by = :delete_by
+            WHERE ins_key = :ins_key and state_version = 3";
+        let p = params! {
+            "ins_key" => ins.key_no_state(),
+            "delete_time" => Local::now().naive_local(),
+            "delete_by" => i8::from(DeleteBy::User),
+        };
+        assert_eq!(Sqlite::idu(sql, p).await.unwrap(), 1);
+        // the latest of the live versions is kept for both the days and the versions
+        let retention = Retention {
+            keep_days: Some(2),
+            keep_versions: Some(1),
+        };
+        assert_eq!(D_I.retain("B:sqlite/retain_deleted:1", &retention).await.unwrap(), 1);
+        let last = D_I.get_last_state(&KeyCondition::from(&ins)).await.unwrap().unwrap();
+        assert_eq!(last.state_version, 2);
+    }
+
+    #[tokio::test]
+    async fn overflow_test() {
+        init_test_db();
+        let store = init_test_blob_store();
+        let mut ins = Instance::new("sqlite/overflow").unwrap();
+        ins.id = 1;
+        ins.content = "o".repeat(*INSTANCE_CONTENT_MAX_LENGTH + 1);
+        assert_eq!(D_I.insert(&ins).await.unwrap(), 1);
+        assert_eq!(D_I.get_by_id(KeyCondition::from(&ins)).await.unwrap().unwrap().content, ins.content);
+        // the blob of the duplicated one is removed
+        assert!(matches!(D_I.insert(&ins).await, Err(DbError::Duplicated(_))));
+        ins.state_version = 1;
+        assert_eq!(D_I.insert_batch(&[ins.clone()]).await.unwrap(), vec![0]);
+        assert_eq!(store.of(&ins.content).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn insert_state_test() {
+        init_test_db();
+        let mut ins = Instance::new("sqlite/cas").unwrap();
+        ins.id = 1;
+        ins.state_version = 2;
+        assert_eq!(D_I.insert_state(&ins).await.unwrap(), StateInsert::Conflict(None));
+        ins.state_version = 1;
+        ins.from = Some(FromInstance::from_str("B:from:1|1||1").unwrap());
+        assert_eq!(D_I.insert_state(&ins).await.unwrap(), StateInsert::Inserted);
+        let mut other = ins.clone();
+        other.from = Some(FromInstance::from_str("B:from:1|1||2").unwrap());
+        other.state_version = 2;
+        assert_eq!(D_I.insert_state(&other).await.unwrap(), StateInsert::Inserted);
+        // the other writer saw version 0 too
+        let mut late = other.clone();
+        late.from = Some(FromInstance::from_str("B:from:1|1||3").unwrap());
+        late.state_version = 1;
+        let rtn = D_I.insert_state(&late).await.unwrap();
+        assert!(matches!(rtn, StateInsert::Conflict(Some(last)) if last.state_version == 2));
+
+        // the upstream is redelivered after the state moved on
+        let mut again = ins.clone();
+        again.state_version = 3;
+        let rtn = D_I.insert_state(&again).await.unwrap();
+        assert!(matches!(rtn, StateInsert::Repeated(existing) if existing.state_version == 1));
+
+        // the deleted versions are still held
+        D_I.delete(&ins).await.unwrap();
+        assert_eq!(D_I.insert_state(&ins).await.unwrap(), StateInsert::Deleted);
+        assert_eq!(D_I.insert_state(&late).await.unwrap(), StateInsert::Deleted);
+    }
+}
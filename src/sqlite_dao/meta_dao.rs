@@ -0,0 +1,128 @@
+use std::convert::TryFrom;
+
+use mysql_async::Value;
+
+use nature_common::Meta;
+
+use crate::{DbError, DbResult, MetaDao, Sqlite};
+use crate::raw_models::RawMeta;
+
+lazy_static! {
+    pub static ref D_M: MetaDaoImpl = MetaDaoImpl {};
+}
+
+pub struct MetaDaoImpl;
+
+#[async_trait]
+impl MetaDao for MetaDaoImpl {
+    async fn get(&self, meta_str: &str) -> DbResult<Option<RawMeta>> {
+        let sql = r"SELECT meta_type, meta_key, description, version, states, fields, config, flag, create_time
+            FROM meta
+            WHERE meta_type = :meta_type and meta_key = :meta_key and version = :version and flag = 1";
+
+        let m = Meta::from_string(&meta_str)?;
+        let p = params! {
+            "meta_type" => m.get_meta_type().get_prefix(),
+            "meta_key" => m.get_key(),
+            "version" => m.version,
+        };
+
+        let rtn = Sqlite::fetch(sql, p, |row| RawMeta::try_from(row)).await?;
+        match rtn.len() {
+            1 => {
+                let meta = rtn[0].clone();
+                debug!("load meta : {:?}", &rtn);
+                Ok(Some(meta))
+            }
+            0 => Ok(None),
+            _ => Err(DbError::Logical("should not return more than one rows".to_string()))
+        }
+    }
+
+    async fn insert(&self, define: &RawMeta) -> DbResult<usize> {
+        let sql = r"INSERT INTO meta
+            (meta_type, meta_key, description, version, states, fields, config, flag, create_time)
+            VALUES(:meta_type, :meta_key, :description, :version, :states, :fields, :config, :flag, :create_time)";
+        let p: Vec<(String, Value)> = define.clone().into();
+        let rtn: usize = Sqlite::idu(sql, p).await?;
+        debug!("Saved meta : {}:{}:{}", define.meta_type, define.meta_key, define.version);
+        Ok(rtn)
+    }
+
+    async fn update_flag(&self, meta_str: &str, flag_f: i32) -> DbResult<usize> {
+        let sql = r"UPDATE meta
+            SET flag=:flag
+            WHERE meta_type = :meta_type and meta_key = :meta_key and version = :version";
+
+        let m = Meta::from_string(meta_str)?;
+        let p = params! {
+            "meta_type" => m.get_meta_type().get_prefix(),
+            "meta_key" => m.get_key(),
+            "version" => m.version,
+            "flag" => flag_f,
+        };
+        let rtn = Sqlite::idu(sql, p).await?;
+        debug!("meta flag updated: {}:{}:{}", m.get_meta_type().get_prefix(), m.get_key(), m.version);
+        Ok(rtn)
+    }
+
+    async fn delete(&self, m: &Meta) -> DbResult<usize> {
+        let sql = r"DELETE FROM meta
+            WHERE meta_type = :meta_type and meta_key = :meta_key and version = :version";
+
+        let p = params! {
+            "meta_type" => m.get_meta_type().get_prefix(),
+            "meta_key" => m.get_key(),
+            "version" => m.version,
+        };
+
+        let rtn: usize = Sqlite::idu(sql, p).await?;
+        debug!("meta deleted: {}:{}:{}", m.get_meta_type().get_prefix(), m.get_key(), m.version);
+        Ok(rtn)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::prelude::*;
+
+    use crate::sqlite_dao::init_test_db;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn define_test() {
+        init_test_db();
+        let define = RawMeta {
+            meta_type: "B".to_string(),
+            description: Some("description".to_string()),
+            version: 100,
+            states: Some("status".to_string()),
+            fields: Some("fields".to_string()),
+            config: "{}".to_string(),
+            flag: 1,
+            create_time: Local::now().naive_local(),
+            meta_key: "sqlite".to_string(),
+        };
+        let meta = "B:sqlite:100";
+        let m = Meta::from_string(meta).unwrap();
+
+        // insert
+        assert_eq!(D_M.insert(&define).await.unwrap(), 1);
+        // repeat insert
+        let rtn = D_M.insert(&define).await;
+        assert!(matches!(rtn, Err(DbError::Duplicated(_))));
+        // find inserted
+        let mut row: RawMeta = D_M.get(meta).await.unwrap().unwrap();
+        row.create_time = define.create_time;
+        assert_eq!(row, define);
+
+        // change flag
+        let _ = D_M.update_flag(meta, 0).await;
+        let row = D_M.get(meta).await.unwrap();
+        assert_eq!(row, None);
+
+        // delete it
+        assert_eq!(D_M.delete(&m).await.unwrap(), 1);
+    }
+}
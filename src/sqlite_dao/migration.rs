@@ -0,0 +1,143 @@
+use rusqlite::{Connection, NO_PARAMS};
+
+use crate::{check_version, DbResult, Migration, Migrator, plan, Step};
+
+use super::{CONN, execute, Sqlite};
+
+static MIGRATIONS: [Migration; 9] = [
+    Migration {
+        version: 1,
+        name: "init",
+        up: include_str!("../../migrations/sqlite/001_init/up.sql"),
+        down: include_str!("../../migrations/sqlite/001_init/down.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "instances_from_key",
+        up: include_str!("../../migrations/sqlite/002_instances_from_key/up.sql"),
+        down: include_str!("../../migrations/sqlite/002_instances_from_key/down.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "instances_content_ref",
+        up: include_str!("../../migrations/sqlite/003_instances_content_ref/up.sql"),
+        down: include_str!("../../migrations/sqlite/003_instances_content_ref/down.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "codec",
+        up: include_str!("../../migrations/sqlite/004_codec/up.sql"),
+        down: include_str!("../../migrations/sqlite/004_codec/down.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "instances_delete_time",
+        up: include_str!("../../migrations/sqlite/005_instances_delete_time/up.sql"),
+        down: include_str!("../../migrations/sqlite/005_instances_delete_time/down.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "task_lease",
+        up: include_str!("../../migrations/sqlite/006_task_lease/up.sql"),
+        down: include_str!("../../migrations/sqlite/006_task_lease/down.sql"),
+    },
+    Migration {
+        version: 7,
+        name: "task_priority",
+        up: include_str!("../../migrations/sqlite/007_task_priority/up.sql"),
+        down: include_str!("../../migrations/sqlite/007_task_priority/down.sql"),
+    },
+    Migration {
+        version: 8,
+        name: "instances_delete_by",
+        up: include_str!("../../migrations/sqlite/008_instances_delete_by/up.sql"),
+        down: include_str!("../../migrations/sqlite/008_instances_delete_by/down.sql"),
+    },
+    Migration {
+        version: 9,
+        name: "task_error_priority",
+        up: include_str!("../../migrations/sqlite/009_task_error_priority/up.sql"),
+        down: include_str!("../../migrations/sqlite/009_task_error_priority/down.sql"),
+    },
+];
+
+pub struct MigratorImpl;
+
+impl Sqlite {
+    /// upgrades the schema to the newest version
+    pub async fn migrate() -> DbResult<i32> {
+        MigratorImpl.migrate().await
+    }
+}
+
+fn current_version(conn: &Connection) -> rusqlite::Result<i32> {
+    conn.execute_batch(r"CREATE TABLE IF NOT EXISTS `schema_version` (
+        `version` INTEGER NOT NULL PRIMARY KEY,
+        `name` TEXT NOT NULL,
+        `applied_time` TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )")?;
+    conn.query_row("SELECT IFNULL(MAX(version), 0) FROM schema_version", NO_PARAMS, |row| row.get(0))
+}
+
+/// each step is applied in its own transaction, sqlite can rollback the DDL too.
+pub(super) fn migrate_conn(conn: &Connection, version: i32) -> rusqlite::Result<i32> {
+    let current = current_version(conn)?;
+    for step in plan(&MIGRATIONS, current, version) {
+        let tx = conn.unchecked_transaction()?;
+        match step {
+            Step::Up(m) => {
+                tx.execute_batch(m.up)?;
+                tx.execute_named("INSERT INTO schema_version (version, name) VALUES(:version, :name)",
+                                 &[(":version", &m.version), (":name", &m.name)])?;
+                info!("schema upgraded to version {}: {}", m.version, m.name);
+            }
+            Step::Down(m) => {
+                tx.execute_batch(m.down)?;
+                tx.execute_named("DELETE FROM schema_version WHERE version = :version", &[(":version", &m.version)])?;
+                info!("schema downgraded from version {}: {}", m.version, m.name);
+            }
+        }
+        tx.commit()?;
+    }
+    Ok(version)
+}
+
+#[async_trait]
+impl Migrator for MigratorImpl {
+    fn migrations(&self) -> &'static [Migration] {
+        &MIGRATIONS
+    }
+
+    async fn current_version(&self) -> DbResult<i32> {
+        let guard = CONN.clone().lock_owned().await;
+        execute(guard, current_version).await.1
+    }
+
+    async fn migrate_to(&self, version: i32) -> DbResult<i32> {
+        check_version(&MIGRATIONS, version)?;
+        let guard = CONN.clone().lock_owned().await;
+        execute(guard, move |conn| migrate_conn(conn, version)).await.1
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn migrate_test() {
+        let conn = Connection::open_in_memory().unwrap();
+        let newest = MIGRATIONS.last().unwrap().version;
+        assert_eq!(current_version(&conn).unwrap(), 0);
+        assert_eq!(migrate_conn(&conn, newest).unwrap(), newest);
+        assert_eq!(current_version(&conn).unwrap(), newest);
+        // again do nothing
+        assert_eq!(migrate_conn(&conn, newest).unwrap(), newest);
+
+        assert_eq!(migrate_conn(&conn, 0).unwrap(), 0);
+        assert_eq!(current_version(&conn).unwrap(), 0);
+        let tables: i32 = conn.query_row("SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'instances'", NO_PARAMS, |row| row.get(0)).unwrap();
+        assert_eq!(tables, 0);
+        assert_eq!(migrate_conn(&conn, newest).unwrap(), newest);
+    }
+}
@@ -0,0 +1,165 @@
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+use mysql_async::Value;
+
+use nature_common::Executor;
+
+use crate::{DbResult, MetaCache, MetaDao, Relation, RelationDao, Relations, RelationSettings};
+use crate::raw_models::RawRelation;
+
+use super::*;
+
+lazy_static! {
+    pub static ref D_R: RelationDaoImpl = RelationDaoImpl {};
+}
+
+pub struct RelationDaoImpl;
+
+#[async_trait]
+impl RelationDao for RelationDaoImpl {
+    async fn get_relations<MC, M>(&self, from: &str, meta_cache_getter: &MC, meta_getter: &M) -> Relations
+        where MC: MetaCache, M: MetaDao {
+        let sql = r"SELECT from_meta, to_meta, settings, flag
+            FROM relation
+            where from_meta = :from_meta and flag = 1";
+
+        let p = params! {
+            "from_meta" => from,
+        };
+
+        let raws = Sqlite::fetch(sql, p, |row| RawRelation::try_from(row)).await?;
+        match raws.len() {
+            0 => Ok(vec![]),
+            x if x > 0 => {
+                let mut rtn: Vec<Relation> = Vec::new();
+                for d in raws {
+                    match Relation::from_raw(d, meta_cache_getter, meta_getter).await {
+                        Ok(r) => rtn.push(r),
+                        Err(e) => return Err(e)
+                    }
+                }
+                Ok(rtn)
+            }
+            _ => Err(NatureError::SystemError("unknown error occurred".to_string(),
+            ))
+        }
+    }
+    async fn insert(&self, one: RawRelation) -> DbResult<usize> {
+        let sql = r"INSERT INTO relation
+            (from_meta, to_meta, settings, flag)
+            VALUES(:from_meta, :to_meta, :settings, :flag)";
+
+        let p: Vec<(String, Value)> = one.clone().into();
+        let rtn: usize = Sqlite::idu(sql, p).await?;
+        debug!("Saved relation : {} -> {}", one.from_meta, one.to_meta);
+        Ok(rtn)
+    }
+    async fn delete(&self, one: RawRelation) -> DbResult<usize> {
+        let sql = r"DELETE FROM relation
+            WHERE from_meta=:from_meta AND to_meta=:to_meta";
+
+        let p = params! {
+            "from_meta" => one.from_meta.to_string(),
+            "to_meta" => one.to_meta.to_string(),
+        };
+
+        let rtn: usize = Sqlite::idu(sql, p).await?;
+        debug!("relation deleted : {} -> {}", one.from_meta, one.to_meta);
+        Ok(rtn)
+    }
+
+    /// `from` and `to`'s form are full_key:version
+    async fn update_flag(&self, from: &str, to: &str, flag_f: i32) -> DbResult<usize> {
+        let sql = r"UPDATE relation
+            SET settings='', flag=:flag
+            WHERE from_meta=:from_meta AND to_meta=:to_meta";
+
+        let p = params! {
+            "from_meta" => from,
+            "to_meta" => to,
+            "flag" => flag_f,
+        };
+
+        let rtn = Sqlite::idu(sql, p).await?;
+        debug!("relation flag updated: : {} -> {}", from, to);
+        Ok(rtn)
+    }
+
+    /// `version` will be set to 0
+    async fn insert_by_biz(&self, from: &str, to: &str, url: &str, protocol: &str) -> DbResult<RawRelation> {
+        let one = RawRelation::new(
+            from,
+            to,
+            &RelationSettings {
+                selector: None,
+                executor: Some(Executor {
+                    protocol: nature_common::Protocol::from_str(protocol)?,
+                    url: url.to_string(),
+                    settings: "".to_string(),
+                }),
+                filter_before: vec![],
+                filter_after: vec![],
+                use_upstream_id: false,
+                target: Default::default(),
+                delay: 0,
+                delay_on_para: (0, 0),
+                id_bridge: false,
+                priority: 0,
+                retry: None,
+            },
+        )?;
+        let _ = D_R.insert(one.clone()).await;
+        Ok(one)
+    }
+
+    async fn delete_by_biz(&self, from: &str, to: &str) -> DbResult<usize> {
+        let row = RawRelation {
+            from_meta: from.to_string(),
+            to_meta: to.to_string(),
+            settings: String::new(),
+            flag: 1,
+        };
+        D_R.delete(row).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use nature_common::Meta;
+
+    use crate::sqlite_dao::init_test_db;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn relation_test() {
+        init_test_db();
+        let meta = "B:sqlite_from:1";
+        let rtn = D_R.get_relations(meta, &MCMock {}, &*D_M).await.unwrap();
+        assert_eq!(rtn.is_empty(), true);
+
+        // insert
+        let _ = D_R.insert_by_biz(meta, "B:sqlite_to:1", "url", "http").await;
+        let rtn = D_R.get_relations(meta, &MCMock {}, &*D_M).await.unwrap();
+        assert_eq!(rtn.len(), 1);
+
+        // update flag
+        let _ = D_R.update_flag(meta, "B:sqlite_to:1", 0).await;
+        let rtn = D_R.get_relations(meta, &MCMock {}, &*D_M).await.unwrap();
+        assert_eq!(rtn.is_empty(), true);
+
+        // delete
+        assert_eq!(D_R.delete_by_biz(meta, "B:sqlite_to:1").await.unwrap(), 1);
+    }
+
+    #[derive(Copy, Clone)]
+    struct MCMock;
+
+    #[async_trait]
+    impl MetaCache for MCMock {
+        async fn get<M>(&self, meta_str: &str, _getter: &M) -> Result<Meta> where M: MetaDao {
+            Meta::from_string(meta_str)
+        }
+    }
+}
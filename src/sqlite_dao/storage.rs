@@ -0,0 +1,63 @@
+use crate::{D_I, D_M, D_R, D_T, DbResult, InstanceDaoImpl, MetaDaoImpl, RelationDaoImpl, Sqlite, SqliteTx, Storage, TaskDaoImpl};
+
+lazy_static! {
+    pub static ref D_S: StorageImpl = StorageImpl {};
+}
+
+pub struct StorageImpl;
+
+#[async_trait]
+impl Storage for StorageImpl {
+    type Instance = InstanceDaoImpl;
+    type Task = TaskDaoImpl;
+    type Meta = MetaDaoImpl;
+    type Relation = RelationDaoImpl;
+    type Tx = SqliteTx;
+
+    fn instance(&self) -> &Self::Instance {
+        &D_I
+    }
+
+    fn task(&self) -> &Self::Task {
+        &D_T
+    }
+
+    fn meta(&self) -> &Self::Meta {
+        &D_M
+    }
+
+    fn relation(&self) -> &Self::Relation {
+        &D_R
+    }
+
+    async fn begin(&self) -> DbResult<Self::Tx> {
+        Sqlite::begin().await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use nature_common::*;
+
+    use crate::{InstanceDao, KeyRange, RangeCondition};
+    use crate::sqlite_dao::init_test_db;
+
+    use super::*;
+
+    async fn save_and_count<S: Storage>(storage: &S, ins: &Instance) -> Result<usize> {
+        storage.instance().insert(ins).await?;
+        let mut para = KeyCondition::new(0, &ins.meta, "", 0);
+        para.id = "".to_string();
+        para.limit = 10;
+        Ok(storage.instance().get_by_key_range(&RangeCondition::from(para)).await?.instances.len())
+    }
+
+    #[tokio::test]
+    async fn generic_storage_test() {
+        init_test_db();
+        let mut ins = Instance::new("sqlite/storage").unwrap();
+        ins.id = 1;
+        assert_eq!(save_and_count(&*D_S, &ins).await.unwrap(), 1);
+        assert_eq!(D_S.instance().delete(&ins).await.unwrap(), 1);
+    }
+}
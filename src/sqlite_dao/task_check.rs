@@ -0,0 +1,73 @@
+use chrono::Local;
+
+use nature_common::Result;
+
+pub use crate::Condition;
+use crate::Sqlite;
+
+pub struct TaskChecker;
+
+impl TaskChecker {
+    pub async fn check(cfg: &Condition) -> Result<usize> {
+        let task_gt = if cfg.key_gt.eq("") { "" } else {
+            " and task_key > :task_gt"
+        };
+        let task_lt = if cfg.key_lt.eq("") { "" } else {
+            " and task_key < :task_lt"
+        };
+        // execute_time is closer to instance.create_time so does not use task.create_time.
+        let time_ge = match cfg.time_ge {
+            Some(_) => " and execute_time >= :time_ge",
+            None => ""
+        };
+        let time_ge_v = match cfg.time_ge {
+            Some(ge) => ge,
+            None => Local::now().naive_local()
+        };
+        // create_time is closer to instance.create_time so does not use task.execute_time.
+        let time_lt = match cfg.time_lt {
+            Some(_) => " and create_time < :time_lt",
+            None => ""
+        };
+        let time_lt_v = match cfg.time_lt {
+            Some(lt) => lt,
+            None => Local::now().naive_local()
+        };
+        let sql = format!("SELECT count(1) as num
+                FROM task
+                WHERE 1=1{}{}{}{}
+                    and task_state = :state
+            ", time_ge, time_lt, task_gt, task_lt);
+        let p = params! {
+            "task_gt" => cfg.key_gt.to_string(),
+            "task_lt" => cfg.key_lt.to_string(),
+            "time_ge" => time_ge_v,
+            "time_lt" => time_lt_v,
+            "state" => i8::from(cfg.state),
+        };
+        let vec = Sqlite::fetch(sql, p, |row| row.get::<_, i64>(0)).await?;
+        Ok(vec[0] as usize)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::sqlite_dao::init_test_db;
+    use crate::TaskState;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn get_test() {
+        init_test_db();
+        let condition = Condition {
+            key_gt: "".to_string(),
+            key_lt: "".to_string(),
+            time_ge: Some(Local::now().naive_local()),
+            time_lt: Some(Local::now().naive_local()),
+            state: TaskState::Finished,
+        };
+        let num = TaskChecker::check(&condition).await.unwrap();
+        assert_eq!(0, num)
+    }
+}
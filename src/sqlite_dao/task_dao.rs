@@ -0,0 +1,583 @@
+use std::convert::TryFrom;
+
+use chrono::{Duration, Local};
+use mysql_async::Value;
+
+use nature_common::{NatureError, Result};
+
+use crate::{BATCH_INSERT_SIZE, check_update_states, DbError, DbResult, lease_expire, OVERDUE_WHERE, overdue_params, Sqlite, state_codes, TaskDao, TaskErrorCondition, TaskErrorDao, TaskQueue, TaskState};
+use crate::raw_models::{multi_row_insert, RawTask, RawTaskError, TASK_COLUMNS, TASK_ERROR_COLUMNS, TASK_FIELDS};
+
+lazy_static! {
+    pub static ref D_T: TaskDaoImpl = TaskDaoImpl {};
+}
+
+pub struct TaskDaoImpl;
+
+#[async_trait]
+impl TaskDao for TaskDaoImpl {
+    async fn insert(&self, raw: &RawTask) -> DbResult<usize> {
+        let sql = r"INSERT INTO task
+            (task_id, task_key, task_type, task_for, task_state, `data`, create_time, execute_time, retried_times, data_codec, priority)
+            VALUES(:task_id, :task_key, :task_type, :task_for, :task_state, :data, :create_time, :execute_time, :retried_times, :data_codec, :priority)";
+
+        let p = Vec::<(String, Value)>::try_from(raw.clone())?;
+        let num: usize = match Sqlite::idu(sql, p).await {
+            Ok(n) => {
+                debug!("---- saved task KEY: {} FOR: {} TYPE: {}", &raw.task_key, &raw.task_for, raw.task_type);
+                n
+            }
+            Err(e) => match e {
+                DbError::Duplicated(_) => {
+                    warn!("==== task repeated. KEY: {} FOR: {} TYPE: {}", &raw.task_key, &raw.task_for, raw.task_type);
+                    0
+                }
+                _ => return {
+                    warn!("**** task insert error. KEY: {} FOR: {} TYPE: {} err: {}", &raw.task_key, &raw.task_for, raw.task_type, e);
+                    Err(e)
+                }
+            }
+        };
+        Ok(num)
+    }
+
+    async fn delete(&self, _record_id: &str) -> DbResult<usize> {
+        let sql = r"DELETE FROM task
+            WHERE task_id=:task_id";
+
+        let p = params! {
+            "task_id" => _record_id,
+        };
+
+        let rtn: usize = Sqlite::idu(sql, p).await?;
+        Ok(rtn)
+    }
+
+    /// delete finished task after `delay` seconds
+    async fn delete_finished(&self, _delay: i64) -> DbResult<usize> {
+        let sql = r"DELETE FROM task
+            WHERE execute_time < :execute_time AND task_state = :finished";
+
+        let _time = Local::now().checked_sub_signed(Duration::seconds(_delay)).unwrap().naive_local();
+        let p = params! {
+            "execute_time" => _time,
+            "finished" => i8::from(TaskState::Finished),
+        };
+
+        let rtn: usize = Sqlite::idu(sql, p).await?;
+        Ok(rtn)
+    }
+
+    async fn raw_to_error(&self, err: &NatureError, raw: &RawTask) -> DbResult<usize> {
+        // `Failed` too, so the one left by a failed delete is moved again
+        let sql = format!("UPDATE task
+            SET task_state=:failed
+            WHERE task_id=:task_id and (task_state in ({}) or task_state=:failed)", state_codes(TaskState::Failed.sources()));
+        let p = params! {
+            "failed" => i8::from(TaskState::Failed),
+            "task_id" => raw.task_id.as_str(),
+        };
+        // the one not found, e.g. failed before being saved, is moved too
+        if Sqlite::idu(sql, p).await? == 0 && self.get(&raw.task_id).await?.is_some() {
+            warn!("==== task can't be failed. KEY: {} FOR: {} TYPE: {}", &raw.task_key, &raw.task_for, raw.task_type);
+            return Ok(0);
+        }
+
+        let sql = r"INSERT INTO task_error
+            (task_id, task_key, task_type, task_for, `data`, create_time, msg, priority)
+            VALUES(:task_id, :task_key, :task_type, :task_for, :data, :create_time, :msg, :priority)";
+
+        let rd = RawTaskError::from_raw(err, raw);
+        let p: Vec<(String, Value)> = rd.into();
+        let num: usize = match Sqlite::idu(sql, p).await {
+            Ok(num) => {
+                self.delete(&raw.task_id).await?;
+                num
+            }
+            Err(DbError::Duplicated(_)) => {
+                self.delete(&raw.task_id).await?;
+                0
+            }
+            Err(e) => return Err(e)
+        };
+        Ok(num)
+    }
+
+    async fn get_overdue_in(&self, queue: &TaskQueue, delay: i64, _limit: i64) -> DbResult<Vec<RawTask>> {
+        let (queue_where, queue_p) = queue.to_where();
+        let sql = format!("SELECT {}
+            FROM task
+            WHERE {}{}
+            ORDER BY priority desc, execute_time
+            LIMIT :limit", TASK_COLUMNS, OVERDUE_WHERE, queue_where);
+
+        let mut p = overdue_params(delay);
+        p.extend(params! {
+            "limit" => _limit,
+        });
+        p.extend(queue_p);
+
+        let rtn = Sqlite::fetch(sql, p, |row| RawTask::try_from(row)).await?;
+        Ok(rtn.into_iter().map(RawTask::decoded).collect::<Result<_>>()?)
+    }
+
+    async fn update_execute_time(&self, _record_id: &str, delay: i64) -> DbResult<usize> {
+        let sql = format!("UPDATE task
+            SET execute_time=:execute_time
+            WHERE task_id=:task_id and task_state in ({})", state_codes(TaskState::ACTIVE));
+
+        let _time = Local::now().checked_add_signed(Duration::seconds(delay)).unwrap().naive_local();
+        let p = params! {
+            "execute_time" => _time,
+            "task_id" => _record_id,
+        };
+        let rtn = Sqlite::idu(sql, p).await?;
+        Ok(rtn)
+    }
+
+    async fn finish_task(&self, _record_id: &str) -> DbResult<usize> {
+        let sql = format!("UPDATE task
+            SET task_state=:finished
+            WHERE task_id=:task_id and task_state in ({})", state_codes(TaskState::Finished.sources()));
+
+        let p = params! {
+            "finished" => i8::from(TaskState::Finished),
+            "task_id" => _record_id,
+        };
+        let rtn = match Sqlite::idu(sql, p).await {
+            Ok(n) => n,
+            Err(e) => {
+                warn!("**** save task error : {}", _record_id);
+                return Err(e);
+            }
+        };
+        Ok(rtn)
+    }
+
+    /// increase one times and delay `delay` seconds
+    async fn increase_times_and_delay(&self, _record_id: &str, delay: i32) -> DbResult<usize> {
+        let sql = format!("UPDATE task
+            SET execute_time=:execute_time, retried_times = retried_times+1, task_state=:pending, lease_owner=NULL, lease_expire=NULL
+            WHERE task_id=:task_id and task_state in ({})", state_codes(TaskState::ACTIVE));
+
+        let _time = Local::now().checked_add_signed(Duration::seconds(delay as i64)).unwrap().naive_local();
+        let p = params! {
+            "execute_time" => _time,
+            "pending" => i8::from(TaskState::Pending),
+            "task_id" => _record_id,
+        };
+        let rtn = Sqlite::idu(sql, p).await?;
+        Ok(rtn)
+    }
+
+    async fn get(&self, _record_id: &str) -> DbResult<Option<RawTask>> {
+        let sql = format!("SELECT {}
+            FROM task
+            WHERE task_id=:task_id", TASK_COLUMNS);
+
+        let p = params! {
+            "task_id" => _record_id,
+        };
+
+        let rtn = Sqlite::fetch(sql, p, |row| RawTask::try_from(row)).await?;
+        match rtn.len() {
+            0 => Ok(None),
+            1 => Ok(Some(rtn[0].clone().decoded()?)),
+            _ => Err(DbError::Logical("should less than 2 record return".to_string())),
+        }
+    }
+
+    async fn update_state(&self, task_id: &str, from: TaskState, to: TaskState) -> DbResult<usize> {
+        from.check_change_to(to)?;
+        let sql = r"UPDATE task
+            SET task_state=:to
+            WHERE task_id=:task_id and task_state=:from";
+
+        let p = params! {
+            "to" => i8::from(to),
+            "task_id" => task_id,
+            "from" => i8::from(from),
+        };
+        let rtn = Sqlite::idu(sql, p).await?;
+        Ok(rtn)
+    }
+
+    async fn update_states(&self, queue: &TaskQueue, from: &[TaskState], to: TaskState) -> DbResult<usize> {
+        check_update_states(queue, from, to)?;
+        let (queue_where, queue_p) = queue.to_where();
+        let sql = format!("UPDATE task
+            SET task_state=:to
+            WHERE task_state in ({}){}", state_codes(from), queue_where);
+
+        let mut p = params! {
+            "to" => i8::from(to),
+        };
+        p.extend(queue_p);
+        let rtn = Sqlite::idu(sql, p).await?;
+        debug!("---- {} tasks changed to {:?}", rtn, to);
+        Ok(rtn)
+    }
+
+    async fn claim(&self, worker: &str, queue: &TaskQueue, delay: i64, lease: i64, limit: i64) -> DbResult<Vec<RawTask>> {
+        let (queue_where, queue_p) = queue.to_where();
+        // no `UPDATE ... LIMIT` for the bundled sqlite, the writers are serialized anyway
+        let sql = format!("UPDATE task
+            SET task_state=:running, lease_owner=:worker, lease_expire=:lease_expire
+            WHERE task_id IN (SELECT task_id FROM task
+                WHERE {}{}
+                ORDER BY priority desc, execute_time
+                LIMIT :limit)", OVERDUE_WHERE, queue_where);
+
+        let _expire = lease_expire(worker, lease)?;
+        let mut p = overdue_params(delay);
+        p.extend(params! {
+            "worker" => worker,
+            "lease_expire" => _expire,
+            "limit" => limit,
+        });
+        p.extend(queue_p);
+        let num = Sqlite::idu(sql, p).await?;
+        if num == 0 {
+            return Ok(vec![]);
+        }
+
+        // `lease_expire` tells this claim apart from the former ones of the same worker
+        let sql = format!("SELECT {}
+            FROM task
+            WHERE lease_owner=:worker and lease_expire=:lease_expire and task_state = :running", TASK_COLUMNS);
+        let p = params! {
+            "worker" => worker,
+            "lease_expire" => _expire,
+            "running" => i8::from(TaskState::Running),
+        };
+        let rtn = Sqlite::fetch(sql, p, |row| RawTask::try_from(row)).await?;
+        debug!("---- {} tasks claimed by {}", rtn.len(), worker);
+        Ok(rtn.into_iter().map(RawTask::decoded).collect::<Result<_>>()?)
+    }
+
+    async fn renew_lease(&self, task_id: &str, worker: &str, lease: i64) -> DbResult<usize> {
+        let sql = r"UPDATE task
+            SET lease_expire=:lease_expire
+            WHERE task_id=:task_id and lease_owner=:worker and task_state=:running";
+
+        let p = params! {
+            "lease_expire" => lease_expire(worker, lease)?,
+            "task_id" => task_id,
+            "worker" => worker,
+            "running" => i8::from(TaskState::Running),
+        };
+        let rtn = Sqlite::idu(sql, p).await?;
+        Ok(rtn)
+    }
+
+    async fn release_lease(&self, task_id: &str, worker: &str) -> DbResult<usize> {
+        let sql = r"UPDATE task
+            SET task_state=:pending, lease_owner=NULL, lease_expire=NULL
+            WHERE task_id=:task_id and lease_owner=:worker and task_state=:running";
+
+        let p = params! {
+            "pending" => i8::from(TaskState::Pending),
+            "task_id" => task_id,
+            "worker" => worker,
+            "running" => i8::from(TaskState::Running),
+        };
+        let rtn = Sqlite::idu(sql, p).await?;
+        Ok(rtn)
+    }
+
+    /// one multi-row `INSERT` for each `BATCH_INSERT_SIZE` rows, falls back to insert one by one
+    /// to tell which are repeated.
+    async fn insert_batch(&self, raws: &[RawTask]) -> DbResult<Vec<usize>> {
+        let mut rtn = Vec::with_capacity(raws.len());
+        for chunk in raws.chunks(*BATCH_INSERT_SIZE) {
+            let rows = chunk.iter().map(|one| Vec::<(String, Value)>::try_from(one.clone())).collect::<Result<_>>()?;
+            let (values, p) = multi_row_insert(&TASK_FIELDS, rows);
+            let sql = format!("INSERT INTO task {}", values);
+            match Sqlite::idu(sql, p).await {
+                Ok(_) => rtn.extend(chunk.iter().map(|_| 1)),
+                Err(DbError::Duplicated(_)) => for one in chunk {
+                    rtn.push(self.insert(one).await?);
+                },
+                Err(e) => return Err(e)
+            }
+        }
+        Ok(rtn)
+    }
+}
+
+
+#[async_trait]
+impl TaskErrorDao for TaskDaoImpl {
+    async fn get_error(&self, task_id: &str) -> DbResult<Option<RawTaskError>> {
+        let sql = format!("SELECT {}
+            FROM task_error
+            WHERE task_id=:task_id", TASK_ERROR_COLUMNS);
+        let p = params! {
+            "task_id" => task_id,
+        };
+        let mut rtn = Sqlite::fetch(sql, p, |row| RawTaskError::try_from(row)).await?;
+        match rtn.len() {
+            0 => Ok(None),
+            1 => Ok(rtn.pop()),
+            _ => Err(DbError::Logical("should less than 2 record return".to_string())),
+        }
+    }
+
+    async fn get_errors(&self, condition: &TaskErrorCondition) -> DbResult<Vec<RawTaskError>> {
+        let (where_clause, mut p) = condition.to_where();
+        let sql = format!("SELECT {}
+            FROM task_error
+            {}
+            ORDER BY task_key
+            LIMIT :limit", TASK_ERROR_COLUMNS, where_clause);
+        p.push(("limit".to_string(), condition.get_limit().into()));
+        Sqlite::fetch(sql, p, |row| RawTaskError::try_from(row)).await
+    }
+
+    async fn delete_error(&self, task_id: &str) -> DbResult<usize> {
+        let sql = r"DELETE FROM task_error
+            WHERE task_id=:task_id";
+        let p = params! {
+            "task_id" => task_id,
+        };
+        Sqlite::idu(sql, p).await
+    }
+
+    async fn purge_errors(&self, condition: &TaskErrorCondition) -> DbResult<usize> {
+        let (where_clause, p) = condition.to_where();
+        let sql = format!("DELETE FROM task_error {}", where_clause);
+        Sqlite::idu(sql, p).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::NaiveDate;
+
+    use crate::{Backoff, RetryPolicy, TaskRetry, TaskType};
+    use crate::sqlite_dao::init_test_db;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn insert_repeat_test() {
+        init_test_db();
+        let task = RawTask {
+            task_id: "sqlite_repeat".to_string(),
+            task_key: "sqlite_repeat".to_string(),
+            ..Default::default()
+        };
+        let num = D_T.insert(&task).await.unwrap();
+        assert_eq!(1, num);
+        let num = D_T.insert(&task).await.unwrap();
+        assert_eq!(0, num);
+        let get_task = D_T.get("sqlite_repeat").await.unwrap();
+        assert!(get_task.is_some());
+        let num = D_T.raw_to_error(&NatureError::LogicalError("my test".to_string()), &task).await.unwrap();
+        assert_eq!(1, num);
+        let get_task = D_T.get("sqlite_repeat").await.unwrap();
+        assert!(get_task.is_none());
+    }
+
+    #[tokio::test]
+    async fn finish_and_overdue_test() {
+        init_test_db();
+        let task = RawTask {
+            task_id: "sqlite_finish".to_string(),
+            task_key: "sqlite_finish".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(D_T.insert(&task).await.unwrap(), 1);
+        let overdue = D_T.get_overdue(1, 100).await.unwrap();
+        assert!(overdue.iter().any(|one| one.task_id == "sqlite_finish"));
+        assert_eq!(D_T.finish_task("sqlite_finish").await.unwrap(), 1);
+        assert_eq!(D_T.finish_task("sqlite_finish").await.unwrap(), 0);
+        let overdue = D_T.get_overdue(1, 100).await.unwrap();
+        assert!(!overdue.iter().any(|one| one.task_id == "sqlite_finish"));
+        assert!(D_T.delete_finished(-1).await.unwrap() >= 1);
+    }
+
+    #[tokio::test]
+    async fn claim_test() {
+        init_test_db();
+        let task = RawTask {
+            task_id: "sqlite_claim".to_string(),
+            task_key: "sqlite_claim".to_string(),
+            execute_time: NaiveDate::from_ymd(2000, 1, 1).and_hms(0, 0, 0),
+            ..Default::default()
+        };
+        assert_eq!(D_T.insert(&task).await.unwrap(), 1);
+        // only the ones overdue for a year, so the tasks of other tests are left alone
+        let delay = -3600 * 24 * 365;
+        let queue = TaskQueue::default();
+        let claimed = D_T.claim("sqlite_worker_a", &queue, delay, 100, 1).await.unwrap();
+        assert_eq!(claimed[0].task_id, "sqlite_claim");
+        assert_eq!(claimed[0].task_state, TaskState::Running);
+        assert_eq!(D_T.claim("sqlite_worker_b", &queue, delay, 100, 100).await.unwrap().len(), 0);
+        let overdue = D_T.get_overdue(1, 100).await.unwrap();
+        assert!(!overdue.iter().any(|one| one.task_id == "sqlite_claim"));
+
+        assert_eq!(D_T.renew_lease("sqlite_claim", "sqlite_worker_b", 100).await.unwrap(), 0);
+        assert_eq!(D_T.renew_lease("sqlite_claim", "sqlite_worker_a", 100).await.unwrap(), 1);
+        assert_eq!(D_T.release_lease("sqlite_claim", "sqlite_worker_a").await.unwrap(), 1);
+        assert_eq!(D_T.get("sqlite_claim").await.unwrap().unwrap().task_state, TaskState::Pending);
+        let overdue = D_T.get_overdue(1, 100).await.unwrap();
+        assert!(overdue.iter().any(|one| one.task_id == "sqlite_claim"));
+        D_T.delete("sqlite_claim").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn priority_test() {
+        init_test_db();
+        let mut queue = TaskQueue {
+            task_for: "B:sqlite/priority:1".to_string(),
+            ..Default::default()
+        };
+        for (key, priority) in &[("B:sqlite/low:1|1||0", 0), ("B:sqlite/high:1|1||0", 5)] {
+            let mut task = RawTask::from_str("data", key, TaskType::Store, &queue.task_for).unwrap();
+            task.priority = *priority;
+            D_T.insert(&task).await.unwrap();
+        }
+        let overdue = D_T.get_overdue_in(&queue, 1, 100).await.unwrap();
+        let keys: Vec<&str> = overdue.iter().map(|t| t.task_key.as_str()).collect();
+        assert_eq!(keys, vec!["B:sqlite/high:1|1||0", "B:sqlite/low:1|1||0"]);
+        assert_eq!(overdue[0].priority, 5);
+        queue.meta_prefix = "B:sqlite/low".to_string();
+        let claimed = D_T.claim("sqlite_worker", &queue, 1, 100, 10).await.unwrap();
+        assert_eq!(claimed.len(), 1);
+        assert_eq!(claimed[0].task_key, "B:sqlite/low:1|1||0");
+        for one in overdue {
+            D_T.delete(&one.task_id).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn state_test() {
+        init_test_db();
+        let task = RawTask::from_str("data", "B:sqlite/state:1|1||0", TaskType::Batch, "B:to:1").unwrap();
+        D_T.insert(&task).await.unwrap();
+        assert_eq!(D_T.set_state(&task.task_id, TaskState::Failed).await.unwrap(), 1);
+        let saved = D_T.get(&task.task_id).await.unwrap().unwrap();
+        assert_eq!(saved.task_state, TaskState::Failed);
+        assert_eq!(saved.task_type, TaskType::Batch);
+        assert!(D_T.update_state(&task.task_id, TaskState::Failed, TaskState::Running).await.is_err());
+        assert_eq!(D_T.finish_task(&task.task_id).await.unwrap(), 0);
+        assert_eq!(D_T.set_state(&task.task_id, TaskState::Pending).await.unwrap(), 1);
+        assert_eq!(D_T.finish_task(&task.task_id).await.unwrap(), 1);
+        D_T.delete(&task.task_id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn cancel_and_pause_test() {
+        init_test_db();
+        let mut queue = TaskQueue {
+            task_for: "B:sqlite/down:1".to_string(),
+            ..Default::default()
+        };
+        let mut ids = vec![];
+        for key in &["B:sqlite/pause:1|1||0", "B:sqlite/pause:1|2||0"] {
+            let task = RawTask::from_str("data", key, TaskType::Convert, &queue.task_for).unwrap();
+            D_T.insert(&task).await.unwrap();
+            ids.push(task.task_id);
+        }
+        assert_eq!(D_T.pause_in(&queue).await.unwrap(), 2);
+        assert_eq!(D_T.get_overdue_in(&queue, 1, 100).await.unwrap().len(), 0);
+        assert_eq!(D_T.resume(&ids[0]).await.unwrap(), 1);
+        assert_eq!(D_T.get_overdue_in(&queue, 1, 100).await.unwrap()[0].task_id, ids[0]);
+        assert_eq!(D_T.cancel(&ids[0]).await.unwrap(), 1);
+        queue.meta_prefix = "B:sqlite/pause:1".to_string();
+        assert_eq!(D_T.cancel_in(&queue).await.unwrap(), 1);
+        assert_eq!(D_T.get(&ids[1]).await.unwrap().unwrap().task_state, TaskState::Cancelled);
+        assert!(D_T.resume_in(&TaskQueue::default()).await.is_err());
+        for id in ids {
+            D_T.delete(&id).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn insert_batch_test() {
+        init_test_db();
+        let raws: Vec<RawTask> = (0..3).map(|i| {
+            RawTask {
+                task_id: format!("sqlite_batch_{}", i),
+                task_key: format!("sqlite_batch_{}", i),
+                ..Default::default()
+            }
+        }).collect();
+        assert_eq!(D_T.insert_batch(&raws[0..2]).await.unwrap(), vec![1, 1]);
+        // the repeated one is told apart from the others
+        assert_eq!(D_T.insert_batch(&raws).await.unwrap(), vec![0, 0, 1]);
+        assert!(D_T.get("sqlite_batch_2").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn retry_by_test() {
+        init_test_db();
+        let err = NatureError::EnvironmentError("executor is down".to_string());
+        let policy = RetryPolicy {
+            max_attempts: 1,
+            backoff: Backoff::Fixed,
+            delay: 10,
+            ..Default::default()
+        };
+        let task = RawTask::from_str("data", "B:sqlite/retry:1|1||0", TaskType::Store, "B:to:1").unwrap();
+        assert_eq!(D_T.insert(&task).await.unwrap(), 1);
+        assert_eq!(D_T.retry_by(&task, &policy, &err).await.unwrap(), TaskRetry::Delayed(10));
+        let task = D_T.get(&task.task_id).await.unwrap().unwrap();
+        assert_eq!(task.retried_times, 1);
+        assert!(task.execute_time > Local::now().naive_local());
+        assert_eq!(D_T.retry_by(&task, &policy, &err).await.unwrap(), TaskRetry::Exhausted);
+        assert!(D_T.get(&task.task_id).await.unwrap().is_none());
+        let error = D_T.get_error(&task.task_id).await.unwrap().unwrap();
+        assert!(error.msg.contains("executor is down"));
+
+        // the cancelled is neither delayed nor failed
+        let task = RawTask::from_str("data", "B:sqlite/retry:1|2||0", TaskType::Store, "B:to:1").unwrap();
+        D_T.insert(&task).await.unwrap();
+        D_T.cancel(&task.task_id).await.unwrap();
+        assert_eq!(D_T.increase_times_and_delay(&task.task_id, 10).await.unwrap(), 0);
+        assert_eq!(D_T.update_execute_time(&task.task_id, 10).await.unwrap(), 0);
+        assert_eq!(D_T.raw_to_error(&err, &task).await.unwrap(), 0);
+        assert_eq!(D_T.get(&task.task_id).await.unwrap().unwrap().task_state, TaskState::Cancelled);
+        assert!(D_T.get_error(&task.task_id).await.unwrap().is_none());
+
+        // the one not saved is still moved to task_error
+        let task = RawTask::from_str("data", "B:sqlite/retry:1|3||0", TaskType::Store, "B:to:1").unwrap();
+        assert_eq!(D_T.raw_to_error(&err, &task).await.unwrap(), 1);
+        assert!(D_T.get_error(&task.task_id).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn task_error_test() {
+        init_test_db();
+        let err = NatureError::LogicalError("sqlite error".to_string());
+        let mut ids = vec![];
+        for (key, task_type) in &[("B:sqlite/error:1|1||0", TaskType::Store), ("B:sqlite/error:1|2||0", TaskType::Convert)] {
+            let mut task = RawTask::from_str("data", key, *task_type, "B:to:1").unwrap();
+            task.retried_times = 5;
+            task.priority = 2;
+            D_T.insert(&task).await.unwrap();
+            assert_eq!(D_T.raw_to_error(&err, &task).await.unwrap(), 1);
+            ids.push(task.task_id);
+        }
+        let error = D_T.get_error(&ids[0]).await.unwrap().unwrap();
+        assert_eq!(error.task_for, "B:to:1");
+        assert_eq!(error.priority, 2);
+        let mut condition = TaskErrorCondition {
+            meta: "B:sqlite/error:1".to_string(),
+            limit: 10,
+            ..Default::default()
+        };
+        assert_eq!(D_T.get_errors(&condition).await.unwrap().len(), 2);
+        condition.task_type = Some(TaskType::Convert);
+        condition.time_lt = Some(Local::now().naive_local());
+        assert_eq!(D_T.get_errors(&condition).await.unwrap()[0].task_id, ids[1]);
+
+        assert_eq!(D_T.requeue(&ids[0..1]).await.unwrap(), 1);
+        let task = D_T.get(&ids[0]).await.unwrap().unwrap();
+        assert_eq!((task.retried_times, task.priority), (0, 2));
+        assert!(D_T.get_error(&ids[0]).await.unwrap().is_none());
+        assert_eq!(D_T.purge_errors(&condition).await.unwrap(), 1);
+        assert!(D_T.get_error(&ids[1]).await.unwrap().is_none());
+    }
+}
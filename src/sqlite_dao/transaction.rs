@@ -0,0 +1,229 @@
+use std::convert::TryFrom;
+
+use mysql_async::{Params, Value};
+use tokio::runtime::Handle;
+
+use nature_common::Instance;
+
+use crate::{DbError, DbResult, Sqlite, state_codes, StorageTx, TaskState, TxBlobs};
+use crate::raw_models::{RawInstance, RawTask};
+
+use super::{CONN, ConnGuard, execute, execute_named, to_named};
+
+/// Holds the connection until `commit` or `rollback`, so the other operations will wait for it.
+/// Dropping it without `commit` rolls back, and the blobs saved are removed.
+pub struct SqliteTx {
+    conn: Option<ConnGuard>,
+    blobs: TxBlobs,
+}
+
+impl Sqlite {
+    pub async fn begin() -> DbResult<SqliteTx> {
+        let guard = CONN.clone().lock_owned().await;
+        match execute(guard, |conn| conn.execute_batch("BEGIN IMMEDIATE")).await {
+            (Some(guard), Ok(_)) => Ok(SqliteTx { conn: Some(guard), blobs: TxBlobs::default() }),
+            (_, Err(e)) => Err(e),
+            (None, Ok(_)) => Err(DbError::ConnectionLost("lost connection".to_string()))
+        }
+    }
+}
+
+impl SqliteTx {
+    async fn batch(&mut self, sql: &'static str) -> DbResult<()> {
+        let guard = match self.conn.take() {
+            Some(guard) => guard,
+            None => return Err(DbError::Logical("transaction is not available".to_string()))
+        };
+        execute(guard, move |conn| conn.execute_batch(sql)).await.1
+    }
+
+    async fn idu<P: Into<Params>>(&mut self, query: &str, params: P) -> DbResult<usize> {
+        let guard = match self.conn.take() {
+            Some(guard) => guard,
+            None => return Err(DbError::Logical("transaction is not available".to_string()))
+        };
+        let sql = query.to_string();
+        let params = to_named(params.into())?;
+        let (guard, rtn) = execute(guard, move |conn| execute_named(conn, &sql, &params)).await;
+        self.conn = guard;
+        if rtn.is_err() {
+            let _ = self.batch("ROLLBACK").await;
+        }
+        rtn
+    }
+}
+
+#[async_trait]
+impl StorageTx for SqliteTx {
+    async fn insert_instance(&mut self, instance: &Instance) -> DbResult<usize> {
+        let sql = r"INSERT INTO instances
+            (ins_key, content, context, states, state_version, create_time, sys_context, from_key, content_ref, content_codec)
+            VALUES(:ins_key, :content,:context,:states,:state_version,:create_time,:sys_context,:from_key,:content_ref,:content_codec)";
+        let mut raw = RawInstance::new(instance)?;
+        self.blobs.push(raw.put_blob().await?);
+        let vec: Vec<(String, Value)> = raw.into();
+        let rtn = self.idu(sql, vec).await?;
+        debug!("Saved instance in transaction : {}", instance.get_key());
+        Ok(rtn)
+    }
+
+    async fn insert_task(&mut self, raw: &RawTask) -> DbResult<usize> {
+        // a duplicate-key error would break the transaction, so make it affect nothing instead.
+        let sql = r"INSERT INTO task
+            (task_id, task_key, task_type, task_for, task_state, `data`, create_time, execute_time, retried_times, data_codec, priority)
+            VALUES(:task_id, :task_key, :task_type, :task_for, :task_state, :data, :create_time, :execute_time, :retried_times, :data_codec, :priority)
+            ON CONFLICT DO NOTHING";
+        let p = Vec::<(String, Value)>::try_from(raw.clone())?;
+        let num = self.idu(sql, p).await?;
+        if num == 0 {
+            warn!("==== task repeated. KEY: {} FOR: {} TYPE: {}", &raw.task_key, &raw.task_for, raw.task_type);
+        }
+        Ok(num)
+    }
+
+    async fn finish_task(&mut self, task_id: &str) -> DbResult<usize> {
+        let sql = format!("UPDATE task
+            SET task_state=:finished
+            WHERE task_id=:task_id and task_state in ({})", state_codes(TaskState::Finished.sources()));
+        let p = params! {
+            "finished" => i8::from(TaskState::Finished),
+            "task_id" => task_id,
+        };
+        self.idu(&sql, p).await
+    }
+
+    async fn commit(mut self) -> DbResult<()> {
+        self.batch("COMMIT").await?;
+        self.blobs.commit();
+        Ok(())
+    }
+
+    async fn rollback(mut self) -> DbResult<()> {
+        let rtn = self.batch("ROLLBACK").await;
+        self.blobs.remove().await;
+        rtn
+    }
+}
+
+/// Dropping it without `commit` rolls back. The `ROLLBACK` runs in the blocking pool like the other
+/// statements, and the connection is held until it is done, so the following operations still wait for it.
+impl Drop for SqliteTx {
+    fn drop(&mut self) {
+        let guard = match self.conn.take() {
+            Some(guard) => guard,
+            None => return
+        };
+        let rollback = move || {
+            if let Some(conn) = guard.as_ref() {
+                // fails harmlessly if it had been rolled back by an error
+                let _ = conn.execute_batch("ROLLBACK");
+            }
+        };
+        match Handle::try_current() {
+            // not awaited, the connection is given back when it is done
+            Ok(handle) => drop(handle.spawn_blocking(rollback)),
+            Err(_) => rollback()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use nature_common::KeyCondition;
+
+    use crate::{D_I, D_T, init_test_blob_store, InstanceDao, TaskDao};
+    use crate::models::define::INSTANCE_CONTENT_MAX_LENGTH;
+    use crate::sqlite_dao::init_test_db;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn commit_test() {
+        init_test_db();
+        let old = RawTask {
+            task_id: "sqlite_tx_old".to_string(),
+            task_key: "sqlite_tx_old".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(D_T.insert(&old).await.unwrap(), 1);
+
+        let new = RawTask {
+            task_id: "sqlite_tx_new".to_string(),
+            task_key: "sqlite_tx_new".to_string(),
+            ..Default::default()
+        };
+        let mut news = vec![new.clone(), old.clone()];
+        let mut tx = Sqlite::begin().await.unwrap();
+        RawTask::save_batch_tx(&mut news, &old.task_id, &mut tx).await.unwrap();
+        assert_eq!(news, vec![new]);
+        tx.commit().await.unwrap();
+
+        assert_eq!(D_T.get("sqlite_tx_old").await.unwrap().unwrap().task_state, TaskState::Finished);
+        assert!(D_T.get("sqlite_tx_new").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn rollback_test() {
+        init_test_db();
+        let mut ins = Instance::new("sqlite/tx").unwrap();
+        ins.id = 1;
+        let task = RawTask {
+            task_id: "sqlite_tx_rollback".to_string(),
+            task_key: "sqlite_tx_rollback".to_string(),
+            ..Default::default()
+        };
+
+        let mut tx = Sqlite::begin().await.unwrap();
+        assert_eq!(tx.insert_instance(&ins).await.unwrap(), 1);
+        assert_eq!(tx.insert_task(&task).await.unwrap(), 1);
+        tx.rollback().await.unwrap();
+        assert!(D_T.get("sqlite_tx_rollback").await.unwrap().is_none());
+
+        // the blob saved is removed too
+        let store = init_test_blob_store();
+        ins.content = "t".repeat(*INSTANCE_CONTENT_MAX_LENGTH + 1);
+        let mut tx = Sqlite::begin().await.unwrap();
+        assert_eq!(tx.insert_instance(&ins).await.unwrap(), 1);
+        assert_eq!(store.of(&ins.content).len(), 1);
+        tx.rollback().await.unwrap();
+        assert!(store.of(&ins.content).is_empty());
+        ins.content = "".to_string();
+
+        // repeated task doesn't break the transaction
+        let mut tx = Sqlite::begin().await.unwrap();
+        assert_eq!(tx.insert_task(&task).await.unwrap(), 1);
+        assert_eq!(tx.insert_task(&task).await.unwrap(), 0);
+        tx.rollback().await.unwrap();
+        assert!(D_T.get("sqlite_tx_rollback").await.unwrap().is_none());
+
+        // an error ends the transaction
+        let mut tx = Sqlite::begin().await.unwrap();
+        assert_eq!(tx.insert_instance(&ins).await.unwrap(), 1);
+        assert!(tx.insert_instance(&ins).await.is_err());
+        assert!(tx.finish_task("any").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn drop_test() {
+        init_test_db();
+        let mut ins = Instance::new("sqlite/tx/drop").unwrap();
+        ins.id = 1;
+        let task = RawTask {
+            task_id: "sqlite_tx_drop".to_string(),
+            task_key: "sqlite_tx_drop".to_string(),
+            ..Default::default()
+        };
+        let mut tx = Sqlite::begin().await.unwrap();
+        assert_eq!(tx.insert_instance(&ins).await.unwrap(), 1);
+        assert_eq!(tx.insert_task(&task).await.unwrap(), 1);
+        drop(tx);
+        assert!(D_T.get("sqlite_tx_drop").await.unwrap().is_none());
+        assert!(D_I.get_by_id(KeyCondition::from(&ins)).await.unwrap().is_none());
+
+        // the connection can be used by the next transaction
+        let mut tx = Sqlite::begin().await.unwrap();
+        assert_eq!(tx.insert_task(&task).await.unwrap(), 1);
+        tx.commit().await.unwrap();
+        assert!(D_T.get("sqlite_tx_drop").await.unwrap().is_some());
+    }
+}